@@ -0,0 +1,122 @@
+//! Building the render context from JSON, TOML or YAML files.
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+use clap::ValueEnum;
+use tera::Context;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Json => "JSON",
+            Format::Toml => "TOML",
+            Format::Yaml => "YAML",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses the content as a map, which is what a context needs to be.
+pub fn parse(content: &str, format: Format) -> Result<Context, Box<dyn Error>> {
+    let context = match format {
+        Format::Json => {
+            Context::from_serialize(&serde_json::from_str::<serde_json::Value>(content)?)
+        }
+        Format::Toml => Context::from_serialize(&toml::from_str::<toml::Table>(content)?),
+        Format::Yaml => {
+            Context::from_serialize(&serde_yaml_ng::from_str::<serde_yaml_ng::Value>(content)?)
+        }
+    };
+    Ok(context?)
+}
+
+/// Reads all the given files, `-` being stdin, and merges them in order: a key defined in
+/// several of them gets the value from the last one.
+pub fn load(paths: &[impl AsRef<Path>], stdin_format: Format) -> Result<Context, Box<dyn Error>> {
+    let mut context = Context::new();
+    for path in paths {
+        let path = path.as_ref();
+        let (content, format) = if path == Path::new("-") {
+            let mut content = String::new();
+            std::io::stdin().read_to_string(&mut content)?;
+            (content, stdin_format)
+        } else {
+            let format = Format::from_path(path).ok_or_else(|| {
+                format!(
+                    "Cannot guess the format of the context file `{}`: it should end with .json, .toml, .yaml or .yml",
+                    path.display()
+                )
+            })?;
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read `{}`: {e}", path.display()))?;
+            (content, format)
+        };
+        let parsed = parse(&content, format)
+            .map_err(|e| format!("Failed to parse `{}` as {format}: {e}", path.display()))?;
+        context.extend(parsed);
+    }
+    Ok(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tera::Tera;
+
+    fn render(context: &Context) -> String {
+        Tera::one_off("{{ name }} {{ list | join(sep=',') }}", context, false).unwrap()
+    }
+
+    #[test]
+    fn can_parse_all_formats() {
+        let json = parse(r#"{"name": "Bob", "list": [1, 2]}"#, Format::Json).unwrap();
+        let toml = parse("name = \"Bob\"\nlist = [1, 2]", Format::Toml).unwrap();
+        let yaml = parse("name: Bob\nlist:\n  - 1\n  - 2", Format::Yaml).unwrap();
+        for context in [json, toml, yaml] {
+            assert_eq!(render(&context), "Bob 1,2");
+        }
+    }
+
+    #[test]
+    fn errors_on_non_map() {
+        assert!(parse("[1, 2]", Format::Json).is_err());
+        assert!(parse("- 1", Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn merges_files_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.toml");
+        std::fs::write(&first, "name = \"Bob\"\nlist = [1]").unwrap();
+        let second = dir.path().join("second.yml");
+        std::fs::write(&second, "name: Alice").unwrap();
+
+        let context = load(&[&first, &second], Format::Json).unwrap();
+        assert_eq!(render(&context), "Alice 1");
+
+        let unknown = dir.path().join("context.txt");
+        std::fs::write(&unknown, "").unwrap();
+        let err = load(&[&unknown], Format::Json).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot guess the format"));
+    }
+}
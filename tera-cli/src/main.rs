@@ -0,0 +1,489 @@
+//! The `tera` command: render templates from the command line or check that they are valid.
+mod context;
+
+use std::error::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use tera::{Delimiters, Tera};
+
+use crate::context::Format;
+
+#[derive(Debug, Parser)]
+#[command(name = "tera", version, about = "Render Tera templates")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Render a template, a block or a component
+    Render(RenderArgs),
+    /// Load the templates and report any error, without rendering anything
+    Check(CheckArgs),
+    /// Rewrite templates with a canonical layout
+    Fmt(FmtArgs),
+}
+
+#[derive(Debug, Args)]
+struct DelimiterArgs {
+    /// Defaults to `{%`
+    #[arg(long, value_name = "DELIMITER", help_heading = "Delimiters")]
+    block_start: Option<String>,
+    /// Defaults to `%}`
+    #[arg(long, value_name = "DELIMITER", help_heading = "Delimiters")]
+    block_end: Option<String>,
+    /// Defaults to `{{`
+    #[arg(long, value_name = "DELIMITER", help_heading = "Delimiters")]
+    variable_start: Option<String>,
+    /// Defaults to `}}`
+    #[arg(long, value_name = "DELIMITER", help_heading = "Delimiters")]
+    variable_end: Option<String>,
+    /// Defaults to `{#`
+    #[arg(long, value_name = "DELIMITER", help_heading = "Delimiters")]
+    comment_start: Option<String>,
+    /// Defaults to `#}`
+    #[arg(long, value_name = "DELIMITER", help_heading = "Delimiters")]
+    comment_end: Option<String>,
+}
+
+impl DelimiterArgs {
+    fn delimiters(&self) -> Delimiters {
+        let mut delimiters = Delimiters::default();
+        let overrides = [
+            (&mut delimiters.block_start, &self.block_start),
+            (&mut delimiters.block_end, &self.block_end),
+            (&mut delimiters.variable_start, &self.variable_start),
+            (&mut delimiters.variable_end, &self.variable_end),
+            (&mut delimiters.comment_start, &self.comment_start),
+            (&mut delimiters.comment_end, &self.comment_end),
+        ];
+        for (delimiter, value) in overrides {
+            if let Some(value) = value {
+                *delimiter = value.clone().into();
+            }
+        }
+        delimiters
+    }
+}
+
+#[derive(Debug, Args)]
+struct TemplateArgs {
+    /// The glob to load templates from, eg `templates/**/*.html`. Templates are named by their
+    /// path relative to the glob directory, like `Tera::load_from_glob`
+    #[arg(short, long, value_name = "GLOB")]
+    templates: Option<String>,
+    /// Only autoescape templates ending with that suffix, can be repeated.
+    /// Defaults to `.html`, `.htm` and `.xml`
+    #[arg(long = "autoescape", value_name = "SUFFIX")]
+    autoescape: Vec<String>,
+    /// Don't autoescape any template
+    #[arg(long, conflicts_with = "autoescape")]
+    no_autoescape: bool,
+    #[command(flatten)]
+    delimiters: DelimiterArgs,
+}
+
+impl TemplateArgs {
+    /// Creates a Tera instance with the templates from the glob and the given files, the files
+    /// being named by their path.
+    fn load(&self, files: &[PathBuf]) -> tera::TeraResult<Tera> {
+        let mut tera = Tera::default();
+        tera.set_delimiters(self.delimiters.delimiters())?;
+        if self.no_autoescape {
+            tera.autoescape_on(Vec::<String>::new());
+        } else if !self.autoescape.is_empty() {
+            tera.autoescape_on(self.autoescape.clone());
+        }
+        if let Some(glob) = &self.templates {
+            tera.load_from_glob(glob)?;
+        }
+        tera.add_template_files(files.iter().map(|p| (p, None::<&str>)))?;
+        Ok(tera)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RenderArgs {
+    #[command(flatten)]
+    templates: TemplateArgs,
+    /// The template to render: its name if `--templates` is used, otherwise its path
+    #[arg(required_unless_present = "component")]
+    template: Option<String>,
+    /// Only render that block of the template
+    #[arg(long, value_name = "NAME")]
+    block: Option<String>,
+    /// Render that component instead of a template, with the context as arguments
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["template", "block"])]
+    component: Option<String>,
+    /// A JSON, TOML or YAML file to use as context, `-` for stdin. Can be repeated: values from
+    /// later files override the ones from earlier files
+    #[arg(short, long = "context", value_name = "FILE")]
+    contexts: Vec<PathBuf>,
+    /// The format of the context given on stdin
+    #[arg(long, value_name = "FORMAT", default_value = "json")]
+    stdin_format: Format,
+    /// Write the output to that file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct CheckArgs {
+    #[command(flatten)]
+    templates: TemplateArgs,
+    /// Template files to check, in addition to the ones matching `--templates`
+    files: Vec<PathBuf>,
+    /// Print the errors as JSON diagnostics
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct FmtArgs {
+    /// The glob of the templates to format, eg `templates/**/*.html`
+    #[arg(short, long, value_name = "GLOB")]
+    templates: Option<String>,
+    /// Template files to format, in addition to the ones matching `--templates`.
+    /// Use `-` to format stdin and print the result to stdout
+    #[arg(required_unless_present = "templates")]
+    files: Vec<PathBuf>,
+    /// Don't write anything, only list the templates that are not formatted and exit with an
+    /// error if there are any
+    #[arg(long)]
+    check: bool,
+    #[command(flatten)]
+    delimiters: DelimiterArgs,
+}
+
+fn render(args: &RenderArgs) -> Result<String, Box<dyn Error>> {
+    let files: Vec<PathBuf> = match (&args.templates.templates, &args.template) {
+        (None, Some(path)) => vec![PathBuf::from(path)],
+        _ => Vec::new(),
+    };
+    let tera = args.templates.load(&files)?;
+    let context = context::load(&args.contexts, args.stdin_format)?;
+
+    let output = match (&args.component, &args.template, &args.block) {
+        (Some(component), _, _) => {
+            tera.render_component(component, &context, None, !args.templates.no_autoescape)?
+        }
+        (None, Some(template), Some(block)) => tera.render_block(template, block, &context)?,
+        (None, Some(template), None) => tera.render(template, &context)?,
+        (None, None, _) => unreachable!("clap requires a template or a component"),
+    };
+    Ok(output)
+}
+
+fn check(args: &CheckArgs) -> Result<usize, tera::Error> {
+    let tera = args.templates.load(&args.files)?;
+    let count = tera.get_template_names().count();
+    if count == 0 {
+        return Err(tera::Error::message("No templates found"));
+    }
+    Ok(count)
+}
+
+/// Formats the templates and returns the paths of the ones that changed. Nothing is written
+/// in check mode.
+fn fmt(args: &FmtArgs) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut tera = Tera::default();
+    tera.set_delimiters(args.delimiters.delimiters())?;
+    let mut paths = args.files.clone();
+    if let Some(glob) = &args.templates {
+        paths.extend(
+            tera::load_from_glob(glob)?
+                .into_iter()
+                .map(|(path, _)| path),
+        );
+    }
+    if paths.is_empty() {
+        return Err("No templates found".into());
+    }
+
+    let mut changed = Vec::new();
+    for path in paths {
+        let is_stdin = path == Path::new("-");
+        let source = if is_stdin {
+            let mut source = String::new();
+            std::io::stdin().read_to_string(&mut source)?;
+            source
+        } else {
+            std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read `{}`: {e}", path.display()))?
+        };
+        let name = if is_stdin {
+            "<stdin>".to_string()
+        } else {
+            path.display().to_string()
+        };
+        let formatted = tera.format_template(&name, &source)?;
+
+        if is_stdin && !args.check {
+            print!("{formatted}");
+        }
+        if formatted == source {
+            continue;
+        }
+        if !is_stdin && !args.check {
+            std::fs::write(&path, formatted)
+                .map_err(|e| format!("Failed to write to `{}`: {e}", path.display()))?;
+        }
+        changed.push(path);
+    }
+    Ok(changed)
+}
+
+fn print_error(err: &dyn Error) {
+    eprintln!("{err}");
+    let mut source = err.source();
+    while let Some(err) = source {
+        eprintln!("Caused by: {err}");
+        source = err.source();
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Render(args) => {
+            let result = render(&args).and_then(|output| match &args.output {
+                Some(path) => std::fs::write(path, output)
+                    .map_err(|e| format!("Failed to write to `{}`: {e}", path.display()).into()),
+                None => {
+                    print!("{output}");
+                    Ok(())
+                }
+            });
+            match result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    print_error(e.as_ref());
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Check(args) => match check(&args) {
+            Ok(count) => {
+                eprintln!("{count} templates checked, no errors found");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                if args.json {
+                    let diagnostics = serde_json::to_string_pretty(&e.diagnostics())
+                        .expect("diagnostics can be serialized");
+                    println!("{diagnostics}");
+                } else {
+                    print_error(&e);
+                }
+                ExitCode::FAILURE
+            }
+        },
+        Command::Fmt(args) => match fmt(&args) {
+            Ok(changed) => {
+                for path in changed.iter().filter(|p| *p != Path::new("-")) {
+                    if args.check {
+                        eprintln!("Would reformat {}", path.display());
+                    } else {
+                        eprintln!("Reformatted {}", path.display());
+                    }
+                }
+                if args.check && !changed.is_empty() {
+                    ExitCode::FAILURE
+                } else {
+                    ExitCode::SUCCESS
+                }
+            }
+            Err(e) => {
+                print_error(e.as_ref());
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Command {
+        Cli::try_parse_from(std::iter::once("tera").chain(args.iter().copied()))
+            .unwrap()
+            .command
+    }
+
+    fn render_args(args: &[&str]) -> RenderArgs {
+        match parse(args) {
+            Command::Render(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn setup() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [
+            (
+                "templates/base.html",
+                "<title>{% block title %}{{ name }}{% endblock %}</title>",
+            ),
+            (
+                "templates/index.html",
+                "{%extends 'base.html'%}{% block title %}Hi {{name}}{% endblock %}",
+            ),
+            (
+                "templates/components.html",
+                "{% component greet(name: string, punct = '!') %}Hello {{ name }}{{ punct }}{% endcomponent %}",
+            ),
+            ("config.toml.tera", "[server]\nhost = \"<< host >>\"\n"),
+            (
+                "context.json",
+                r#"{"name": "<Bob>", "host": "example.com"}"#,
+            ),
+            ("greet.yaml", "name: <Bob>"),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn path(dir: &Path, path: &str) -> String {
+        dir.join(path).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn can_render_templates_blocks_and_components() {
+        let dir = setup();
+        let glob = path(dir.path(), "templates/**/*.html");
+        let ctx = path(dir.path(), "context.json");
+
+        let args = render_args(&["render", "-t", &glob, "index.html", "-c", &ctx]);
+        assert_eq!(render(&args).unwrap(), "<title>Hi &lt;Bob&gt;</title>");
+
+        let args = render_args(&[
+            "render",
+            "-t",
+            &glob,
+            "index.html",
+            "--block",
+            "title",
+            "-c",
+            &ctx,
+            "--no-autoescape",
+        ]);
+        assert_eq!(render(&args).unwrap(), "Hi <Bob>");
+
+        let args = render_args(&[
+            "render",
+            "-t",
+            &glob,
+            "--component",
+            "greet",
+            "-c",
+            &path(dir.path(), "greet.yaml"),
+        ]);
+        assert_eq!(render(&args).unwrap(), "Hello &lt;Bob&gt;!");
+    }
+
+    #[test]
+    fn can_render_a_file_with_custom_delimiters() {
+        let dir = setup();
+        let args = render_args(&[
+            "render",
+            &path(dir.path(), "config.toml.tera"),
+            "-c",
+            &path(dir.path(), "context.json"),
+            "--variable-start",
+            "<<",
+            "--variable-end",
+            ">>",
+        ]);
+        assert_eq!(render(&args).unwrap(), "[server]\nhost = \"example.com\"\n");
+    }
+
+    #[test]
+    fn can_check_templates() {
+        let dir = setup();
+        let glob = path(dir.path(), "templates/**/*.html");
+        let Command::Check(args) = parse(&["check", "-t", &glob]) else {
+            unreachable!()
+        };
+        assert_eq!(check(&args).unwrap(), 3);
+
+        std::fs::write(dir.path().join("templates/broken.html"), "{{ a | nope }}").unwrap();
+        let err = check(&args).unwrap_err();
+        assert_eq!(err.diagnostics()[0].code(), "unknown-filter");
+
+        let Command::Check(args) = parse(&["check", "-t", &path(dir.path(), "nothing/*")]) else {
+            unreachable!()
+        };
+        assert!(check(&args).is_err());
+    }
+
+    #[test]
+    fn can_format_templates() {
+        let dir = setup();
+        let glob = path(dir.path(), "templates/**/*.html");
+        let index = dir
+            .path()
+            .join("templates/index.html")
+            .canonicalize()
+            .unwrap();
+        let original = std::fs::read_to_string(&index).unwrap();
+        let Command::Fmt(args) = parse(&["fmt", "-t", &glob, "--check"]) else {
+            unreachable!()
+        };
+        assert_eq!(fmt(&args).unwrap(), vec![index.clone()]);
+        assert_eq!(std::fs::read_to_string(&index).unwrap(), original);
+
+        let Command::Fmt(args) = parse(&["fmt", "-t", &glob]) else {
+            unreachable!()
+        };
+        assert_eq!(fmt(&args).unwrap(), vec![index.clone()]);
+        assert_eq!(
+            std::fs::read_to_string(&index).unwrap(),
+            "{% extends 'base.html' %}{% block title %}Hi {{ name }}{% endblock %}"
+        );
+        assert!(fmt(&args).unwrap().is_empty());
+
+        let config = path(dir.path(), "config.toml.tera");
+        let Command::Fmt(args) = parse(&[
+            "fmt",
+            &config,
+            "--variable-start",
+            "<<",
+            "--variable-end",
+            ">>",
+        ]) else {
+            unreachable!()
+        };
+        assert!(fmt(&args).unwrap().is_empty());
+    }
+
+    #[test]
+    fn validates_arguments() {
+        let cli = |args: &[&str]| {
+            Cli::try_parse_from(std::iter::once("tera").chain(args.iter().copied()))
+        };
+        assert!(cli(&["render"]).is_err());
+        assert!(cli(&["render", "a.html", "--component", "b"]).is_err());
+        assert!(
+            cli(&[
+                "render",
+                "a.html",
+                "--autoescape",
+                ".txt",
+                "--no-autoescape"
+            ])
+            .is_err()
+        );
+        assert!(cli(&["render", "a.html", "--stdin-format", "xml"]).is_err());
+        assert!(cli(&["fmt"]).is_err());
+        assert!(cli(&["fmt", "--check", "-t", "*.html"]).is_ok());
+    }
+}
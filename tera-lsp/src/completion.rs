@@ -0,0 +1,185 @@
+//! Guessing what to complete from the text before the cursor.
+//!
+//! This doesn't use the parser since the template is usually not valid while typing.
+
+/// What kind of name is being typed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Completion {
+    Filter,
+    Test,
+    Function,
+    Template,
+    Component,
+    /// The arguments of a component call, minus the ones already given
+    ComponentArg {
+        component: String,
+        given: Vec<String>,
+    },
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the text of the tag the offset is in, from its opening delimiter to the offset.
+fn current_tag(source: &str, offset: usize) -> Option<&str> {
+    let before = &source[..offset];
+    let start = before.rfind("{{").max(before.rfind("{%"))?;
+    let tag = &before[start..];
+    if tag.contains("}}") || tag.contains("%}") {
+        return None;
+    }
+    Some(tag)
+}
+
+/// Walks through the tag, skipping strings and `{...}` expressions in component attributes.
+/// Returns whether the end of the text is in a string and the words found at the top level.
+fn scan(text: &str) -> (bool, Vec<&str>) {
+    let mut quote = None;
+    let mut depth = 0;
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (i, c) in text.char_indices() {
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        let in_word = depth == 0 && is_ident_char(c);
+        match (in_word, word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                words.push(&text[start..i]);
+                word_start = None;
+            }
+            _ => (),
+        }
+        match c {
+            '"' | '\'' | '`' => quote = Some(c),
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    if let Some(start) = word_start {
+        words.push(&text[start..]);
+    }
+    (quote.is_some(), words)
+}
+
+pub fn completion_at(source: &str, offset: usize) -> Option<Completion> {
+    let tag = current_tag(source, offset)?;
+    let (opening, body) = tag.split_at(2);
+    let body = body.strip_prefix('-').unwrap_or(body);
+    let (in_string, _) = scan(body);
+
+    if in_string {
+        let keyword = body.split_whitespace().next();
+        return (opening == "{%" && matches!(keyword, Some("extends" | "include")))
+            .then_some(Completion::Template);
+    }
+
+    // Components: `{{<name attr="a" ` or `{% <name `
+    if let Some(call) = body.trim_start().strip_prefix('<') {
+        let name_end = call
+            .find(|c: char| !is_ident_char(c) && c != '.')
+            .unwrap_or(call.len());
+        let (name, attrs) = call.split_at(name_end);
+        if attrs.is_empty() {
+            return Some(Completion::Component);
+        }
+        if !attrs.starts_with(char::is_whitespace) || attrs.contains('>') {
+            return None;
+        }
+        let (_, words) = scan(attrs);
+        // Only complete argument names, not in the middle of an expression
+        if attrs.matches('{').count() != attrs.matches('}').count() {
+            return None;
+        }
+        let typing = attrs.ends_with(is_ident_char);
+        let given = words
+            .iter()
+            .take(words.len() - usize::from(typing))
+            .map(|w| w.to_string())
+            .collect();
+        return Some(Completion::ComponentArg {
+            component: name.to_string(),
+            given,
+        });
+    }
+
+    let before_word = body.trim_end_matches(is_ident_char);
+    let trimmed = before_word.trim_end();
+    if trimmed.ends_with('|') {
+        return Some(Completion::Filter);
+    }
+    if before_word.len() != trimmed.len() {
+        let mut words = trimmed.rsplit(char::is_whitespace);
+        let last = words.next();
+        if last == Some("is") || (last == Some("not") && words.next() == Some("is")) {
+            return Some(Completion::Test);
+        }
+    }
+    // In a `{% %}` tag, the first word is the tag name
+    if opening == "{%" && trimmed.is_empty() {
+        return None;
+    }
+    if before_word.ends_with('.') {
+        return None;
+    }
+    Some(Completion::Function)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(source: &str) -> Option<Completion> {
+        completion_at(source, source.len())
+    }
+
+    #[test]
+    fn can_detect_what_to_complete() {
+        assert_eq!(complete("{{ name | up"), Some(Completion::Filter));
+        assert_eq!(complete("{{ name|"), Some(Completion::Filter));
+        assert_eq!(complete("{% if a is "), Some(Completion::Test));
+        assert_eq!(complete("{% if a is not def"), Some(Completion::Test));
+        assert_eq!(complete("{{ ran"), Some(Completion::Function));
+        assert_eq!(complete("{% set a = now"), Some(Completion::Function));
+        assert_eq!(complete("{% include \"par"), Some(Completion::Template));
+        assert_eq!(complete("{%- extends '"), Some(Completion::Template));
+        assert_eq!(complete("{{<ui."), Some(Completion::Component));
+        assert_eq!(complete("{% <"), Some(Completion::Component));
+    }
+
+    #[test]
+    fn can_detect_component_args() {
+        assert_eq!(
+            complete(r#"{{<ui.button label="a b" {...rest} disabled={a or b} var"#),
+            Some(Completion::ComponentArg {
+                component: "ui.button".to_string(),
+                given: vec!["label".to_string(), "disabled".to_string()],
+            })
+        );
+        assert_eq!(
+            complete("{% <card title "),
+            Some(Completion::ComponentArg {
+                component: "card".to_string(),
+                given: vec!["title".to_string()],
+            })
+        );
+        assert_eq!(complete("{{<card title={a | up"), None);
+    }
+
+    #[test]
+    fn nothing_to_complete() {
+        assert_eq!(complete("hello"), None);
+        assert_eq!(complete("{{ a }} b"), None);
+        assert_eq!(complete("{% "), None);
+        assert_eq!(complete("{% for"), None);
+        assert_eq!(complete("{{ a.b"), None);
+        assert_eq!(complete("{{ \"a"), None);
+        assert_eq!(complete("{% <card />"), None);
+    }
+}
@@ -0,0 +1,26 @@
+//! A language server for [Tera](https://keats.github.io/tera/) templates, talking over stdio.
+//!
+//! It reports errors as you save, and goes to the definition of templates, blocks and
+//! components. It also completes filters, tests, functions and component arguments.
+mod completion;
+mod rpc;
+mod server;
+mod text;
+mod workspace;
+
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut reader = BufReader::new(io::stdin().lock());
+    let mut server = server::Server::new(io::stdout().lock());
+    match server.run(&mut reader) {
+        Ok(true) => ExitCode::SUCCESS,
+        // The client exited without asking for a shutdown first
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("tera-lsp: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Reading and writing JSON-RPC messages with the headers used by the Language Server Protocol.
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// Reads the next message, returning `None` when the input is closed.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = Some(value.trim().parse::<usize>().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Invalid length: {e}"))
+            })?);
+        }
+    }
+
+    let Some(length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn can_roundtrip_messages() {
+        let mut buf = Vec::new();
+        write_message(
+            &mut buf,
+            &json!({"jsonrpc": "2.0", "method": "initialized"}),
+        )
+        .unwrap();
+        write_message(&mut buf, &json!({"id": 1, "result": "é"})).unwrap();
+
+        let mut reader = io::Cursor::new(buf);
+        let first = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(first["method"], "initialized");
+        let second = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(second["result"], "é");
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn errors_without_length() {
+        let mut reader = io::Cursor::new(b"Content-Type: json\r\n\r\n{}".to_vec());
+        assert!(read_message(&mut reader).is_err());
+    }
+}
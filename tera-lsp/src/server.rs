@@ -0,0 +1,576 @@
+//! Handling the LSP requests and notifications.
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+use tera::{ComponentInfo, Severity};
+
+use crate::completion::{Completion, completion_at};
+use crate::rpc::{read_message, write_message};
+use crate::text::{lsp_position_offset, lsp_range, path_to_uri, uri_to_path};
+use crate::workspace::{Config, Document, Problem, Workspace};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#completionItemKind
+const KIND_FUNCTION: u8 = 3;
+const KIND_FIELD: u8 = 5;
+const KIND_CLASS: u8 = 7;
+const KIND_FILE: u8 = 17;
+
+/// Formats a component the way it's defined, eg `button(label: string, size = "big", ...rest)`.
+pub fn signature(info: &ComponentInfo) -> String {
+    let mut args: Vec<String> = info
+        .args()
+        .iter()
+        .map(|arg| {
+            let mut out = arg.name().to_string();
+            if let Some(typ) = arg.arg_type() {
+                out.push_str(&format!(": {typ}"));
+            }
+            if let Some(default) = arg.default() {
+                out.push_str(&format!(" = {default}"));
+            }
+            out
+        })
+        .collect();
+    if let Some(rest) = info.rest_param() {
+        args.push(format!("...{rest}"));
+    }
+    format!("{}({})", info.name(), args.join(", "))
+}
+
+/// Returns the range of the component name in a component call, eg `ui.button` in
+/// `<ui.button label="hi" />`.
+fn component_name_range(source: &str, call: &Range<usize>) -> Range<usize> {
+    let text = source.get(call.clone()).unwrap_or_default();
+    let start = text
+        .find(|c: char| c != '<' && !c.is_whitespace())
+        .unwrap_or(0);
+    let len = text[start..]
+        .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+        .unwrap_or(text.len() - start);
+    call.start + start..call.start + start + len
+}
+
+fn location(doc: &Document, range: Range<usize>) -> Value {
+    json!({
+        "uri": path_to_uri(&doc.path),
+        "range": lsp_range(&doc.source, range),
+    })
+}
+
+pub struct Server<W> {
+    writer: W,
+    workspace: Option<Workspace>,
+    /// The documents we sent diagnostics for, to clear them when they are fixed
+    with_problems: HashSet<PathBuf>,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            workspace: None,
+            with_problems: HashSet::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles messages until the client asks us to exit or closes the input.
+    /// Returns whether the client asked to shutdown before exiting.
+    pub fn run(&mut self, reader: &mut impl BufRead) -> io::Result<bool> {
+        while let Some(message) = read_message(reader)? {
+            if message["method"] == "exit" {
+                break;
+            }
+            self.handle(&message)?;
+        }
+        Ok(self.shutdown)
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        write_message(&mut self.writer, &message)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+    }
+
+    fn handle(&mut self, message: &Value) -> io::Result<()> {
+        let Some(method) = message["method"].as_str() else {
+            // A response to a request we never send
+            return Ok(());
+        };
+        let params = &message["params"];
+        let id = &message["id"];
+
+        if id.is_null() {
+            return self.handle_notification(method, params);
+        }
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            _ if self.workspace.is_none() => Err((INVALID_REQUEST, "Server not initialized")),
+            "textDocument/definition" => Ok(self.definition(params).unwrap_or(Value::Null)),
+            "textDocument/completion" => Ok(self.completion(params).unwrap_or(Value::Null)),
+            "textDocument/hover" => Ok(self.hover(params).unwrap_or(Value::Null)),
+            _ => Err((METHOD_NOT_FOUND, "Method not supported")),
+        };
+        match result {
+            Ok(result) => self.send(json!({"jsonrpc": "2.0", "id": id, "result": result})),
+            Err((code, message)) => self.send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            })),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let path = params["textDocument"]["uri"].as_str().and_then(uri_to_path);
+        let Some(workspace) = self.workspace.as_mut() else {
+            return Ok(());
+        };
+
+        match (method, path) {
+            ("initialized", _) => {}
+            ("textDocument/didOpen", Some(path)) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                workspace.open(&path, text);
+            }
+            ("textDocument/didChange", Some(path)) => {
+                // We ask for the full content on every change
+                if let Some(text) = params["contentChanges"][0]["text"].as_str() {
+                    workspace.change(&path, text);
+                }
+                return Ok(());
+            }
+            ("textDocument/didSave", Some(_)) => workspace.reload(),
+            ("textDocument/didClose", Some(path)) => workspace.close(&path),
+            _ => return Ok(()),
+        }
+        self.publish_diagnostics()
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        let root = params["rootUri"]
+            .as_str()
+            .and_then(uri_to_path)
+            .or_else(|| params["rootPath"].as_str().map(PathBuf::from))
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        let config = Config::from_options(&params["initializationOptions"]);
+        self.workspace = Some(Workspace::new(&root, config));
+
+        json!({
+            "capabilities": {
+                "textDocumentSync": {
+                    "openClose": true,
+                    // Full content
+                    "change": 1,
+                    "save": {"includeText": false},
+                },
+                "definitionProvider": true,
+                "hoverProvider": true,
+                "completionProvider": {
+                    "triggerCharacters": ["|", " ", "<", "\"", "'"],
+                },
+            },
+            "serverInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    fn lsp_diagnostic(workspace: &Workspace, doc: &Document, problem: &Problem) -> Value {
+        let severity = match problem.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+            _ => 3,
+        };
+        let related: Vec<Value> = problem
+            .related
+            .iter()
+            .filter_map(|r| {
+                let related_doc = workspace.document_by_name(&r.template)?;
+                Some(json!({
+                    "location": location(related_doc, r.range.clone()),
+                    "message": r.message,
+                }))
+            })
+            .collect();
+        json!({
+            "range": lsp_range(&doc.source, problem.range.clone()),
+            "severity": severity,
+            "code": problem.code,
+            "source": "tera",
+            "message": problem.message,
+            "relatedInformation": related,
+        })
+    }
+
+    fn publish_diagnostics(&mut self) -> io::Result<()> {
+        let Some(workspace) = self.workspace.as_ref() else {
+            return Ok(());
+        };
+        let problems = workspace.check();
+
+        let mut notifications = Vec::new();
+        let mut with_problems = HashSet::new();
+        for doc in workspace.documents() {
+            let diagnostics: Vec<Value> = problems
+                .iter()
+                .filter(|p| p.template.as_deref() == Some(doc.name.as_str()))
+                .map(|p| Self::lsp_diagnostic(workspace, doc, p))
+                .collect();
+            if diagnostics.is_empty() && !self.with_problems.contains(&doc.path) {
+                continue;
+            }
+            if !diagnostics.is_empty() {
+                with_problems.insert(doc.path.clone());
+            }
+            notifications.push(json!({
+                "uri": path_to_uri(&doc.path),
+                "diagnostics": diagnostics,
+            }));
+        }
+        // Documents that were deleted
+        for path in self.with_problems.difference(&with_problems) {
+            if workspace.document(path).is_none() {
+                notifications.push(json!({"uri": path_to_uri(path), "diagnostics": []}));
+            }
+        }
+        let messages: Vec<String> = problems
+            .iter()
+            .filter(|p| p.template.is_none())
+            .map(|p| p.message.clone())
+            .collect();
+
+        self.with_problems = with_problems;
+        for params in notifications {
+            self.notify("textDocument/publishDiagnostics", params)?;
+        }
+        for message in messages {
+            self.notify(
+                "window/showMessage",
+                json!({"type": 1, "message": format!("Tera: {message}")}),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the workspace, the document and the byte offset a request is about.
+    fn document_at<'a>(&'a self, params: &Value) -> Option<(&'a Workspace, &'a Document, usize)> {
+        let workspace = self.workspace.as_ref()?;
+        let path: PathBuf = uri_to_path(params["textDocument"]["uri"].as_str()?)?;
+        let doc = workspace.document(Path::new(&path))?;
+        let offset = lsp_position_offset(&doc.source, &params["position"])?;
+        Some((workspace, doc, offset))
+    }
+
+    fn definition(&self, params: &Value) -> Option<Value> {
+        let (workspace, doc, offset) = self.document_at(params)?;
+        let symbols = doc.symbols.as_ref().ok()?;
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+
+        let templates = symbols.extends().into_iter().chain(symbols.includes());
+        if let Some(symbol) = templates.into_iter().find(|s| contains(&s.span().range)) {
+            let target = workspace.document_by_name(symbol.name())?;
+            return Some(location(target, 0..0));
+        }
+        if let Some(symbol) = symbols.blocks().iter().find(|s| contains(&s.span().range)) {
+            let (target, block) = workspace.parent_block(doc, symbol.name())?;
+            return Some(location(target, block.span().range.clone()));
+        }
+        let call = symbols
+            .component_calls()
+            .iter()
+            .find(|s| contains(&component_name_range(&doc.source, &s.span().range)))?;
+        let (target, definition, _) = workspace.component(call.name())?;
+        Some(location(target, definition.span().range.clone()))
+    }
+
+    fn hover(&self, params: &Value) -> Option<Value> {
+        let (workspace, doc, offset) = self.document_at(params)?;
+        let symbols = doc.symbols.as_ref().ok()?;
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+
+        let (range, name) = symbols
+            .component_calls()
+            .iter()
+            .map(|s| {
+                let range = component_name_range(&doc.source, &s.span().range);
+                (range, s.name())
+            })
+            .chain(
+                symbols
+                    .components()
+                    .iter()
+                    .map(|(s, _)| (s.span().range.clone(), s.name())),
+            )
+            .find(|(range, _)| contains(range))?;
+        let (definition_doc, _, info) = workspace.component(name)?;
+
+        let mut value = format!(
+            "```tera\n{}\n```\nDefined in `{}`",
+            signature(info),
+            definition_doc.name
+        );
+        if !info.slots().is_empty() {
+            let slots: Vec<_> = info.slots().iter().map(|s| format!("`{s}`")).collect();
+            value.push_str(&format!("\n\nSlots: {}", slots.join(", ")));
+        }
+        if !info.metadata().is_empty() {
+            value.push_str("\n\nMetadata:");
+            for (key, val) in info.metadata() {
+                value.push_str(&format!("\n- `{key}`: `{val}`"));
+            }
+        }
+        Some(json!({
+            "contents": {"kind": "markdown", "value": value},
+            "range": lsp_range(&doc.source, range),
+        }))
+    }
+
+    fn completion(&self, params: &Value) -> Option<Value> {
+        let (workspace, doc, offset) = self.document_at(params)?;
+        let tera = workspace.tera();
+
+        let item = |label: &str, kind: u8, detail: String| json!({"label": label, "kind": kind, "detail": detail});
+        let names = |names: Vec<&str>, kind: &str| {
+            let mut names = names;
+            names.sort_unstable();
+            names
+                .into_iter()
+                .map(|n| item(n, KIND_FUNCTION, kind.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let items = match completion_at(&doc.source, offset)? {
+            Completion::Filter => names(tera.get_filter_names().collect(), "filter"),
+            Completion::Test => names(tera.get_test_names().collect(), "test"),
+            Completion::Function => names(tera.get_function_names().collect(), "function"),
+            Completion::Template => workspace
+                .documents()
+                .iter()
+                .filter(|d| d.name != doc.name)
+                .map(|d| item(&d.name, KIND_FILE, "template".to_string()))
+                .collect(),
+            Completion::Component => workspace
+                .documents()
+                .iter()
+                .filter_map(|d| d.symbols.as_ref().ok())
+                .flat_map(|s| s.components())
+                .map(|(s, info)| item(s.name(), KIND_CLASS, signature(info)))
+                .collect(),
+            Completion::ComponentArg { component, given } => {
+                let (_, _, info) = workspace.component(&component)?;
+                info.args()
+                    .iter()
+                    .filter(|arg| !given.iter().any(|g| g == arg.name()))
+                    .map(|arg| {
+                        let detail = match (arg.arg_type(), arg.is_required()) {
+                            (Some(t), true) => format!("{t}, required"),
+                            (Some(t), false) => t.to_string(),
+                            (None, true) => "required".to_string(),
+                            (None, false) => "optional".to_string(),
+                        };
+                        item(arg.name(), KIND_FIELD, detail)
+                    })
+                    .collect()
+            }
+        };
+        Some(Value::Array(items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "method": method, "params": params})
+    }
+
+    /// Runs a session with the given messages and returns what the server sent back
+    fn session(root: &Path, messages: Vec<Value>) -> Vec<Value> {
+        let mut input = Vec::new();
+        let init = request(0, "initialize", json!({"rootUri": path_to_uri(root)}));
+        for message in std::iter::once(init).chain(messages) {
+            write_message(&mut input, &message).unwrap();
+        }
+        let mut output = Vec::new();
+        let mut server = Server::new(&mut output);
+        server.run(&mut Cursor::new(input)).unwrap();
+
+        let mut reader = Cursor::new(output);
+        let mut responses = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            responses.push(message);
+        }
+        responses
+    }
+
+    fn position(uri: &str, line: u64, character: u64) -> Value {
+        json!({
+            "textDocument": {"uri": uri},
+            "position": {"line": line, "character": character},
+        })
+    }
+
+    fn setup() -> (tempfile::TempDir, impl Fn(&str) -> String) {
+        let dir = tempfile::tempdir().unwrap();
+        let templates = dir.path().join("templates");
+        std::fs::create_dir_all(&templates).unwrap();
+        let files = [
+            ("base.html", "{% block content %}{% endblock content %}"),
+            (
+                "ui.html",
+                r#"{% component ui.button(label: string, size="big", ...rest) {"css": true} %}{{ label }}{% endcomponent ui.button %}"#,
+            ),
+            (
+                "page.html",
+                "{% extends \"base.html\" %}\n{% block content %}\n{{<ui.button label=\"hi\" />}}{% include \"ui.html\" %}\n{% endblock content %}",
+            ),
+        ];
+        for (name, content) in files {
+            std::fs::write(templates.join(name), content).unwrap();
+        }
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let uri = move |name: &str| path_to_uri(&root.join("templates").join(name));
+        (dir, uri)
+    }
+
+    #[test]
+    fn can_go_to_definitions() {
+        let (dir, uri) = setup();
+        let page = uri("page.html");
+        let responses = session(
+            dir.path(),
+            vec![
+                // extends
+                request(1, "textDocument/definition", position(&page, 0, 14)),
+                // block
+                request(2, "textDocument/definition", position(&page, 1, 10)),
+                // component
+                request(3, "textDocument/definition", position(&page, 2, 5)),
+                // include
+                request(4, "textDocument/definition", position(&page, 2, 45)),
+                // nothing
+                request(5, "textDocument/definition", position(&page, 2, 20)),
+            ],
+        );
+        assert_eq!(responses[1]["result"]["uri"], uri("base.html"));
+        assert_eq!(responses[2]["result"]["uri"], uri("base.html"));
+        assert_eq!(
+            responses[2]["result"]["range"],
+            json!({"start": {"line": 0, "character": 9}, "end": {"line": 0, "character": 16}})
+        );
+        assert_eq!(responses[3]["result"]["uri"], uri("ui.html"));
+        assert_eq!(
+            responses[3]["result"]["range"]["start"],
+            json!({"line": 0, "character": 13})
+        );
+        assert_eq!(responses[4]["result"]["uri"], uri("ui.html"));
+        assert_eq!(responses[5]["result"], Value::Null);
+    }
+
+    #[test]
+    fn can_hover_components() {
+        let (dir, uri) = setup();
+        let responses = session(
+            dir.path(),
+            vec![request(
+                1,
+                "textDocument/hover",
+                position(&uri("page.html"), 2, 5),
+            )],
+        );
+        assert_eq!(
+            responses[1]["result"]["contents"]["value"],
+            "```tera\nui.button(label: string, size: string = \"big\", ...rest)\n```\nDefined in `ui.html`\n\nMetadata:\n- `css`: `true`"
+        );
+    }
+
+    #[test]
+    fn can_complete() {
+        let (dir, uri) = setup();
+        let page = uri("page.html");
+        let text = "{{ a | up }}{{<ui.button si";
+        let responses = session(
+            dir.path(),
+            vec![
+                notification(
+                    "textDocument/didChange",
+                    json!({"textDocument": {"uri": page}, "contentChanges": [{"text": text}]}),
+                ),
+                request(1, "textDocument/completion", position(&page, 0, 9)),
+                request(2, "textDocument/completion", position(&page, 0, 27)),
+            ],
+        );
+        let labels = |response: &Value| -> Vec<String> {
+            response["result"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|i| i["label"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert!(labels(&responses[1]).contains(&"upper".to_string()));
+        assert_eq!(labels(&responses[2]), vec!["label", "size"]);
+    }
+
+    #[test]
+    fn publishes_diagnostics() {
+        let (dir, uri) = setup();
+        let page = uri("page.html");
+        let responses = session(
+            dir.path(),
+            vec![
+                notification(
+                    "textDocument/didOpen",
+                    json!({"textDocument": {"uri": page, "text": "{{ a | nope }}"}}),
+                ),
+                notification(
+                    "textDocument/didOpen",
+                    json!({"textDocument": {"uri": page, "text": "{{ a }}"}}),
+                ),
+                request(1, "shutdown", Value::Null),
+                notification("exit", Value::Null),
+            ],
+        );
+        assert_eq!(responses[1]["method"], "textDocument/publishDiagnostics");
+        let diagnostic = &responses[1]["params"]["diagnostics"][0];
+        assert_eq!(diagnostic["code"], "unknown-filter");
+        assert_eq!(
+            diagnostic["range"],
+            json!({"start": {"line": 0, "character": 7}, "end": {"line": 0, "character": 11}})
+        );
+        // Fixed
+        assert_eq!(responses[2]["params"]["uri"], page);
+        assert_eq!(responses[2]["params"]["diagnostics"], json!([]));
+        assert_eq!(responses[3]["id"], 1);
+    }
+
+    #[test]
+    fn errors_on_unknown_requests() {
+        let (dir, _) = setup();
+        let responses = session(dir.path(), vec![request(1, "workspace/symbol", json!({}))]);
+        assert_eq!(responses[1]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,136 @@
+//! Conversions between byte offsets and LSP positions, and between URIs and paths.
+//!
+//! LSP positions are a line and a character offset in UTF-16 code units.
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+/// Converts a byte offset to a `(line, character)` position.
+pub fn offset_to_position(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+    (line, character)
+}
+
+/// Converts a `(line, character)` position to a byte offset, clamping it to the end of the
+/// line or of the source.
+pub fn position_to_offset(source: &str, line: usize, character: usize) -> usize {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match source[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return source.len(),
+        }
+    }
+    let line_end = source[line_start..]
+        .find('\n')
+        .map_or(source.len(), |i| line_start + i);
+
+    let mut units = 0;
+    for (i, c) in source[line_start..line_end].char_indices() {
+        if units >= character {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_end
+}
+
+/// Converts a byte range to a LSP `Range`.
+pub fn lsp_range(source: &str, range: Range<usize>) -> Value {
+    let (start_line, start_char) = offset_to_position(source, range.start);
+    let (end_line, end_char) = offset_to_position(source, range.end);
+    json!({
+        "start": {"line": start_line, "character": start_char},
+        "end": {"line": end_line, "character": end_char},
+    })
+}
+
+/// Returns the byte offset of a LSP `Position`.
+pub fn lsp_position_offset(source: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()?;
+    let character = position["character"].as_u64()?;
+    Some(position_to_offset(
+        source,
+        line as usize,
+        character as usize,
+    ))
+}
+
+/// Returns the path of a `file://` URI.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // Windows paths look like `/C:/templates`
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// Returns the `file://` URI of a path.
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            // Keep the drive letter of Windows paths
+            b':' if uri.len() == "file:///C".len() => uri.push(':'),
+            _ => uri.push_str(&format!("%{b:02X}")),
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_convert_positions() {
+        let source = "ab\n€𝄞x\n";
+        assert_eq!(offset_to_position(source, 0), (0, 0));
+        assert_eq!(offset_to_position(source, 3), (1, 0));
+        // 3 bytes and 1 unit for €, 4 bytes and 2 units for 𝄞
+        assert_eq!(offset_to_position(source, 10), (1, 3));
+        assert_eq!(position_to_offset(source, 1, 3), 10);
+        assert_eq!(position_to_offset(source, 0, 10), 2);
+        assert_eq!(position_to_offset(source, 5, 0), source.len());
+        assert_eq!(position_to_offset(source, 2, 0), source.len());
+    }
+
+    #[test]
+    fn can_convert_uris() {
+        let path = Path::new("/home/bob/my templates/index.html");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/bob/my%20templates/index.html");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+        assert_eq!(
+            uri_to_path("file:///C:/templates/a.html").unwrap(),
+            Path::new("C:/templates/a.html")
+        );
+        assert_eq!(uri_to_path("https://example.com"), None);
+    }
+}
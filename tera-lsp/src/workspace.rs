@@ -0,0 +1,463 @@
+//! The templates of the project being edited and the problems found in them.
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use tera::{ComponentInfo, ErrorKind, Kwargs, Severity, State, Symbol, TemplateSymbols, Tera};
+
+/// The settings given by the editor in the `initializationOptions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// The glob to find templates, relative to the workspace root
+    pub templates: String,
+    /// The filters, tests and functions registered by the application, so they are not
+    /// reported as unknown
+    pub filters: Vec<String>,
+    pub tests: Vec<String>,
+    pub functions: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            templates: "templates/**/*".to_string(),
+            filters: Vec::new(),
+            tests: Vec::new(),
+            functions: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_options(options: &Value) -> Self {
+        let names = |key: &str| -> Vec<String> {
+            options[key]
+                .as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let mut config = Self {
+            filters: names("filters"),
+            tests: names("tests"),
+            functions: names("functions"),
+            ..Self::default()
+        };
+        if let Some(templates) = options["templates"].as_str() {
+            config.templates = templates.to_string();
+        }
+        config
+    }
+}
+
+/// A template of the workspace.
+pub struct Document {
+    pub path: PathBuf,
+    pub name: String,
+    pub source: String,
+    /// The error is always a syntax error
+    pub symbols: Result<TemplateSymbols, tera::Error>,
+}
+
+/// Another location relevant to a [`Problem`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Related {
+    pub template: String,
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// A diagnostic to show in the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// `None` if the problem is not about a specific template, eg a component defined twice
+    pub template: Option<String>,
+    pub range: Range<usize>,
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub related: Vec<Related>,
+}
+
+impl Problem {
+    fn new(template: &str, range: Range<usize>, code: &str, message: String) -> Self {
+        Self {
+            template: Some(template.to_string()),
+            range,
+            severity: Severity::Error,
+            code: code.to_string(),
+            message,
+            related: Vec::new(),
+        }
+    }
+}
+
+impl From<&tera::Diagnostic> for Problem {
+    fn from(diagnostic: &tera::Diagnostic) -> Self {
+        Self {
+            template: diagnostic.template().map(|t| t.to_string()),
+            range: diagnostic.span().map_or(0..0, |s| s.range.clone()),
+            severity: diagnostic.severity(),
+            code: diagnostic.code().to_string(),
+            message: diagnostic.message().to_string(),
+            related: diagnostic
+                .labels()
+                .iter()
+                .map(|l| Related {
+                    template: l.template().to_string(),
+                    range: l.span().range.clone(),
+                    message: l.message().to_string(),
+                })
+                .chain(diagnostic.notes().iter().map(|note| Related {
+                    template: diagnostic.template().unwrap_or_default().to_string(),
+                    range: diagnostic.span().map_or(0..0, |s| s.range.clone()),
+                    message: note.clone(),
+                }))
+                .collect(),
+        }
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+pub struct Workspace {
+    root: PathBuf,
+    config: Config,
+    /// Only used for its filters, tests and functions
+    tera: Tera,
+    /// The content of the documents opened in the editor, which can be unsaved
+    open: HashMap<PathBuf, String>,
+    documents: Vec<Document>,
+}
+
+impl Workspace {
+    pub fn new(root: &Path, config: Config) -> Self {
+        let mut tera = Tera::default();
+        for name in &config.filters {
+            tera.register_filter(name.clone(), |v: tera::Value, _: Kwargs, _: &State| v);
+        }
+        for name in &config.tests {
+            tera.register_test(name.clone(), |_: tera::Value, _: Kwargs, _: &State| true);
+        }
+        for name in &config.functions {
+            tera.register_function(name.clone(), |_: Kwargs, _: &State| tera::Value::none());
+        }
+
+        let mut workspace = Self {
+            root: normalize(root),
+            config,
+            tera,
+            open: HashMap::new(),
+            documents: Vec::new(),
+        };
+        workspace.reload();
+        workspace
+    }
+
+    /// Finds the templates again, eg to pick up new or deleted files, and parses all of them.
+    pub fn reload(&mut self) {
+        let glob = self.root.join(&self.config.templates);
+        let mut entries = tera::load_from_glob(&glob.to_string_lossy()).unwrap_or_default();
+        entries.sort_by(|a, b| a.1.cmp(&b.1));
+
+        self.documents = entries
+            .into_iter()
+            .filter_map(|(path, name)| {
+                let path = normalize(&path);
+                let source = match self.open.get(&path) {
+                    Some(source) => source.clone(),
+                    None => std::fs::read_to_string(&path).ok()?,
+                };
+                let symbols = self.tera.parse_template_symbols(&name, &source);
+                Some(Document {
+                    path,
+                    name,
+                    source,
+                    symbols,
+                })
+            })
+            .collect();
+    }
+
+    pub fn open(&mut self, path: &Path, text: &str) {
+        let path = normalize(path);
+        let known = self.document(&path).is_some();
+        self.change(&path, text);
+        if !known {
+            // Might be a new file
+            self.reload();
+        }
+    }
+
+    /// Updates the content of an open document, only parsing that document again.
+    pub fn change(&mut self, path: &Path, text: &str) {
+        let path = normalize(path);
+        self.open.insert(path.clone(), text.to_string());
+        if let Some(doc) = self.documents.iter_mut().find(|d| d.path == path) {
+            doc.source = text.to_string();
+            doc.symbols = self.tera.parse_template_symbols(&doc.name, text);
+        }
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        self.open.remove(&normalize(path));
+        self.reload();
+    }
+
+    pub fn tera(&self) -> &Tera {
+        &self.tera
+    }
+
+    pub fn documents(&self) -> &[Document] {
+        &self.documents
+    }
+
+    pub fn document(&self, path: &Path) -> Option<&Document> {
+        let path = normalize(path);
+        self.documents.iter().find(|d| d.path == path)
+    }
+
+    pub fn document_by_name(&self, name: &str) -> Option<&Document> {
+        self.documents.iter().find(|d| d.name == name)
+    }
+
+    /// Finds where a component is defined.
+    pub fn component(&self, name: &str) -> Option<(&Document, &Symbol, &ComponentInfo)> {
+        self.documents.iter().find_map(|doc| {
+            let symbols = doc.symbols.as_ref().ok()?;
+            symbols
+                .components()
+                .iter()
+                .find(|(s, _)| s.name() == name)
+                .map(|(s, info)| (doc, s, info))
+        })
+    }
+
+    /// Finds the definition of a block in the parents of the given template.
+    pub fn parent_block(&self, doc: &Document, block: &str) -> Option<(&Document, &Symbol)> {
+        let mut current = self.document_by_name(&doc.name)?;
+        // Bounded in case of circular extends
+        for _ in 0..self.documents.len() {
+            let parent_name = current.symbols.as_ref().ok()?.extends()?.name();
+            current = self.document_by_name(parent_name)?;
+            let symbols = current.symbols.as_ref().ok()?;
+            if let Some(symbol) = symbols.blocks().iter().find(|s| s.name() == block) {
+                return Some((current, symbol));
+            }
+        }
+        None
+    }
+
+    /// Checks all the templates together, like Tera does when adding them.
+    pub fn check(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut broken = HashSet::new();
+        for doc in &self.documents {
+            if let Err(e) = &doc.symbols {
+                broken.insert(doc.name.as_str());
+                problems.extend(e.diagnostics().iter().map(Problem::from));
+            }
+        }
+
+        let extends_range = |name: &str| {
+            self.document_by_name(name)
+                .and_then(|d| d.symbols.as_ref().ok()?.extends())
+                .map_or(0..0, |s| s.span().range.clone())
+        };
+        // Whether the diagnostic is about a template not found because it has a syntax error,
+        // which is already reported
+        let is_about_broken = |diagnostic: &tera::Diagnostic| {
+            let (Some(template), Some(span)) = (diagnostic.template(), diagnostic.span()) else {
+                return false;
+            };
+            diagnostic.code() == "unknown-template"
+                && self.document_by_name(template).is_some_and(|d| {
+                    let literal = d.source.get(span.range.clone()).unwrap_or_default();
+                    broken.contains(literal.trim_matches(['"', '\'', '`']))
+                })
+        };
+
+        let mut tera = self.tera.clone();
+        let mut batch: Vec<&Document> = self
+            .documents
+            .iter()
+            .filter(|d| d.symbols.is_ok())
+            .collect();
+        // Templates that can't be added at all are reported and removed from the batch, so we
+        // still check the other ones
+        while !batch.is_empty() {
+            let Err(e) =
+                tera.add_raw_templates(batch.iter().map(|d| (d.name.as_str(), d.source.as_str())))
+            else {
+                break;
+            };
+            let culprit = match e.kind() {
+                ErrorKind::MissingParent { current, parent } => {
+                    if !broken.contains(parent.as_str()) {
+                        problems.push(Problem::new(
+                            current,
+                            extends_range(current),
+                            "missing-parent",
+                            format!("Parent template `{parent}` not found"),
+                        ));
+                    }
+                    Some(current.clone())
+                }
+                ErrorKind::CircularExtend { tpl, .. } => {
+                    problems.push(Problem::new(
+                        tpl,
+                        extends_range(tpl),
+                        "circular-extend",
+                        e.to_string(),
+                    ));
+                    Some(tpl.clone())
+                }
+                ErrorKind::CircularInclude { tpl, .. } => {
+                    problems.push(Problem::new(tpl, 0..0, "circular-include", e.to_string()));
+                    Some(tpl.clone())
+                }
+                _ => {
+                    problems.extend(
+                        e.diagnostics()
+                            .iter()
+                            .filter(|d| !is_about_broken(d))
+                            .map(Problem::from),
+                    );
+                    None
+                }
+            };
+            match culprit {
+                Some(name) => batch.retain(|d| d.name != name),
+                None => break,
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn workspace(files: &[(&str, &str)], config: Config) -> (tempfile::TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join("templates").join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let workspace = Workspace::new(dir.path(), config);
+        (dir, workspace)
+    }
+
+    fn codes(problems: &[Problem]) -> Vec<(&str, &str)> {
+        problems
+            .iter()
+            .map(|p| (p.template.as_deref().unwrap(), p.code.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn can_read_config() {
+        let config = Config::from_options(&json!({"templates": "tpl/*.html", "filters": ["md"]}));
+        assert_eq!(config.templates, "tpl/*.html");
+        assert_eq!(config.filters, vec!["md"]);
+        assert_eq!(Config::from_options(&Value::Null), Config::default());
+    }
+
+    #[test]
+    fn reports_problems_in_all_templates() {
+        let (_dir, ws) = workspace(
+            &[
+                ("base.html", "{% block content %}{% endblock %}"),
+                (
+                    "index.html",
+                    "{% extends 'base.html' %}{% block nope %}{% endblock %}",
+                ),
+                ("broken.html", "{% if %}"),
+                (
+                    "uses_broken.html",
+                    "{% include 'broken.html' %}{{ a | nope }}",
+                ),
+                ("orphan.html", "{% extends 'missing.html' %}"),
+            ],
+            Config::default(),
+        );
+        let problems = ws.check();
+        assert_eq!(
+            codes(&problems),
+            vec![
+                ("broken.html", "syntax"),
+                ("orphan.html", "missing-parent"),
+                ("index.html", "unknown-block"),
+                ("uses_broken.html", "unknown-filter"),
+            ]
+        );
+        let orphan = ws.document_by_name("orphan.html").unwrap();
+        assert_eq!(&orphan.source[problems[1].range.clone()], "'missing.html'");
+    }
+
+    #[test]
+    fn registers_names_from_config() {
+        let config = Config {
+            filters: vec!["markdown".to_string()],
+            ..Config::default()
+        };
+        let (_dir, ws) = workspace(&[("a.html", "{{ a | markdown }}")], config);
+        assert!(ws.check().is_empty());
+    }
+
+    #[test]
+    fn uses_unsaved_content() {
+        let (dir, mut ws) = workspace(&[("a.html", "hello")], Config::default());
+        let path = dir.path().join("templates/a.html");
+        ws.change(&path, "{{ a | nope }}");
+        assert_eq!(codes(&ws.check()), vec![("a.html", "unknown-filter")]);
+        ws.close(&path);
+        assert!(ws.check().is_empty());
+    }
+
+    #[test]
+    fn can_find_definitions() {
+        let (_dir, ws) = workspace(
+            &[
+                (
+                    "base.html",
+                    "{% block a %}{% block b %}{% endblock %}{% endblock %}",
+                ),
+                (
+                    "mid.html",
+                    "{% extends 'base.html' %}{% block a %}{% endblock %}",
+                ),
+                (
+                    "page.html",
+                    "{% extends 'mid.html' %}{% block b %}{% endblock %}",
+                ),
+                (
+                    "ui.html",
+                    "{% component ui.button(label) %}{% endcomponent %}",
+                ),
+            ],
+            Config::default(),
+        );
+        let page = ws.document_by_name("page.html").unwrap();
+        let (doc, symbol) = ws.parent_block(page, "b").unwrap();
+        assert_eq!(doc.name, "base.html");
+        assert_eq!(&doc.source[symbol.span().range.clone()], "b");
+        let (doc, _) = ws.parent_block(page, "a").unwrap();
+        assert_eq!(doc.name, "mid.html");
+        assert!(ws.parent_block(page, "c").is_none());
+
+        let (doc, _, info) = ws.component("ui.button").unwrap();
+        assert_eq!(doc.name, "ui.html");
+        assert_eq!(info.args()[0].name(), "label");
+    }
+}
@@ -0,0 +1,355 @@
+//! Embed [Tera](https://docs.rs/tera) templates in your binary.
+//!
+//! The templates are read, parsed and compiled when compiling your crate: a syntax error in a
+//! template is a compile error, you don't need to ship the templates next to your binary and
+//! they are not parsed again at runtime.
+//!
+//! ```rust,ignore
+//! let tera = tera_macros::embed!("templates/**/*.html")?;
+//! let out = tera.render("index.html", &tera::Context::new())?;
+//! ```
+//!
+//! Your crate needs to depend on `tera` as well.
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Ident, LitByteStr, LitStr, Token, braced};
+use tera::Delimiters;
+
+struct EmbedArgs {
+    glob: LitStr,
+    delimiters: Option<DelimitersArg>,
+    setup: Option<Expr>,
+}
+
+impl Parse for EmbedArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let glob = input.parse()?;
+        let mut delimiters = None;
+        let mut setup = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            if input.peek(Ident) && input.peek2(Token![=]) {
+                delimiters = Some(input.parse()?);
+                input.parse::<Option<Token![,]>>()?;
+            }
+            if !input.is_empty() {
+                setup = Some(input.parse()?);
+                input.parse::<Option<Token![,]>>()?;
+            }
+        }
+        Ok(Self {
+            glob,
+            delimiters,
+            setup,
+        })
+    }
+}
+
+/// `delimiters = { block_start: "<%", ... }`, the fields not given keep their default value.
+struct DelimitersArg {
+    span: Span,
+    fields: Vec<(Ident, LitStr)>,
+}
+
+impl DelimitersArg {
+    fn to_delimiters(&self) -> syn::Result<Delimiters> {
+        let mut delimiters = Delimiters::default();
+        for (name, value) in &self.fields {
+            let field = match name.to_string().as_str() {
+                "block_start" => &mut delimiters.block_start,
+                "block_end" => &mut delimiters.block_end,
+                "variable_start" => &mut delimiters.variable_start,
+                "variable_end" => &mut delimiters.variable_end,
+                "comment_start" => &mut delimiters.comment_start,
+                "comment_end" => &mut delimiters.comment_end,
+                _ => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!("Unknown delimiter `{name}`"),
+                    ));
+                }
+            };
+            *field = value.value().into();
+        }
+        Ok(delimiters)
+    }
+}
+
+impl Parse for DelimitersArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        if name != "delimiters" {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("Unknown argument `{name}`, expected `delimiters`"),
+            ));
+        }
+        input.parse::<Token![=]>()?;
+        let content;
+        braced!(content in input);
+        let mut fields = Vec::new();
+        while !content.is_empty() {
+            let field: Ident = content.parse()?;
+            content.parse::<Token![:]>()?;
+            fields.push((field, content.parse()?));
+            if content.parse::<Option<Token![,]>>()?.is_none() {
+                break;
+            }
+        }
+        if !content.is_empty() {
+            return Err(content.error("Expected `,`"));
+        }
+        Ok(Self {
+            span: name.span(),
+            fields,
+        })
+    }
+}
+
+/// Whether the tokens call `set_delimiters`, which would not change how the templates were
+/// parsed at compile time.
+fn calls_set_delimiters(tokens: TokenStream) -> Option<Span> {
+    tokens.into_iter().find_map(|token| match token {
+        TokenTree::Ident(ident) if ident == "set_delimiters" => Some(ident.span()),
+        TokenTree::Group(group) => calls_set_delimiters(group.stream()),
+        _ => None,
+    })
+}
+
+/// Embeds all the templates matching the glob and returns a `tera::TeraResult<tera::Tera>`
+/// with them added.
+///
+/// The glob is relative to the directory containing your `Cargo.toml` and the templates are
+/// named the same way as with `Tera::load_from_glob`: relative to the glob directory.
+///
+/// Templates are parsed and compiled at compile time and any syntax error will fail the
+/// compilation. Other errors, like calling an unknown filter, are only known when the compiled
+/// templates are added to Tera at runtime with `Tera::add_compiled_templates`. To register your own filters, tests and functions before that, pass
+/// a closure taking a `&mut Tera` as second argument:
+///
+/// ```rust,ignore
+/// let tera = tera_macros::embed!("templates/**/*.html", |tera| {
+///     tera.register_filter("double", |x: i64, _: tera::Kwargs, _: &tera::State| x * 2);
+/// })?;
+/// ```
+///
+/// Templates using custom delimiters need to give them to the macro, before the closure if
+/// there is one, as they are parsed before the Tera instance exists. The delimiters not given
+/// keep their default value:
+///
+/// ```rust,ignore
+/// let tera = tera_macros::embed!("templates/**/*.tex", delimiters = {
+///     block_start: "<%",
+///     block_end: "%>",
+///     variable_start: "<<",
+///     variable_end: ">>",
+/// })?;
+/// ```
+///
+/// Calling `Tera::set_delimiters` in the closure is a compile error.
+///
+/// Cargo will rebuild your crate when an embedded template changes but not when a new file
+/// matching the glob is created.
+#[proc_macro]
+pub fn embed(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = syn::parse_macro_input!(input as EmbedArgs);
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    match expand(&args, Path::new(&manifest_dir)) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(args: &EmbedArgs, manifest_dir: &Path) -> syn::Result<TokenStream> {
+    if let Some(span) = args
+        .setup
+        .as_ref()
+        .and_then(|setup| calls_set_delimiters(quote! { #setup }))
+    {
+        return Err(syn::Error::new(
+            span,
+            "The templates are parsed at compile time so the delimiters need to be given to \
+             `embed!` with `delimiters = { ... }` instead of calling `set_delimiters`",
+        ));
+    }
+    let delimiters = match &args.delimiters {
+        Some(arg) => {
+            let delimiters = arg.to_delimiters()?;
+            tera::compile_templates(std::iter::empty::<(&str, &str)>(), &delimiters)
+                .map_err(|e| syn::Error::new(arg.span, e.to_string()))?;
+            delimiters
+        }
+        None => Delimiters::default(),
+    };
+
+    let templates = read_templates(&args.glob.value(), manifest_dir, args.glob.span())?;
+    let mut sources = Vec::with_capacity(templates.len());
+    for (path, name) in &templates {
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            syn::Error::new(
+                args.glob.span(),
+                format!("Failed to read template {}: {e}", path.display()),
+            )
+        })?;
+        sources.push((name.as_str(), source));
+    }
+
+    // Checking the templates against each other, eg missing parents or unknown filters, depends
+    // on what's registered on the Tera instance at runtime so only syntax errors are reported here
+    let compiled = match tera::compile_templates(sources.iter().map(|(n, s)| (n, s)), &delimiters) {
+        Ok(compiled) => compiled,
+        Err(_) => {
+            // Report the syntax errors of every template, not only the first one
+            let mut errors: Option<syn::Error> = None;
+            for (name, source) in &sources {
+                if let Err(e) = tera::compile_templates([(name, source)], &delimiters) {
+                    let err = syn::Error::new(args.glob.span(), e.to_string());
+                    match errors.as_mut() {
+                        Some(errors) => errors.combine(err),
+                        None => errors = Some(err),
+                    }
+                }
+            }
+            return Err(errors.expect("a template failed to compile"));
+        }
+    };
+    let compiled = LitByteStr::new(&compiled, Span::call_site());
+
+    // The files are not used but it makes Cargo rebuild the crate when they change
+    let tracked = templates.iter().map(|(path, _)| {
+        let path = path.to_string_lossy();
+        quote! { const _: &str = ::core::include_str!(#path); }
+    });
+    // Templates added at runtime, eg with `render_str`, use the same delimiters
+    let set_delimiters = args.delimiters.as_ref().map(|_| {
+        let fields = [
+            ("block_start", &delimiters.block_start),
+            ("block_end", &delimiters.block_end),
+            ("variable_start", &delimiters.variable_start),
+            ("variable_end", &delimiters.variable_end),
+            ("comment_start", &delimiters.comment_start),
+            ("comment_end", &delimiters.comment_end),
+        ]
+        .map(|(name, value)| {
+            let name = Ident::new(name, Span::call_site());
+            let value = value.as_ref();
+            quote! { #name: ::std::borrow::Cow::Borrowed(#value) }
+        });
+        quote! {
+            tera.set_delimiters(::tera::Delimiters { #(#fields),* })?;
+        }
+    });
+    let setup = args.setup.as_ref().map(|setup| {
+        // Going through a function so the closure argument type is inferred
+        quote! {
+            fn setup<F: ::core::ops::FnOnce(&mut ::tera::Tera)>(f: F) -> F {
+                f
+            }
+            setup(#setup)(&mut tera);
+        }
+    });
+
+    Ok(quote! {
+        (|| -> ::tera::TeraResult<::tera::Tera> {
+            #(#tracked)*
+            let mut tera = ::tera::Tera::default();
+            #set_delimiters
+            #setup
+            tera.add_compiled_templates(#compiled)?;
+            Ok(tera)
+        })()
+    })
+}
+
+/// Finds the templates matching the glob, sorted by name so the output is deterministic.
+fn read_templates(
+    glob: &str,
+    manifest_dir: &Path,
+    span: Span,
+) -> syn::Result<Vec<(PathBuf, String)>> {
+    let full_glob = manifest_dir.join(glob);
+    let mut templates = tera::load_from_glob(&full_glob.to_string_lossy())
+        .map_err(|e| syn::Error::new(span, e.to_string()))?;
+    if templates.is_empty() {
+        return Err(syn::Error::new(
+            span,
+            format!("No templates found matching `{glob}`"),
+        ));
+    }
+    templates.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(input: &str) -> syn::Result<String> {
+        let args: EmbedArgs = syn::parse_str(input)?;
+        expand(&args, Path::new(env!("CARGO_MANIFEST_DIR"))).map(|t| t.to_string())
+    }
+
+    #[test]
+    fn embeds_matching_templates() {
+        let out = expand_str(r#""tests/templates/**/*.html""#).unwrap();
+        assert!(out.contains("tests/templates/base.html\") ;"));
+        assert!(out.contains("tests/templates/partials/header.html\") ;"));
+        assert!(!out.contains("broken"));
+        assert!(out.contains("tera . add_compiled_templates (b\""));
+        assert!(!out.contains("add_raw_templates"));
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let err = expand_str(r#""tests/broken/*.html""#).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("broken.html:1:"), "{msg}");
+    }
+
+    #[test]
+    fn errors_on_empty_glob() {
+        let err = expand_str(r#""tests/nothing/*.html""#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No templates found matching `tests/nothing/*.html`"
+        );
+    }
+
+    #[test]
+    fn parses_with_the_given_delimiters() {
+        let out = expand_str(
+            r##""tests/delimiters/*.txt", delimiters = { block_start: "<%", block_end: "%>", variable_start: "<<", variable_end: ">>", comment_start: "<#", comment_end: "#>" }"##,
+        )
+        .unwrap();
+        assert!(out.contains("tera . set_delimiters"));
+
+        // The `<% %>` tags are plain text with the default delimiters so it parses either way
+        let out = expand_str(r#""tests/delimiters/*.txt", |tera| {}"#).unwrap();
+        assert!(!out.contains("set_delimiters"));
+    }
+
+    #[test]
+    fn errors_on_invalid_delimiters() {
+        let err = expand_str(r#""tests/templates/**/*.html", delimiters = { block_start: "<" }"#)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`block_start` delimiter must be 2 bytes long"
+        );
+
+        let err =
+            expand_str(r#""tests/templates/**/*.html", delimiters = { block: "<%" }"#).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown delimiter `block`");
+    }
+
+    #[test]
+    fn errors_on_set_delimiters_in_setup() {
+        let err = expand_str(
+            r#""tests/templates/**/*.html", |tera| { tera.set_delimiters(Default::default()).unwrap(); }"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("delimiters = { ... }"), "{err}");
+    }
+}
@@ -0,0 +1,45 @@
+use tera::{Kwargs, State, context};
+
+#[test]
+fn can_embed_templates() {
+    let tera = tera_macros::embed!("tests/templates/**/*.html", |tera| {
+        tera.register_filter("shout", |s: &str, _: Kwargs, _: &State| {
+            format!("{}!", s.to_uppercase())
+        });
+    })
+    .unwrap();
+
+    let out = tera
+        .render(
+            "index.html",
+            &context! { title => "Hi", greeting => "hello" },
+        )
+        .unwrap();
+    assert_eq!(out, "<html><h1>Hi</h1>HELLO!</html>\n");
+}
+
+#[test]
+fn missing_filters_error_at_runtime() {
+    let err = tera_macros::embed!("tests/templates/**/*.html").unwrap_err();
+    assert!(err.to_string().contains("shout"));
+}
+
+#[test]
+fn can_embed_templates_with_custom_delimiters() {
+    let tera = tera_macros::embed!("tests/delimiters/*.txt", delimiters = {
+        block_start: "<%",
+        block_end: "%>",
+        variable_start: "<<",
+        variable_end: ">>",
+        comment_start: "<#",
+        comment_end: "#>",
+    })
+    .unwrap();
+
+    let out = tera
+        .render("page.txt", &context! { show => &true, name => "bob" })
+        .unwrap();
+    assert_eq!(out, "BOB\n");
+    let out = tera.render_str("<< 1 + 1 >>", &context! {}, false).unwrap();
+    assert_eq!(out, "2");
+}
@@ -332,6 +332,46 @@ impl Kwargs {
             Err(Error::missing_arg(key))
         }
     }
+
+    /// Names the arguments given by position, stored under their index, from the parameters
+    /// declared by the filter/test/function `name` being called.
+    pub(crate) fn name_positional_args(
+        self,
+        kind: &str,
+        name: &str,
+        params: &[&'static str],
+    ) -> TeraResult<Self> {
+        if !self.values.contains_key(&Key::U64(0)) {
+            return Ok(self);
+        }
+        let signature = format!("{name}({})", params.join(", "));
+
+        let mut map = Map::new();
+        for (key, value) in self.values.iter() {
+            let Key::U64(idx) = key else {
+                map.insert(key.clone(), value.clone());
+                continue;
+            };
+            let Some(param) = params.get(*idx as usize) else {
+                return Err(Error::message(if params.is_empty() {
+                    format!("The {kind} `{name}` only accepts keyword arguments")
+                } else {
+                    format!(
+                        "The {kind} `{name}` accepts at most {} positional argument(s): `{signature}`",
+                        params.len()
+                    )
+                }));
+            };
+            if self.values.contains_key(&Key::Str(param)) {
+                return Err(Error::message(format!(
+                    "The argument `{param}` of the {kind} `{name}` is given both by position and by name: `{signature}`"
+                )));
+            }
+            map.insert(Key::from(*param), value.clone());
+        }
+
+        Ok(Self::new(Arc::new(map)))
+    }
 }
 
 impl<const N: usize> From<[(&'static str, Value); N]> for Kwargs {
@@ -0,0 +1,1219 @@
+//! A binary format for compiled templates so they can be loaded back without going through
+//! the lexer/parser/compiler again.
+//!
+//! The format is only meant to be read by the exact same version of Tera that wrote it: the
+//! header contains the crate version and anything else is rejected.
+//! The header also contains a checksum of the templates so corrupted data is rejected before
+//! the VM gets to run it.
+//! It is not meant to be read from untrusted sources: besides the names checked by `check_chunk`,
+//! the operands of the instructions, like jump targets, are trusted and the VM panics if they
+//! are wrong.
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use crate::HashMap;
+use crate::delimiters::Delimiters;
+use crate::errors::{Error, TeraResult};
+use crate::parsing::ast::{
+    ArgDefault, ComponentArgument, ComponentCallSite, ComponentDefinition, Type,
+};
+use crate::parsing::{Chunk, IncludeContext, Instruction, Lambda};
+use crate::template::Template;
+use crate::utils::Span;
+use crate::value::{Key, Map, StringKind, Value, ValueInner};
+
+const MAGIC: &[u8; 4] = b"TERA";
+/// Bump it whenever the encoding of anything in this file changes.
+const FORMAT_VERSION: u32 = 12;
+const TERA_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Encodes the templates, including their header.
+pub(crate) fn encode_templates<'a>(
+    templates: impl ExactSizeIterator<Item = &'a Template>,
+) -> Vec<u8> {
+    let mut payload = Encoder::default();
+    payload.usize(templates.len());
+    for tpl in templates {
+        tpl.encode(&mut payload);
+    }
+
+    let mut enc = Encoder::default();
+    enc.buf.extend_from_slice(MAGIC);
+    enc.buf.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    TERA_VERSION.encode(&mut enc);
+    enc.buf
+        .extend_from_slice(&checksum(&payload.buf).to_le_bytes());
+    enc.buf.extend_from_slice(&payload.buf);
+    enc.buf
+}
+
+/// FNV-1a, to catch data that was corrupted or edited since being written
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Parses and compiles the templates given, without a [`Tera`](crate::Tera) instance, and
+/// serializes them like [`Tera::compiled_templates`](crate::Tera::compiled_templates).
+///
+/// Only syntax errors are reported: the templates are checked against each other and against
+/// the filters, tests and functions when loaded with
+/// [`Tera::add_compiled_templates`](crate::Tera::add_compiled_templates).
+/// This is meant for compiling templates ahead of time, eg in a build script.
+///
+/// ```
+/// # use tera::{Tera, Delimiters};
+/// let bytes = tera::compile_templates([("hello.html", "Hello {{ name }}!")], &Delimiters::default()).unwrap();
+///
+/// let mut tera = Tera::default();
+/// tera.add_compiled_templates(&bytes).unwrap();
+/// ```
+pub fn compile_templates<I, N, C>(templates: I, delimiters: &Delimiters) -> TeraResult<Vec<u8>>
+where
+    I: IntoIterator<Item = (N, C)>,
+    N: AsRef<str>,
+    C: AsRef<str>,
+{
+    delimiters.validate()?;
+    let mut templates = templates
+        .into_iter()
+        .map(|(name, content)| {
+            Template::new(name.as_ref(), content.as_ref(), None, delimiters.clone())
+        })
+        .collect::<TeraResult<Vec<_>>>()?;
+    // Keep the output deterministic
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(encode_templates(templates.iter()))
+}
+
+/// Decodes templates written by `encode_templates`, erroring if they come from another version
+/// of Tera.
+pub(crate) fn decode_templates(bytes: &[u8]) -> TeraResult<Vec<Template>> {
+    let mut dec = Decoder { bytes, pos: 0 };
+    if dec.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(Error::message(
+            "The data given is not a set of compiled Tera templates",
+        ));
+    }
+    let format_version = u32::from_le_bytes(dec.take(4)?.try_into().expect("4 bytes"));
+    let tera_version = String::decode(&mut dec)?;
+    if format_version != FORMAT_VERSION || tera_version != TERA_VERSION {
+        return Err(Error::message(format!(
+            "The compiled templates were created by Tera {tera_version} and cannot be loaded by \
+             Tera {TERA_VERSION}: they need to be compiled again"
+        )));
+    }
+    let expected_checksum = u64::from_le_bytes(dec.take(8)?.try_into().expect("8 bytes"));
+    if checksum(&bytes[dec.pos..]) != expected_checksum {
+        return Err(dec.invalid("checksum mismatch"));
+    }
+
+    let num_templates = dec.usize()?;
+    let mut templates = Vec::with_capacity(num_templates.min(1024));
+    for _ in 0..num_templates {
+        templates.push(Template::decode(&mut dec)?);
+    }
+    if dec.pos != bytes.len() {
+        return Err(dec.invalid("trailing data"));
+    }
+    Ok(templates)
+}
+
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    /// LEB128
+    fn u64(&mut self, mut val: u64) {
+        loop {
+            let byte = (val & 0x7f) as u8;
+            val >>= 7;
+            if val == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0x80);
+        }
+    }
+
+    fn usize(&mut self, val: usize) {
+        self.u64(val as u64);
+    }
+
+    fn bytes(&mut self, val: &[u8]) {
+        self.usize(val.len());
+        self.buf.extend_from_slice(val);
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn invalid(&self, msg: &str) -> Error {
+        Error::message(format!(
+            "Invalid compiled templates: {msg} at byte {}",
+            self.pos
+        ))
+    }
+
+    fn take(&mut self, len: usize) -> TeraResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| self.invalid("unexpected end of data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> TeraResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> TeraResult<u64> {
+        let mut val = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            val |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+        Err(self.invalid("integer too large"))
+    }
+
+    fn usize(&mut self) -> TeraResult<usize> {
+        let val = self.u64()?;
+        usize::try_from(val).map_err(|_| self.invalid("integer too large"))
+    }
+
+    fn bytes(&mut self) -> TeraResult<&'a [u8]> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+
+    fn str(&mut self) -> TeraResult<&'a str> {
+        let bytes = self.bytes()?;
+        std::str::from_utf8(bytes).map_err(|_| self.invalid("invalid UTF-8"))
+    }
+
+    /// Reads a length, capping it so broken data can't make us allocate huge amounts upfront.
+    fn len(&mut self) -> TeraResult<usize> {
+        let len = self.usize()?;
+        if len > self.bytes.len() - self.pos {
+            return Err(self.invalid("length larger than the data"));
+        }
+        Ok(len)
+    }
+}
+
+trait Encode {
+    fn encode(&self, enc: &mut Encoder);
+}
+
+trait Decode: Sized {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self>;
+}
+
+impl Encode for bool {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.u8(*self as u8);
+    }
+}
+
+impl Decode for bool {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        match dec.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(dec.invalid("invalid bool")),
+        }
+    }
+}
+
+impl Encode for usize {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.usize(*self);
+    }
+}
+
+impl Decode for usize {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        dec.usize()
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.bytes(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, enc: &mut Encoder) {
+        self.as_str().encode(enc);
+    }
+}
+
+impl Decode for String {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        dec.str().map(ToOwned::to_owned)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            None => enc.u8(0),
+            Some(val) => {
+                enc.u8(1);
+                val.encode(enc);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        match dec.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(dec)?)),
+            _ => Err(dec.invalid("invalid option")),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Box<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        (**self).encode(enc);
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        T::decode(dec).map(Box::new)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.usize(self.len());
+        for val in self {
+            val.encode(enc);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        let len = dec.len()?;
+        let mut out = Vec::with_capacity(len);
+        for _ in 0..len {
+            out.push(T::decode(dec)?);
+        }
+        Ok(out)
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, enc: &mut Encoder) {
+        self.0.encode(enc);
+        self.1.encode(enc);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok((A::decode(dec)?, B::decode(dec)?))
+    }
+}
+
+/// Maps and sets are written as a list of entries
+fn encode_entries<'a, T: Encode + 'a>(
+    enc: &mut Encoder,
+    len: usize,
+    entries: impl Iterator<Item = &'a T>,
+) {
+    enc.usize(len);
+    for entry in entries {
+        entry.encode(enc);
+    }
+}
+
+fn decode_entries<T: Decode, C: FromIterator<T>>(dec: &mut Decoder) -> TeraResult<C> {
+    let len = dec.len()?;
+    (0..len).map(|_| T::decode(dec)).collect()
+}
+
+impl<T: Encode> Encode for HashMap<String, T> {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.usize(self.len());
+        for (key, val) in self {
+            key.encode(enc);
+            val.encode(enc);
+        }
+    }
+}
+
+impl<T: Decode> Decode for HashMap<String, T> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        decode_entries(dec)
+    }
+}
+
+impl<T: Encode> Encode for BTreeMap<String, T> {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.usize(self.len());
+        for (key, val) in self {
+            key.encode(enc);
+            val.encode(enc);
+        }
+    }
+}
+
+impl<T: Decode> Decode for BTreeMap<String, T> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        decode_entries(dec)
+    }
+}
+
+impl Encode for HashSet<String> {
+    fn encode(&self, enc: &mut Encoder) {
+        encode_entries(enc, self.len(), self.iter());
+    }
+}
+
+impl Decode for HashSet<String> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        decode_entries(dec)
+    }
+}
+
+impl Encode for Span {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.usize(self.start_line);
+        enc.usize(self.start_col);
+        enc.usize(self.end_line);
+        enc.usize(self.end_col);
+        enc.usize(self.range.start);
+        enc.usize(self.range.end);
+    }
+}
+
+impl Decode for Span {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(Span {
+            start_line: dec.usize()?,
+            start_col: dec.usize()?,
+            end_line: dec.usize()?,
+            end_col: dec.usize()?,
+            range: dec.usize()?..dec.usize()?,
+        })
+    }
+}
+
+impl Encode for Key<'_> {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Key::Bool(b) => {
+                enc.u8(0);
+                b.encode(enc);
+            }
+            Key::U64(v) => {
+                enc.u8(1);
+                enc.u64(*v);
+            }
+            Key::I64(v) => {
+                enc.u8(2);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Key::U128(v) => {
+                enc.u8(3);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Key::I128(v) => {
+                enc.u8(4);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Key::String(s) => {
+                enc.u8(5);
+                s.encode(enc);
+            }
+            Key::Str(s) => {
+                enc.u8(5);
+                s.encode(enc);
+            }
+        }
+    }
+}
+
+impl Decode for Key<'static> {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(match dec.u8()? {
+            0 => Key::Bool(bool::decode(dec)?),
+            1 => Key::U64(dec.u64()?),
+            2 => Key::I64(i64::from_le_bytes(
+                dec.take(8)?.try_into().expect("8 bytes"),
+            )),
+            3 => Key::U128(u128::from_le_bytes(
+                dec.take(16)?.try_into().expect("16 bytes"),
+            )),
+            4 => Key::I128(i128::from_le_bytes(
+                dec.take(16)?.try_into().expect("16 bytes"),
+            )),
+            5 => Key::String(Arc::from(dec.str()?)),
+            _ => return Err(dec.invalid("invalid key")),
+        })
+    }
+}
+
+impl Encode for Value {
+    fn encode(&self, enc: &mut Encoder) {
+        match &self.inner {
+            ValueInner::Undefined => enc.u8(0),
+            ValueInner::None => enc.u8(1),
+            ValueInner::Bool(b) => {
+                enc.u8(2);
+                b.encode(enc);
+            }
+            ValueInner::U64(v) => {
+                enc.u8(3);
+                enc.u64(*v);
+            }
+            ValueInner::I64(v) => {
+                enc.u8(4);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            ValueInner::F64(v) => {
+                enc.u8(5);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            ValueInner::U128(v) => {
+                enc.u8(6);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            ValueInner::I128(v) => {
+                enc.u8(7);
+                enc.buf.extend_from_slice(&v.to_le_bytes());
+            }
+            ValueInner::String(s) => {
+                enc.u8(match s.kind() {
+                    StringKind::Normal => 8,
+                    StringKind::Safe => 9,
+                });
+                s.as_str().encode(enc);
+            }
+            ValueInner::Array(arr) => {
+                enc.u8(10);
+                encode_entries(enc, arr.len(), arr.iter());
+            }
+            ValueInner::Map(map) => {
+                enc.u8(11);
+                enc.usize(map.len());
+                for (key, val) in map.iter() {
+                    key.encode(enc);
+                    val.encode(enc);
+                }
+            }
+            ValueInner::Bytes(bytes) => {
+                enc.u8(12);
+                enc.bytes(bytes);
+            }
+            ValueInner::Lambda(lambda) => {
+                enc.u8(13);
+                lambda.param.encode(enc);
+                lambda.chunk.encode(enc);
+            }
+        }
+    }
+}
+
+impl Decode for Value {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(match dec.u8()? {
+            0 => Value::undefined(),
+            1 => Value::none(),
+            2 => Value::from(bool::decode(dec)?),
+            3 => Value::from(dec.u64()?),
+            4 => Value::from(i64::from_le_bytes(
+                dec.take(8)?.try_into().expect("8 bytes"),
+            )),
+            5 => Value::from(f64::from_le_bytes(
+                dec.take(8)?.try_into().expect("8 bytes"),
+            )),
+            6 => Value::from(u128::from_le_bytes(
+                dec.take(16)?.try_into().expect("16 bytes"),
+            )),
+            7 => Value::from(i128::from_le_bytes(
+                dec.take(16)?.try_into().expect("16 bytes"),
+            )),
+            8 => Value::normal_string(dec.str()?),
+            9 => Value::safe_string(dec.str()?),
+            10 => ValueInner::Array(Arc::new(Vec::decode(dec)?)).into(),
+            11 => {
+                let map: Map = decode_entries::<(Key<'static>, Value), _>(dec)?;
+                ValueInner::Map(Arc::new(map)).into()
+            }
+            12 => Value::bytes(dec.bytes()?),
+            // The variables captured are found again from the chunk
+            13 => Value::lambda(Lambda::new(String::decode(dec)?, Chunk::decode(dec)?)),
+            _ => return Err(dec.invalid("invalid value")),
+        })
+    }
+}
+
+impl Encode for Instruction {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Instruction::LoadConst(v) => {
+                enc.u8(0);
+                v.encode(enc);
+            }
+            Instruction::LoadName(s) => {
+                enc.u8(1);
+                s.encode(enc);
+            }
+            Instruction::LoadAttr(s) => {
+                enc.u8(2);
+                s.encode(enc);
+            }
+            Instruction::LoadAttrOpt(s) => {
+                enc.u8(3);
+                s.encode(enc);
+            }
+            Instruction::BinarySubscript => enc.u8(4),
+            Instruction::BinarySubscriptOpt => enc.u8(5),
+            Instruction::Slice => enc.u8(6),
+            Instruction::SliceOpt => enc.u8(7),
+            Instruction::WriteText(s) => {
+                enc.u8(8);
+                s.encode(enc);
+            }
+            Instruction::WriteTop => enc.u8(9),
+            Instruction::Set(s) => {
+                enc.u8(10);
+                s.encode(enc);
+            }
+            Instruction::SetGlobal(s) => {
+                enc.u8(11);
+                s.encode(enc);
+            }
+            Instruction::Include(b, context) => {
+                enc.u8(12);
+                b.encode(enc);
+                context.encode(enc);
+            }
+            Instruction::BuildMap(n) => {
+                enc.u8(13);
+                n.encode(enc);
+            }
+            Instruction::BuildList(n) => {
+                enc.u8(14);
+                n.encode(enc);
+            }
+            Instruction::BuildMapWithSpreads(spreads) => {
+                enc.u8(15);
+                spreads.encode(enc);
+            }
+            Instruction::BuildListWithSpreads(spreads) => {
+                enc.u8(16);
+                spreads.encode(enc);
+            }
+            Instruction::CallFunction(s) => {
+                enc.u8(17);
+                s.encode(enc);
+            }
+            Instruction::RenderInlineComponent(s) => {
+                enc.u8(18);
+                s.encode(enc);
+            }
+            Instruction::RenderBodyComponent(s) => {
+                enc.u8(19);
+                s.encode(enc);
+            }
+            Instruction::ApplyFilter(s) => {
+                enc.u8(20);
+                s.encode(enc);
+            }
+            Instruction::RunTest(s) => {
+                enc.u8(21);
+                s.encode(enc);
+            }
+            Instruction::RenderBlock(s) => {
+                enc.u8(22);
+                s.encode(enc);
+            }
+            Instruction::Jump(n) => {
+                enc.u8(23);
+                n.encode(enc);
+            }
+            Instruction::PopJumpIfFalse(n) => {
+                enc.u8(24);
+                n.encode(enc);
+            }
+            Instruction::JumpIfFalseOrPop(n) => {
+                enc.u8(25);
+                n.encode(enc);
+            }
+            Instruction::JumpIfTrueOrPop(n) => {
+                enc.u8(26);
+                n.encode(enc);
+            }
+            Instruction::Capture => enc.u8(27),
+            Instruction::EndCapture => enc.u8(28),
+            Instruction::StartIterate(b) => {
+                enc.u8(29);
+                b.encode(enc);
+            }
+            Instruction::StartIterateComprehension(b) => {
+                enc.u8(30);
+                b.encode(enc);
+            }
+            Instruction::Iterate(n) => {
+                enc.u8(31);
+                n.encode(enc);
+            }
+            Instruction::StoreLocal(s) => {
+                enc.u8(32);
+                s.encode(enc);
+            }
+            Instruction::StoreDidNotIterate => enc.u8(33),
+            Instruction::Break => enc.u8(34),
+            Instruction::PopLoop => enc.u8(35),
+            Instruction::AppendToList => enc.u8(36),
+            Instruction::Mul => enc.u8(37),
+            Instruction::Div => enc.u8(38),
+            Instruction::FloorDiv => enc.u8(39),
+            Instruction::Mod => enc.u8(40),
+            Instruction::Plus => enc.u8(41),
+            Instruction::Minus => enc.u8(42),
+            Instruction::Power => enc.u8(43),
+            Instruction::LessThan => enc.u8(44),
+            Instruction::GreaterThan => enc.u8(45),
+            Instruction::LessThanOrEqual => enc.u8(46),
+            Instruction::GreaterThanOrEqual => enc.u8(47),
+            Instruction::Equal => enc.u8(48),
+            Instruction::NotEqual => enc.u8(49),
+            Instruction::StrConcat => enc.u8(50),
+            Instruction::In => enc.u8(51),
+            Instruction::Not => enc.u8(52),
+            Instruction::Negative => enc.u8(53),
+            Instruction::LoadPath(path) => {
+                enc.u8(54);
+                path.encode(enc);
+            }
+            Instruction::WritePath(path) => {
+                enc.u8(55);
+                path.encode(enc);
+            }
+            Instruction::RenderSlot(name, n) => {
+                enc.u8(56);
+                name.encode(enc);
+                n.encode(enc);
+            }
+            Instruction::JumpIfArgGiven(name, n) => {
+                enc.u8(57);
+                name.encode(enc);
+                n.encode(enc);
+            }
+            Instruction::SetArgDefault(name, typ) => {
+                enc.u8(58);
+                name.encode(enc);
+                typ.encode(enc);
+            }
+            Instruction::LoopChanged => enc.u8(59),
+            Instruction::LoopCycle => enc.u8(60),
+            Instruction::RecursiveLoop(b, n) => {
+                enc.u8(61);
+                b.encode(enc);
+                n.encode(enc);
+            }
+            Instruction::RecurseLoop(n) => {
+                enc.u8(62);
+                n.encode(enc);
+            }
+            Instruction::CaptureLambda => enc.u8(63),
+        }
+    }
+}
+
+impl Decode for Instruction {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(match dec.u8()? {
+            0 => Instruction::LoadConst(Value::decode(dec)?),
+            1 => Instruction::LoadName(String::decode(dec)?),
+            2 => Instruction::LoadAttr(String::decode(dec)?),
+            3 => Instruction::LoadAttrOpt(String::decode(dec)?),
+            4 => Instruction::BinarySubscript,
+            5 => Instruction::BinarySubscriptOpt,
+            6 => Instruction::Slice,
+            7 => Instruction::SliceOpt,
+            8 => Instruction::WriteText(String::decode(dec)?),
+            9 => Instruction::WriteTop,
+            10 => Instruction::Set(String::decode(dec)?),
+            11 => Instruction::SetGlobal(String::decode(dec)?),
+            12 => Instruction::Include(bool::decode(dec)?, IncludeContext::decode(dec)?),
+            13 => Instruction::BuildMap(dec.usize()?),
+            14 => Instruction::BuildList(dec.usize()?),
+            15 => Instruction::BuildMapWithSpreads(Vec::decode(dec)?),
+            16 => Instruction::BuildListWithSpreads(Vec::decode(dec)?),
+            17 => Instruction::CallFunction(String::decode(dec)?),
+            18 => Instruction::RenderInlineComponent(String::decode(dec)?),
+            19 => Instruction::RenderBodyComponent(String::decode(dec)?),
+            20 => Instruction::ApplyFilter(String::decode(dec)?),
+            21 => Instruction::RunTest(String::decode(dec)?),
+            22 => Instruction::RenderBlock(String::decode(dec)?),
+            23 => Instruction::Jump(dec.usize()?),
+            24 => Instruction::PopJumpIfFalse(dec.usize()?),
+            25 => Instruction::JumpIfFalseOrPop(dec.usize()?),
+            26 => Instruction::JumpIfTrueOrPop(dec.usize()?),
+            27 => Instruction::Capture,
+            28 => Instruction::EndCapture,
+            29 => Instruction::StartIterate(bool::decode(dec)?),
+            30 => Instruction::StartIterateComprehension(bool::decode(dec)?),
+            31 => Instruction::Iterate(dec.usize()?),
+            32 => Instruction::StoreLocal(String::decode(dec)?),
+            33 => Instruction::StoreDidNotIterate,
+            34 => Instruction::Break,
+            35 => Instruction::PopLoop,
+            36 => Instruction::AppendToList,
+            37 => Instruction::Mul,
+            38 => Instruction::Div,
+            39 => Instruction::FloorDiv,
+            40 => Instruction::Mod,
+            41 => Instruction::Plus,
+            42 => Instruction::Minus,
+            43 => Instruction::Power,
+            44 => Instruction::LessThan,
+            45 => Instruction::GreaterThan,
+            46 => Instruction::LessThanOrEqual,
+            47 => Instruction::GreaterThanOrEqual,
+            48 => Instruction::Equal,
+            49 => Instruction::NotEqual,
+            50 => Instruction::StrConcat,
+            51 => Instruction::In,
+            52 => Instruction::Not,
+            53 => Instruction::Negative,
+            54 => Instruction::LoadPath(Vec::decode(dec)?),
+            55 => Instruction::WritePath(Vec::decode(dec)?),
+            56 => Instruction::RenderSlot(String::decode(dec)?, dec.usize()?),
+            57 => Instruction::JumpIfArgGiven(String::decode(dec)?.into(), dec.usize()?),
+            58 => Instruction::SetArgDefault(String::decode(dec)?.into(), Option::decode(dec)?),
+            59 => Instruction::LoopChanged,
+            60 => Instruction::LoopCycle,
+            61 => Instruction::RecursiveLoop(bool::decode(dec)?, dec.usize()?),
+            62 => Instruction::RecurseLoop(dec.usize()?),
+            63 => Instruction::CaptureLambda,
+            _ => return Err(dec.invalid("invalid instruction")),
+        })
+    }
+}
+
+impl Encode for Chunk {
+    fn encode(&self, enc: &mut Encoder) {
+        self.name.encode(enc);
+        encode_entries(enc, self.len(), self.instructions().iter());
+    }
+}
+
+impl Decode for Chunk {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        let name = String::decode(dec)?;
+        let instructions: Vec<(Instruction, Vec<Span>)> = Vec::decode(dec)?;
+        // Jumps are not checked by the VM so make sure they stay within the chunk
+        let len = instructions.len();
+        for (instr, _) in &instructions {
+            if let Instruction::Jump(target)
+            | Instruction::PopJumpIfFalse(target)
+            | Instruction::JumpIfFalseOrPop(target)
+            | Instruction::JumpIfTrueOrPop(target)
+            | Instruction::Iterate(target)
+            | Instruction::RenderSlot(_, target)
+            | Instruction::JumpIfArgGiven(_, target)
+            | Instruction::RecursiveLoop(_, target) = instr
+                && *target > len
+            {
+                return Err(dec.invalid("jump outside of the chunk"));
+            }
+            // The VM reads the loop to run again from the instruction it points to
+            if let Instruction::RecurseLoop(target) = instr
+                && !matches!(
+                    instructions.get(*target),
+                    Some((Instruction::RecursiveLoop(_, end), _)) if end > target
+                )
+            {
+                return Err(dec.invalid("recursive loop call not pointing to a loop"));
+            }
+        }
+        Ok(Chunk::from_instructions(name, instructions))
+    }
+}
+
+impl Encode for IncludeContext {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.u8(match self {
+            IncludeContext::Inherit => 0,
+            IncludeContext::Extend => 1,
+            IncludeContext::Only => 2,
+        });
+    }
+}
+
+impl Decode for IncludeContext {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(match dec.u8()? {
+            0 => IncludeContext::Inherit,
+            1 => IncludeContext::Extend,
+            2 => IncludeContext::Only,
+            _ => return Err(dec.invalid("invalid include context")),
+        })
+    }
+}
+
+impl Encode for Type {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            Type::String => enc.u8(0),
+            Type::Bool => enc.u8(1),
+            Type::Integer => enc.u8(2),
+            Type::Float => enc.u8(3),
+            Type::Number => enc.u8(4),
+            Type::Array => enc.u8(5),
+            Type::Map => enc.u8(6),
+            Type::Bytes => enc.u8(7),
+            Type::OneOf(values) => {
+                enc.u8(8);
+                values.encode(enc);
+            }
+            Type::ArrayOf(t) => {
+                enc.u8(9);
+                t.encode(enc);
+            }
+            Type::MapWithKeys(keys) => {
+                enc.u8(10);
+                keys.encode(enc);
+            }
+            Type::Optional(t) => {
+                enc.u8(11);
+                t.encode(enc);
+            }
+        }
+    }
+}
+
+impl Decode for Type {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(match dec.u8()? {
+            0 => Type::String,
+            1 => Type::Bool,
+            2 => Type::Integer,
+            3 => Type::Float,
+            4 => Type::Number,
+            5 => Type::Array,
+            6 => Type::Map,
+            7 => Type::Bytes,
+            8 => Type::OneOf(Vec::decode(dec)?),
+            9 => Type::ArrayOf(Box::decode(dec)?),
+            10 => Type::MapWithKeys(Vec::decode(dec)?),
+            11 => Type::Optional(Box::decode(dec)?),
+            _ => return Err(dec.invalid("invalid type")),
+        })
+    }
+}
+
+impl Encode for ArgDefault {
+    fn encode(&self, enc: &mut Encoder) {
+        match self {
+            ArgDefault::Value(val) => {
+                enc.u8(0);
+                val.encode(enc);
+            }
+            ArgDefault::Expression(source) => {
+                enc.u8(1);
+                source.encode(enc);
+            }
+        }
+    }
+}
+
+impl Decode for ArgDefault {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(match dec.u8()? {
+            0 => ArgDefault::Value(Value::decode(dec)?),
+            1 => ArgDefault::Expression(String::decode(dec)?),
+            _ => return Err(dec.invalid("invalid argument default")),
+        })
+    }
+}
+
+impl Encode for ComponentArgument {
+    fn encode(&self, enc: &mut Encoder) {
+        self.default.encode(enc);
+        self.typ.encode(enc);
+    }
+}
+
+impl Decode for ComponentArgument {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(ComponentArgument {
+            default: Option::decode(dec)?,
+            typ: Option::decode(dec)?,
+        })
+    }
+}
+
+impl Encode for ComponentCallSite {
+    fn encode(&self, enc: &mut Encoder) {
+        self.name.encode(enc);
+        self.span.encode(enc);
+        self.kwargs.encode(enc);
+        self.has_spread.encode(enc);
+        self.slots.encode(enc);
+    }
+}
+
+impl Decode for ComponentCallSite {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(ComponentCallSite {
+            name: String::decode(dec)?,
+            span: Span::decode(dec)?,
+            kwargs: Vec::decode(dec)?,
+            has_spread: bool::decode(dec)?,
+            slots: Vec::decode(dec)?,
+        })
+    }
+}
+
+/// The body and default expressions are not kept: they are only needed to compile the
+/// component chunk.
+impl Encode for ComponentDefinition {
+    fn encode(&self, enc: &mut Encoder) {
+        self.name.encode(enc);
+        self.kwargs.encode(enc);
+        self.rest_param_name.encode(enc);
+        self.metadata.encode(enc);
+        self.slots.encode(enc);
+    }
+}
+
+impl Decode for ComponentDefinition {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        Ok(ComponentDefinition {
+            name: String::decode(dec)?,
+            kwargs: BTreeMap::decode(dec)?,
+            rest_param_name: Option::decode(dec)?,
+            metadata: BTreeMap::decode(dec)?,
+            slots: Vec::decode(dec)?,
+            default_exprs: Vec::new(),
+            body: Vec::new(),
+        })
+    }
+}
+
+/// Only what `Template::new` produces is kept, the rest is computed when finalizing the
+/// templates. Lint warnings are not kept either since they are for template authors, not for
+/// loading already checked templates.
+impl Encode for Template {
+    fn encode(&self, enc: &mut Encoder) {
+        self.name.encode(enc);
+        self.source.encode(enc);
+        self.path.encode(enc);
+        self.chunk.encode(enc);
+        self.blocks.encode(enc);
+        self.block_name_spans.encode(enc);
+        self.block_definition_spans.encode(enc);
+        self.components.encode(enc);
+        self.component_definition_spans.encode(enc);
+        self.component_calls.encode(enc);
+        self.component_call_sites.encode(enc);
+        self.filter_calls.encode(enc);
+        self.test_calls.encode(enc);
+        self.function_calls.encode(enc);
+        self.include_calls.encode(enc);
+        self.optional_include_calls.encode(enc);
+        self.extends.encode(enc);
+        self.extends_span.encode(enc);
+        self.top_level_variables.encode(enc);
+    }
+}
+
+/// Checks that the names used by the instructions of a chunk are the ones the VM expects to
+/// find: the chunk template to report errors and the filters/tests/functions/components
+/// that get checked against what is registered when the templates are added.
+fn check_chunk(chunk: &Chunk, tpl: &Template) -> Result<(), &'static str> {
+    if chunk.name != tpl.name {
+        return Err("chunk not belonging to its template");
+    }
+    for (instr, _) in chunk.instructions() {
+        let known = match instr {
+            Instruction::ApplyFilter(name) => tpl.filter_calls.contains_key(name),
+            Instruction::RunTest(name) => tpl.test_calls.contains_key(name),
+            Instruction::CallFunction(name) => tpl.function_calls.contains_key(name),
+            Instruction::RenderInlineComponent(name) | Instruction::RenderBodyComponent(name) => {
+                tpl.component_calls.contains_key(name)
+            }
+            Instruction::LoadConst(val) => {
+                if let Some(lambda) = val.as_lambda() {
+                    check_chunk(&lambda.chunk, tpl)?;
+                }
+                true
+            }
+            _ => true,
+        };
+        if !known {
+            return Err("call not listed in its template");
+        }
+    }
+    Ok(())
+}
+
+impl Decode for Template {
+    fn decode(dec: &mut Decoder) -> TeraResult<Self> {
+        let name = String::decode(dec)?;
+        let source = String::decode(dec)?;
+        let tpl = Template {
+            path: Option::decode(dec)?,
+            from_glob: false,
+            chunk: Chunk::decode(dec)?,
+            blocks: HashMap::decode(dec)?,
+            block_name_spans: HashMap::decode(dec)?,
+            block_definition_spans: HashMap::decode(dec)?,
+            components: HashMap::decode(dec)?,
+            component_definition_spans: HashMap::decode(dec)?,
+            component_calls: HashMap::decode(dec)?,
+            component_call_sites: Vec::decode(dec)?,
+            filter_calls: HashMap::decode(dec)?,
+            test_calls: HashMap::decode(dec)?,
+            function_calls: HashMap::decode(dec)?,
+            include_calls: HashMap::decode(dec)?,
+            optional_include_calls: HashMap::decode(dec)?,
+            total_content_num_bytes: source.len(),
+            extends: Option::decode(dec)?,
+            extends_span: Option::decode(dec)?,
+            parents: Vec::new(),
+            block_lineage: HashMap::new(),
+            autoescape_enabled: true,
+            top_level_variables: HashSet::decode(dec)?,
+            warnings: Vec::new(),
+            name,
+            source,
+        };
+
+        std::iter::once(&tpl.chunk)
+            .chain(tpl.blocks.values())
+            .chain(tpl.components.values().map(|(_, chunk)| chunk))
+            .try_for_each(|chunk| check_chunk(chunk, &tpl))
+            .map_err(|msg| dec.invalid(msg))?;
+        Ok(tpl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_roundtrip_templates() {
+        let source = r#"{% component Button(label: string, size="big", ...rest) {"icon": true} %}
+<button>{{ label | upper }}</button>
+{% endcomponent Button %}
+{% block content %}{% for k, v in map %}{{ k }}{{ v.a.b }}{% else %}{{ [1, -2, 3.5, "é", none] }}{% endfor %}{% endblock %}
+{{ x is defined and y in [1, 2] }}{% include "other" %}"#;
+        let tpl = Template::new(
+            "tpl",
+            source,
+            Some("tpl.html".to_owned()),
+            Delimiters::default(),
+        )
+        .unwrap();
+
+        let bytes = encode_templates([&tpl].into_iter());
+        let decoded = decode_templates(&bytes).unwrap();
+        assert_eq!(decoded.len(), 1);
+        let mut expected = tpl.clone();
+        for (def, _) in expected.components.values_mut() {
+            def.body.clear();
+        }
+        assert_eq!(decoded[0], expected);
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let tpl = Template::new("tpl", "{{ hello }}", None, Delimiters::default()).unwrap();
+        let bytes = encode_templates([&tpl].into_iter());
+
+        let err = decode_templates(b"not templates").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("not a set of compiled Tera templates")
+        );
+
+        let mut other_version = bytes.clone();
+        other_version[4] += 1;
+        let err = decode_templates(&other_version).unwrap_err();
+        assert!(err.to_string().contains("need to be compiled again"));
+
+        // Every truncation has to error rather than panic
+        for len in 0..bytes.len() {
+            assert!(decode_templates(&bytes[..len]).is_err());
+        }
+
+        // And so does any change
+        for i in 0..bytes.len() {
+            let mut changed = bytes.clone();
+            changed[i] ^= 0x10;
+            assert!(decode_templates(&changed).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_chunks_the_vm_cannot_run() {
+        let tpl = Template::new("tpl", "{{ hello | upper }}", None, Delimiters::default()).unwrap();
+
+        let mut other_name = tpl.clone();
+        other_name.chunk.name = "other".to_owned();
+        let err = decode_templates(&encode_templates([&other_name].into_iter())).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("chunk not belonging to its template")
+        );
+
+        let mut unlisted_filter = tpl.clone();
+        unlisted_filter.filter_calls.clear();
+        let err = decode_templates(&encode_templates([&unlisted_filter].into_iter())).unwrap_err();
+        assert!(err.to_string().contains("call not listed in its template"));
+    }
+
+    #[test]
+    fn can_compile_templates_with_delimiters() {
+        let delimiters = Delimiters {
+            variable_start: "<<".into(),
+            variable_end: ">>".into(),
+            ..Delimiters::default()
+        };
+        let bytes = compile_templates([("b", "<< b >>"), ("a", "{{ a }}")], &delimiters).unwrap();
+        let decoded = decode_templates(&bytes).unwrap();
+        assert_eq!(decoded[0].name, "a");
+        assert_eq!(
+            decoded[1],
+            Template::new("b", "<< b >>", None, delimiters.clone()).unwrap()
+        );
+
+        let invalid = Delimiters {
+            variable_start: "{%".into(),
+            ..Delimiters::default()
+        };
+        assert!(compile_templates([("a", "")], &invalid).is_err());
+    }
+}
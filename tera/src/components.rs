@@ -1,11 +1,11 @@
 use std::collections::BTreeMap;
 use std::fmt;
 
-use crate::parsing::ast::{ComponentDefinition, Type};
+use crate::parsing::ast::{ArgDefault, ComponentDefinition, Type, fmt_literal};
 use crate::value::Value;
 
 /// The type of component arguments.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ComponentArgType {
     #[allow(missing_docs)]
@@ -24,10 +24,21 @@ pub enum ComponentArgType {
     Map,
     #[allow(missing_docs)]
     Bytes,
+    /// One of the given literals, eg `"primary" | "secondary"`
+    OneOf(Vec<Value>),
+    /// An array where all items are of the given type, eg `array<map>`
+    ArrayOf(Box<ComponentArgType>),
+    /// A map with the given keys, eg `{title: string, url: string?}`.
+    /// Keys with an optional type can be missing and other keys are allowed.
+    MapWithKeys(Vec<(String, ComponentArgType)>),
+    /// The given type or `none`, eg `string?`
+    Optional(Box<ComponentArgType>),
 }
 
 impl ComponentArgType {
-    /// Returns the name of this argument type
+    /// Returns the name of the kind of this argument type, eg `array` for `array<string>` or
+    /// `string` for `string?` and `"primary" | "secondary"` or `float` for `1.5 | 2.5`.
+    /// Use the `Display` implementation to get the full type.
     pub fn as_str(&self) -> &'static str {
         match self {
             ComponentArgType::String => "string",
@@ -35,21 +46,60 @@ impl ComponentArgType {
             ComponentArgType::Integer => "integer",
             ComponentArgType::Float => "float",
             ComponentArgType::Number => "number",
-            ComponentArgType::Array => "array",
-            ComponentArgType::Map => "map",
+            ComponentArgType::Array | ComponentArgType::ArrayOf(_) => "array",
+            ComponentArgType::Map | ComponentArgType::MapWithKeys(_) => "map",
             ComponentArgType::Bytes => "bytes",
+            ComponentArgType::OneOf(values) => {
+                if values.iter().all(|v| v.is_string()) {
+                    "string"
+                } else if values.iter().all(|v| v.is_f64()) {
+                    "float"
+                } else if values.iter().all(|v| v.is_number() && !v.is_f64()) {
+                    "integer"
+                } else if values.iter().all(|v| v.is_number()) {
+                    "number"
+                } else {
+                    "literal"
+                }
+            }
+            ComponentArgType::Optional(t) => t.as_str(),
         }
     }
+
+    /// Whether `none` is accepted, in which case the argument can also be missing from maps
+    pub fn is_optional(&self) -> bool {
+        matches!(self, ComponentArgType::Optional(_))
+    }
 }
 
+/// Formats the type the way it's written in templates
 impl fmt::Display for ComponentArgType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(self.as_str())
+        match self {
+            ComponentArgType::String => f.write_str("string"),
+            ComponentArgType::Bool => f.write_str("bool"),
+            ComponentArgType::Integer => f.write_str("integer"),
+            ComponentArgType::Float => f.write_str("float"),
+            ComponentArgType::Number => f.write_str("number"),
+            ComponentArgType::Array => f.write_str("array"),
+            ComponentArgType::Map => f.write_str("map"),
+            ComponentArgType::Bytes => f.write_str("bytes"),
+            ComponentArgType::OneOf(values) => {
+                let values: Vec<_> = values.iter().map(fmt_literal).collect();
+                f.write_str(&values.join(" | "))
+            }
+            ComponentArgType::ArrayOf(t) => write!(f, "array<{t}>"),
+            ComponentArgType::MapWithKeys(keys) => {
+                let keys: Vec<_> = keys.iter().map(|(k, t)| format!("{k}: {t}")).collect();
+                write!(f, "{{{}}}", keys.join(", "))
+            }
+            ComponentArgType::Optional(t) => write!(f, "{t}?"),
+        }
     }
 }
 
-impl From<Type> for ComponentArgType {
-    fn from(t: Type) -> Self {
+impl From<&Type> for ComponentArgType {
+    fn from(t: &Type) -> Self {
         match t {
             Type::String => ComponentArgType::String,
             Type::Bool => ComponentArgType::Bool,
@@ -59,6 +109,41 @@ impl From<Type> for ComponentArgType {
             Type::Array => ComponentArgType::Array,
             Type::Map => ComponentArgType::Map,
             Type::Bytes => ComponentArgType::Bytes,
+            Type::OneOf(values) => ComponentArgType::OneOf(values.clone()),
+            Type::ArrayOf(t) => ComponentArgType::ArrayOf(Box::new(t.as_ref().into())),
+            Type::MapWithKeys(keys) => ComponentArgType::MapWithKeys(
+                keys.iter().map(|(k, t)| (k.clone(), t.into())).collect(),
+            ),
+            Type::Optional(t) => ComponentArgType::Optional(Box::new(t.as_ref().into())),
+        }
+    }
+}
+
+/// The default of a component argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ComponentArgDefault {
+    /// A literal value, eg `size = 1`
+    Value(Value),
+    /// An expression evaluated when the component is called, as written in the template,
+    /// eg `id = "btn-" ~ label | slug`
+    Expression(String),
+}
+
+impl fmt::Display for ComponentArgDefault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComponentArgDefault::Value(val) => f.write_str(&fmt_literal(val)),
+            ComponentArgDefault::Expression(source) => f.write_str(source),
+        }
+    }
+}
+
+impl From<&ArgDefault> for ComponentArgDefault {
+    fn from(default: &ArgDefault) -> Self {
+        match default {
+            ArgDefault::Value(val) => ComponentArgDefault::Value(val.clone()),
+            ArgDefault::Expression(source) => ComponentArgDefault::Expression(source.clone()),
         }
     }
 }
@@ -67,7 +152,7 @@ impl From<Type> for ComponentArgType {
 #[derive(Clone, Debug)]
 pub struct ComponentArg {
     name: String,
-    default: Option<Value>,
+    default: Option<ComponentArgDefault>,
     arg_type: Option<ComponentArgType>,
 }
 
@@ -76,17 +161,17 @@ impl ComponentArg {
     pub fn name(&self) -> &str {
         &self.name
     }
-    /// The default value, if one was specified
-    pub fn default(&self) -> Option<&Value> {
+    /// The default, if one was specified
+    pub fn default(&self) -> Option<&ComponentArgDefault> {
         self.default.as_ref()
     }
     /// The type, if one was specified.
     pub fn arg_type(&self) -> Option<ComponentArgType> {
-        self.arg_type
+        self.arg_type.clone()
     }
-    /// Whether this param is required, eg no default value
+    /// Whether this param is required, eg no default value and a type that isn't optional
     pub fn is_required(&self) -> bool {
-        self.default.is_none()
+        self.default.is_none() && !self.arg_type.as_ref().is_some_and(|t| t.is_optional())
     }
 }
 
@@ -97,6 +182,7 @@ pub struct ComponentInfo {
     args: Vec<ComponentArg>,
     rest_param: Option<String>,
     metadata: BTreeMap<String, Value>,
+    slots: Vec<String>,
 }
 
 impl ComponentInfo {
@@ -116,6 +202,10 @@ impl ComponentInfo {
     pub fn metadata(&self) -> &BTreeMap<String, Value> {
         &self.metadata
     }
+    /// The names of the slots the component defines, in order
+    pub fn slots(&self) -> &[String] {
+        &self.slots
+    }
 }
 
 impl From<&ComponentDefinition> for ComponentInfo {
@@ -125,8 +215,8 @@ impl From<&ComponentDefinition> for ComponentInfo {
             .iter()
             .map(|(name, arg)| ComponentArg {
                 name: name.clone(),
-                default: arg.default.clone(),
-                arg_type: arg.typ.map(ComponentArgType::from),
+                default: arg.default.as_ref().map(ComponentArgDefault::from),
+                arg_type: arg.typ.as_ref().map(ComponentArgType::from),
             })
             .collect();
 
@@ -135,6 +225,7 @@ impl From<&ComponentDefinition> for ComponentInfo {
             args,
             rest_param: def.rest_param_name.clone(),
             metadata: def.metadata.clone(),
+            slots: def.slots.clone(),
         }
     }
 }
@@ -0,0 +1,388 @@
+//! Structured version of the errors, for tools like editors or CI annotations.
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::errors::{Error, ErrorKind, ReportError};
+use crate::utils::Span;
+
+/// How bad a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Severity {
+    /// The template cannot be used
+    Error,
+    /// The template works but probably doesn't do what was intended
+    Warning,
+}
+
+impl Severity {
+    /// Returns the name of this severity
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A secondary location attached to a [`Diagnostic`], eg where a failing include was called from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    message: String,
+    template: String,
+    span: Span,
+}
+
+impl Label {
+    /// What this location is about.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The name of the template this location is in.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Where in the template source.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+/// A single problem found in a template.
+///
+/// Serializing it, eg to JSON with `serde_json`, gives 1-based lines and columns as well as the
+/// byte offsets of the span in the template source.
+///
+/// ```
+/// # use tera::Tera;
+/// let mut tera = Tera::default();
+/// let err = tera.add_raw_template("hello", "{{ name | nope }}").unwrap_err();
+/// let diagnostics = err.diagnostics();
+/// assert_eq!(diagnostics[0].code(), "unknown-filter");
+/// assert_eq!(diagnostics[0].template(), Some("hello"));
+/// assert_eq!(diagnostics[0].span().unwrap().range, 10..14);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    code: &'static str,
+    severity: Severity,
+    message: String,
+    template: Option<String>,
+    span: Option<Span>,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(code: &'static str, severity: Severity, message: impl ToString) -> Self {
+        Self {
+            code,
+            severity,
+            message: message.to_string(),
+            template: None,
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_location(mut self, template: &str, span: &Span) -> Self {
+        self.template = Some(template.to_string());
+        self.span = Some(span.clone());
+        self
+    }
+
+    pub(crate) fn with_note(mut self, note: impl ToString) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    fn from_report(report: &ReportError, default_code: &'static str) -> Self {
+        let mut diagnostic = Self::new(
+            report.code.unwrap_or(default_code),
+            Severity::Error,
+            &report.message,
+        )
+        .with_location(&report.filename, &report.span);
+        diagnostic.labels = report
+            .notes
+            .iter()
+            .map(|note| Label {
+                message: note.label(),
+                template: note.filename.clone(),
+                span: note.span.clone(),
+            })
+            .collect();
+        diagnostic
+    }
+
+    /// A stable identifier for the kind of problem, eg `unknown-filter`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Whether it's an error or a warning.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// The message describing the problem, without any source context.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The name of the template the problem is in, if it's about a specific template.
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    /// Where in the template source the problem is, if known.
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    /// Other locations relevant to the problem.
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Additional information about the problem.
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let (Some(template), Some(span)) = (&self.template, &self.span) {
+            write!(
+                f,
+                " ({template}:{}:{})",
+                span.start_line,
+                span.start_col + 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a span with 1-based columns to match what editors expect.
+struct SerializedSpan<'a>(&'a Span);
+
+impl Serialize for SerializedSpan<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Span", 6)?;
+        s.serialize_field("start", &self.0.range.start)?;
+        s.serialize_field("end", &self.0.range.end)?;
+        s.serialize_field("start_line", &self.0.start_line)?;
+        s.serialize_field("start_column", &(self.0.start_col + 1))?;
+        s.serialize_field("end_line", &self.0.end_line)?;
+        s.serialize_field("end_column", &(self.0.end_col + 1))?;
+        s.end()
+    }
+}
+
+impl Serialize for Label {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Label", 3)?;
+        s.serialize_field("message", &self.message)?;
+        s.serialize_field("template", &self.template)?;
+        s.serialize_field("span", &SerializedSpan(&self.span))?;
+        s.end()
+    }
+}
+
+impl Serialize for Diagnostic {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("Diagnostic", 7)?;
+        s.serialize_field("code", self.code)?;
+        s.serialize_field("severity", self.severity.as_str())?;
+        s.serialize_field("message", &self.message)?;
+        s.serialize_field("template", &self.template)?;
+        s.serialize_field("span", &self.span.as_ref().map(SerializedSpan))?;
+        s.serialize_field("labels", &self.labels)?;
+        s.serialize_field("notes", &self.notes)?;
+        s.end()
+    }
+}
+
+pub(crate) fn error_diagnostics(error: &Error) -> Vec<Diagnostic> {
+    match &error.kind {
+        ErrorKind::Msg(msg) => vec![Diagnostic::new("message", Severity::Error, msg)],
+        ErrorKind::SyntaxError(report) => vec![Diagnostic::from_report(report, "syntax")],
+        ErrorKind::RenderingError(report) => {
+            vec![Diagnostic::from_report(report, "rendering")]
+        }
+        ErrorKind::ResourceLimitExceeded { limit, report } => {
+            vec![
+                Diagnostic::from_report(report, "resource-limit")
+                    .with_note(format!("the limit exceeded is `{limit}`")),
+            ]
+        }
+        ErrorKind::InvalidTemplates(reports) => reports
+            .iter()
+            .map(|report| Diagnostic::from_report(report, "invalid-template"))
+            .collect(),
+        ErrorKind::CircularExtend {
+            inheritance_chain, ..
+        } => vec![
+            Diagnostic::new("circular-extend", Severity::Error, &error.kind).with_note(format!(
+                "inheritance chain: {}",
+                inheritance_chain.join(" -> ")
+            )),
+        ],
+        ErrorKind::CircularInclude { include_chain, .. } => vec![
+            Diagnostic::new("circular-include", Severity::Error, &error.kind)
+                .with_note(format!("include chain: {}", include_chain.join(" -> "))),
+        ],
+        ErrorKind::MissingParent { .. } => {
+            vec![Diagnostic::new(
+                "missing-parent",
+                Severity::Error,
+                &error.kind,
+            )]
+        }
+        ErrorKind::TemplateNotFound(_) => {
+            vec![Diagnostic::new(
+                "template-not-found",
+                Severity::Error,
+                &error.kind,
+            )]
+        }
+        ErrorKind::ComponentNotFound(_) => {
+            vec![Diagnostic::new(
+                "component-not-found",
+                Severity::Error,
+                &error.kind,
+            )]
+        }
+        ErrorKind::InvalidArgument { .. }
+        | ErrorKind::MissingArgument { .. }
+        | ErrorKind::OutOfRangeArgument { .. } => {
+            vec![Diagnostic::new(
+                "invalid-argument",
+                Severity::Error,
+                &error.kind,
+            )]
+        }
+        ErrorKind::Io(_) => vec![Diagnostic::new("io", Severity::Error, &error.kind)],
+        ErrorKind::Utf8Conversion => {
+            vec![Diagnostic::new("utf8", Severity::Error, &error.kind)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Context, Tera};
+
+    use super::*;
+
+    #[test]
+    fn invalid_templates_have_one_diagnostic_per_problem() {
+        let mut tera = Tera::default();
+        let err = tera
+            .add_raw_templates(vec![
+                ("b", "{{ hello | nope }}\n{% include 'missing' %}"),
+                ("a", "{{ unknown_fn() }}{{ 1 is nope }}"),
+            ])
+            .unwrap_err();
+        let diagnostics = err.diagnostics();
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.template().unwrap(),
+                    d.code(),
+                    d.span().unwrap().start_line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a", "unknown-function", 1),
+                ("a", "unknown-test", 1),
+                ("b", "unknown-filter", 1),
+                ("b", "unknown-template", 2),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity() == Severity::Error));
+    }
+
+    #[test]
+    fn rendering_errors_have_labels() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("inc", "{{ 1 + hello }}"),
+            ("tpl", "{% include 'inc' %}"),
+        ])
+        .unwrap();
+        let err = tera.render("tpl", &Context::new()).unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), "rendering");
+        assert_eq!(diagnostics[0].template(), Some("inc"));
+        assert_eq!(diagnostics[0].labels().len(), 1);
+        assert_eq!(diagnostics[0].labels()[0].template(), "tpl");
+    }
+
+    #[test]
+    fn can_serialize_to_json() {
+        let mut tera = Tera::default();
+        let err = tera
+            .add_raw_template("tpl", "hello\n{{ name | nope }}")
+            .unwrap_err();
+        let json = serde_json::to_value(err.diagnostics()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "code": "unknown-filter",
+                "severity": "error",
+                "message": "Unknown filter `nope`",
+                "template": "tpl",
+                "span": {
+                    "start": 16,
+                    "end": 20,
+                    "start_line": 2,
+                    "start_column": 11,
+                    "end_line": 2,
+                    "end_column": 15,
+                },
+                "labels": [],
+                "notes": [],
+            }])
+        );
+    }
+
+    #[test]
+    fn errors_without_location() {
+        let mut tera = Tera::default();
+        let err = tera
+            .add_raw_templates(vec![("a", "{% include 'b' %}"), ("b", "{% include 'a' %}")])
+            .unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics[0].code(), "circular-include");
+        assert!(diagnostics[0].span().is_none());
+        assert_eq!(diagnostics[0].notes().len(), 1);
+    }
+}
@@ -2,6 +2,8 @@
 use std::error::Error as StdError;
 use std::fmt::{self};
 
+use crate::diagnostics::{Diagnostic, error_diagnostics};
+use crate::limits::ResourceLimit;
 use crate::reporting::generate_report;
 
 use crate::utils::Span;
@@ -12,6 +14,18 @@ pub(crate) struct Note {
     pub(crate) filename: String,
     pub(crate) source: String,
     pub(crate) span: Span,
+    /// How many times the same note was added in a row, eg for a template including itself
+    pub(crate) times: usize,
+}
+
+impl Note {
+    pub(crate) fn label(&self) -> String {
+        if self.times > 1 {
+            format!("{} ({} times)", self.label, self.times)
+        } else {
+            self.label.clone()
+        }
+    }
 }
 
 /// An error that knows how to present itself nicely, with the right spans/notes etc.
@@ -22,6 +36,8 @@ pub struct ReportError {
     pub(crate) source: String,
     pub(crate) span: Span,
     pub(crate) notes: Vec<Note>,
+    /// The diagnostic code, if more specific than the one of the error kind
+    pub(crate) code: Option<&'static str>,
 }
 
 impl ReportError {
@@ -32,6 +48,7 @@ impl ReportError {
             source: source.to_string(),
             span: span.clone(),
             notes: Vec::new(),
+            code: None,
         }
     }
 
@@ -43,20 +60,35 @@ impl ReportError {
             source: String::new(),
             span: span.clone(),
             notes: Vec::new(),
+            code: None,
         }
     }
 
+    pub(crate) fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
     pub(crate) fn set_source(&mut self, filename: &str, source: &str) {
         self.filename = filename.to_string();
         self.source = source.to_string();
     }
 
     pub(crate) fn add_note(&mut self, label: &str, filename: &str, source: &str, span: &Span) {
+        if let Some(last) = self.notes.last_mut()
+            && last.label == label
+            && last.filename == filename
+            && last.span == *span
+        {
+            last.times += 1;
+            return;
+        }
         self.notes.push(Note {
             label: label.to_string(),
             filename: filename.to_string(),
             source: source.to_string(),
             span: span.clone(),
+            times: 1,
         });
     }
 
@@ -95,6 +127,17 @@ pub enum ErrorKind {
     SyntaxError(Box<ReportError>),
     /// An error that happens while rendering a template. Will point to the source file
     RenderingError(Box<ReportError>),
+    /// A render went over one of its [`RenderLimits`](crate::RenderLimits).
+    /// Will point to the source file
+    ResourceLimitExceeded {
+        /// Which limit was exceeded
+        limit: ResourceLimit,
+        /// Where it was exceeded
+        report: Box<ReportError>,
+    },
+    /// Problems found when checking the templates, eg calling an unknown filter.
+    /// Will point to the source files
+    InvalidTemplates(Vec<ReportError>),
     /// A loop was found while looking up the inheritance chain
     CircularExtend {
         /// Name of the template with the loop
@@ -152,9 +195,20 @@ impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ErrorKind::Msg(message) => write!(f, "{message}"),
-            ErrorKind::SyntaxError(s) | ErrorKind::RenderingError(s) => {
+            ErrorKind::SyntaxError(s)
+            | ErrorKind::RenderingError(s)
+            | ErrorKind::ResourceLimitExceeded { report: s, .. } => {
                 write!(f, "{}", s.generate_report())
             }
+            ErrorKind::InvalidTemplates(reports) => {
+                for (i, report) in reports.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "\n\n")?;
+                    }
+                    write!(f, "{}", report.generate_report())?;
+                }
+                Ok(())
+            }
             ErrorKind::CircularExtend {
                 tpl,
                 inheritance_chain,
@@ -224,6 +278,14 @@ impl Error {
         &self.kind
     }
 
+    /// Returns the problems making up this error, with their location when known.
+    ///
+    /// Most errors are a single [`Diagnostic`] but errors found when checking templates have
+    /// one per problem.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        error_diagnostics(self)
+    }
+
     /// Creates generic error with a source
     pub fn chain(value: impl ToString, source: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
         Self {
@@ -247,6 +309,19 @@ impl Error {
         }
     }
 
+    /// Sorts the reports by template name and then by position so the output is deterministic.
+    pub(crate) fn invalid_templates(mut reports: Vec<ReportError>) -> Self {
+        reports.sort_by(|a, b| {
+            a.filename
+                .cmp(&b.filename)
+                .then(a.span.range.start.cmp(&b.span.range.start))
+        });
+        Self {
+            kind: ErrorKind::InvalidTemplates(reports),
+            source: None,
+        }
+    }
+
     pub(crate) fn circular_extend(tpl: impl ToString, inheritance_chain: Vec<String>) -> Self {
         Self {
             kind: ErrorKind::CircularExtend {
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::BTreeSet;
 use std::fmt::Write;
 use std::sync::Arc;
@@ -21,6 +22,13 @@ pub trait Filter<Arg, Res: FunctionResult>: Sync + Send + 'static {
     fn is_safe(&self) -> bool {
         false
     }
+
+    /// The names of the arguments that can be given by position, in order: `["precision", "method"]`
+    /// allows `round(2)` for `round(precision=2)`. Defaults to none, all arguments need to be
+    /// given by name.
+    fn positional_args(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 impl<Func, Arg, Res> Filter<Arg, Res> for Func
@@ -40,12 +48,14 @@ type FilterFunc = dyn Fn(&Value, Kwargs, &State) -> TeraResult<Value> + Sync + S
 pub(crate) struct StoredFilter {
     func: Arc<FilterFunc>,
     is_safe: bool,
+    positional_args: &'static [&'static str],
 }
 
 impl std::fmt::Debug for StoredFilter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("StoredFilter")
             .field("is_safe", &self.is_safe)
+            .field("positional_args", &self.positional_args)
             .finish_non_exhaustive()
     }
 }
@@ -58,6 +68,7 @@ impl StoredFilter {
         Res: FunctionResult,
     {
         let is_safe = Filter::<Arg, Res>::is_safe(&f);
+        let positional_args = Filter::<Arg, Res>::positional_args(&f);
         let closure = move |arg: &Value, kwargs, state: &State| -> TeraResult<Value> {
             f.call(Arg::from_value(arg)?, kwargs, state).into_result()
         };
@@ -65,6 +76,7 @@ impl StoredFilter {
         StoredFilter {
             func: Arc::new(closure),
             is_safe,
+            positional_args,
         }
     }
 
@@ -75,6 +87,14 @@ impl StoredFilter {
     pub fn is_safe(&self) -> bool {
         self.is_safe
     }
+
+    pub fn positional_args(&self) -> &'static [&'static str] {
+        self.positional_args
+    }
+
+    pub(crate) fn set_positional_args(&mut self, params: &'static [&'static str]) {
+        self.positional_args = params;
+    }
 }
 
 pub(crate) fn safe(val: Cow<'_, str>, _: Kwargs, _: &State) -> Value {
@@ -500,30 +520,24 @@ fn ensure_comparable<'a>(keys: impl Iterator<Item = &'a Value>) -> TeraResult<()
     Ok(())
 }
 
-/// Sorts an array. If `attribute` is provided, sorts by that attribute.
-pub(crate) fn sort(val: &[Value], kwargs: Kwargs, _: &State) -> TeraResult<Vec<Value>> {
+/// Sorts an array. If `attribute` and/or `key` are provided, sorts by the key of each element.
+pub(crate) fn sort(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Vec<Value>> {
     if val.is_empty() {
         return Ok(Vec::new());
     }
 
-    if let Some(attribute) = kwargs.get::<&str>("attribute")? {
+    let attribute = kwargs.get::<&str>("attribute")?;
+    let key = kwargs.get::<Value>("key")?;
+    if attribute.is_some() || key.is_some() {
         let mut decorated = Vec::with_capacity(val.len());
         for v in val {
-            let key = match v.get_from_path(attribute) {
-                Some(key) => key,
-                None => {
-                    return Err(Error::message(format!(
-                        "Value {v} does not have an attribute after following path: {attribute}"
-                    )));
-                }
-            };
-            decorated.push((key, v));
+            decorated.push((element_key(v, attribute, key.as_ref(), state)?, v));
         }
         // We sort with Ord::cmp because we have our own custom impl that the default sorting will
         // disagree with
         #[allow(clippy::unnecessary_sort_by)]
         decorated.sort_by(|(a, _), (b, _)| a.cmp(b));
-        ensure_comparable(decorated.iter().map(|(k, _)| *k))?;
+        ensure_comparable(decorated.iter().map(|(k, _)| k.as_ref()))?;
         Ok(decorated.into_iter().map(|(_, v)| v.clone()).collect())
     } else {
         let mut out = val.to_vec();
@@ -536,22 +550,27 @@ pub(crate) fn sort(val: &[Value], kwargs: Kwargs, _: &State) -> TeraResult<Vec<V
     }
 }
 
-pub(crate) fn unique(val: &[Value], _: Kwargs, _: &State) -> Vec<Value> {
+/// Removes the duplicates, keeping the first element for each key if `attribute` and/or `key`
+/// are provided.
+pub(crate) fn unique(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Vec<Value>> {
     if val.is_empty() {
-        return Vec::new();
+        return Ok(Vec::new());
     }
 
+    let attribute = kwargs.get::<&str>("attribute")?;
+    let key = kwargs.get::<Value>("key")?;
     let mut seen = BTreeSet::new();
     let mut res = Vec::with_capacity(val.len());
 
     for v in val {
-        if !seen.contains(v) {
-            seen.insert(v.clone());
+        let k = element_key(v, attribute, key.as_ref(), state)?;
+        if !seen.contains(k.as_ref()) {
+            seen.insert(k.into_owned());
             res.push(v.clone());
         }
     }
 
-    res
+    Ok(res)
 }
 
 pub(crate) fn values(val: &Map, _: Kwargs, _: &State) -> TeraResult<Vec<Value>> {
@@ -583,35 +602,232 @@ pub(crate) fn get(val: &Map, kwargs: Kwargs, _: &State) -> TeraResult<Value> {
     }
 }
 
-pub(crate) fn group_by(val: &[Value], kwargs: Kwargs, _: &State) -> TeraResult<Map> {
+pub(crate) fn group_by(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Map> {
     if val.is_empty() {
         return Ok(Map::new());
     }
 
-    let attribute = kwargs.must_get::<&str>("attribute")?;
+    let key = kwargs.get::<Value>("key")?;
+    let attribute = match key {
+        Some(_) => kwargs.get::<&str>("attribute")?,
+        None => Some(kwargs.must_get::<&str>("attribute")?),
+    };
+
     let mut grouped: HashMap<Key, Vec<Value>> = HashMap::new();
     for v in val {
-        match v.get_from_path(attribute) {
-            None => {
-                return Err(Error::message(format!(
-                    "Value {v} does not have an attribute after following path: {attribute}"
-                )));
-            }
-            Some(x) if x.is_none() => (),
-            Some(x) => {
-                let key = x.as_key()?;
-                if let Some(arr) = grouped.get_mut(&key) {
-                    arr.push(v.clone());
-                } else {
-                    grouped.insert(key, vec![v.clone()]);
-                }
-            }
+        let x = element_key(v, attribute, key.as_ref(), state)?;
+        if x.is_none() {
+            continue;
+        }
+        let group = x.as_key()?;
+        if let Some(arr) = grouped.get_mut(&group) {
+            arr.push(v.clone());
+        } else {
+            grouped.insert(group, vec![v.clone()]);
         }
     }
 
     Ok(grouped.into_iter().map(|(k, v)| (k, v.into())).collect())
 }
 
+/// Follows the `attribute` path in the value, erroring if there is nothing at that path
+fn attribute_of<'a>(val: &'a Value, attribute: &'a str) -> TeraResult<&'a Value> {
+    val.get_from_path(attribute).ok_or_else(|| {
+        Error::message(format!(
+            "Value {val} does not have an attribute after following path: {attribute}"
+        ))
+    })
+}
+
+/// What to sort, group or compare an element on: its `attribute` if given, on which the `key`
+/// lambda is then called if given.
+fn element_key<'a>(
+    val: &'a Value,
+    attribute: Option<&'a str>,
+    key: Option<&Value>,
+    state: &State,
+) -> TeraResult<Cow<'a, Value>> {
+    let val = match attribute {
+        Some(attribute) => attribute_of(val, attribute)?,
+        None => val,
+    };
+    match key {
+        Some(key) => Ok(Cow::Owned(state.call_lambda(key, val)?)),
+        None => Ok(Cow::Borrowed(val)),
+    }
+}
+
+/// The kwargs to pass to the filter or test called by a filter, given as a map in `args`
+fn call_kwargs(kwargs: &Kwargs) -> TeraResult<Kwargs> {
+    let args = kwargs.get::<Map>("args")?.unwrap_or_default();
+    Ok(Kwargs::new(Arc::new(args)))
+}
+
+/// Applies a filter, given by name or as a lambda, to each element and/or gets the `attribute`
+/// of each element. If both are given, the filter is applied to the attribute.
+pub(crate) fn map(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Vec<Value>> {
+    let attribute = kwargs.get::<&str>("attribute")?;
+    let lambda = kwargs.get::<Value>("filter")?.filter(Value::is_lambda);
+    let filter = match lambda {
+        Some(_) => None,
+        None => kwargs.get::<&str>("filter")?,
+    };
+    if attribute.is_none() && filter.is_none() && lambda.is_none() {
+        return Err(Error::message(
+            "The `map` filter requires a `filter` or an `attribute` argument",
+        ));
+    }
+    let filter_kwargs = call_kwargs(&kwargs)?;
+
+    val.iter()
+        .map(|v| {
+            let v = match attribute {
+                Some(attribute) => attribute_of(v, attribute)?,
+                None => v,
+            };
+            match (filter, &lambda) {
+                (Some(filter), _) => state.call_filter(filter, v, filter_kwargs.clone()),
+                (None, Some(lambda)) => state.call_lambda(lambda, v),
+                (None, None) => Ok(v.clone()),
+            }
+        })
+        .collect()
+}
+
+/// Keeps the elements whose `attribute` is equal to `value`, which defaults to `none`.
+/// Elements without that attribute are considered as having it set to `none`.
+pub(crate) fn filter(val: &[Value], kwargs: Kwargs, _: &State) -> TeraResult<Vec<Value>> {
+    let attribute = kwargs.must_get::<&str>("attribute")?;
+    let value = kwargs.get::<Value>("value")?.unwrap_or(Value::none());
+    let none = Value::none();
+
+    Ok(val
+        .iter()
+        .filter(|v| v.get_from_path(attribute).unwrap_or(&none) == &value)
+        .cloned()
+        .collect())
+}
+
+/// Keeps the elements passing the test, given by name or as a lambda returning a truthy value,
+/// or the truthy ones if no test is given.
+/// With `attribute`, the test is done on that attribute: it is undefined for the elements
+/// that don't have it.
+fn select_or_reject(
+    val: &[Value],
+    kwargs: &Kwargs,
+    state: &State,
+    keep: bool,
+) -> TeraResult<Vec<Value>> {
+    let attribute = kwargs.get::<&str>("attribute")?;
+    let lambda = kwargs.get::<Value>("test")?.filter(Value::is_lambda);
+    let test = match lambda {
+        Some(_) => None,
+        None => kwargs.get::<&str>("test")?,
+    };
+    let test_kwargs = call_kwargs(kwargs)?;
+    let undefined = Value::undefined();
+
+    let mut res = Vec::with_capacity(val.len());
+    for v in val {
+        let tested = match attribute {
+            Some(attribute) => v.get_from_path(attribute).unwrap_or(&undefined),
+            None => v,
+        };
+        let passes = match (test, &lambda) {
+            (Some(test), _) => state.call_test(test, tested, test_kwargs.clone())?,
+            (None, Some(lambda)) => state.call_lambda(lambda, tested)?.is_truthy(),
+            (None, None) => tested.is_truthy(),
+        };
+        if passes == keep {
+            res.push(v.clone());
+        }
+    }
+    Ok(res)
+}
+
+pub(crate) fn select(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Vec<Value>> {
+    select_or_reject(val, &kwargs, state, true)
+}
+
+pub(crate) fn reject(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Vec<Value>> {
+    select_or_reject(val, &kwargs, state, false)
+}
+
+/// Adds all the elements, or their `attribute` and/or `key`, to `start` which defaults to 0
+pub(crate) fn sum(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Value> {
+    let attribute = kwargs.get::<&str>("attribute")?;
+    let key = kwargs.get::<Value>("key")?;
+    let mut total = kwargs.get::<Value>("start")?.unwrap_or(Value::from(0));
+
+    for v in val {
+        let v = element_key(v, attribute, key.as_ref(), state)?;
+        total = crate::value::number::add(&total, &v)?;
+    }
+    Ok(total)
+}
+
+/// Finds the element that is the smallest or the largest, comparing their `attribute` and/or
+/// `key` if given. Elements that are, or have that key set to, none are ignored.
+/// None if there is nothing to compare.
+fn extremum(val: &[Value], kwargs: &Kwargs, state: &State, wanted: Ordering) -> TeraResult<Value> {
+    let attribute = kwargs.get::<&str>("attribute")?;
+    let key = kwargs.get::<Value>("key")?;
+    let mut decorated = Vec::with_capacity(val.len());
+    for v in val {
+        let k = element_key(v, attribute, key.as_ref(), state)?;
+        if !k.is_none() {
+            decorated.push((k, v));
+        }
+    }
+    ensure_comparable(decorated.iter().map(|(k, _)| k.as_ref()))?;
+
+    Ok(decorated
+        .into_iter()
+        .reduce(|found, candidate| {
+            if candidate.0.cmp(&found.0) == wanted {
+                candidate
+            } else {
+                found
+            }
+        })
+        .map(|(_, v)| v.clone())
+        .unwrap_or(Value::none()))
+}
+
+pub(crate) fn min(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Value> {
+    extremum(val, &kwargs, state, Ordering::Less)
+}
+
+pub(crate) fn max(val: &[Value], kwargs: Kwargs, state: &State) -> TeraResult<Value> {
+    extremum(val, &kwargs, state, Ordering::Greater)
+}
+
+/// Splits an array in arrays of `size` elements. If `fill_with` is given, the last one is
+/// filled with it to be as long as the others, otherwise it can be smaller.
+pub(crate) fn batch(val: &[Value], kwargs: Kwargs, _: &State) -> TeraResult<Vec<Value>> {
+    let size = kwargs.must_get::<usize>("size")?;
+    if size == 0 {
+        return Err(Error::message(
+            "The `size` argument of the `batch` filter needs to be greater than 0",
+        ));
+    }
+    let fill_with = kwargs.get::<Value>("fill_with")?;
+    // A single batch has no others to match, so it is never padded and a huge `size` can't
+    // allocate more than the array
+    let batch_len = size.min(val.len());
+
+    Ok(val
+        .chunks(size)
+        .map(|chunk| {
+            let mut batch = chunk.to_vec();
+            if let Some(fill_with) = &fill_with {
+                batch.resize(batch_len, fill_with.clone());
+            }
+            Value::from(batch)
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -775,6 +991,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sum_min_max() {
+        let ctx = Context::new();
+        let state = State::new(&ctx);
+        let numbers: Vec<Value> = vec![3.into(), 1.into(), 2.5.into()];
+        assert_eq!(
+            sum(&numbers, Kwargs::default(), &state).unwrap(),
+            (6.5).into()
+        );
+        assert_eq!(
+            sum(&[], Kwargs::from([("start", 1.into())]), &state).unwrap(),
+            1.into()
+        );
+        assert!(sum(&["a".into()], Kwargs::default(), &state).is_err());
+
+        assert_eq!(min(&numbers, Kwargs::default(), &state).unwrap(), 1.into());
+        assert_eq!(max(&numbers, Kwargs::default(), &state).unwrap(), 3.into());
+        assert!(min(&[], Kwargs::default(), &state).unwrap().is_none());
+        assert!(max(&[1.into(), "a".into()], Kwargs::default(), &state).is_err());
+    }
+
+    #[test]
+    fn test_batch() {
+        let ctx = Context::new();
+        let state = State::new(&ctx);
+        let val: Vec<Value> = vec![1.into(), 2.into(), 3.into()];
+        assert_eq!(
+            Value::from(batch(&val, Kwargs::from([("size", 2.into())]), &state).unwrap()),
+            Value::from(vec![vec![1, 2], vec![3]])
+        );
+        assert_eq!(
+            Value::from(
+                batch(
+                    &val,
+                    Kwargs::from([("size", 2.into()), ("fill_with", 0.into())]),
+                    &state
+                )
+                .unwrap()
+            ),
+            Value::from(vec![vec![1, 2], vec![3, 0]])
+        );
+        assert_eq!(
+            Value::from(
+                batch(
+                    &val,
+                    Kwargs::from([
+                        ("size", 1_000_000_000_000_000_000_u64.into()),
+                        ("fill_with", 0.into())
+                    ]),
+                    &state
+                )
+                .unwrap()
+            ),
+            Value::from(vec![vec![1, 2, 3]])
+        );
+        assert!(batch(&val, Kwargs::from([("size", 0.into())]), &state).is_err());
+    }
+
     #[cfg(feature = "unicode")]
     #[test]
     fn can_truncate_graphemes() {
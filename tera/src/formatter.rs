@@ -0,0 +1,512 @@
+//! Rewrites the source of a template with a canonical layout.
+//!
+//! The AST doesn't keep comments, whitespace control, parentheses or the order of keyword
+//! arguments so we can't print it back as is. Instead, the template is parsed to ensure it is
+//! valid and its tokens are then printed back with normalized spacing, using their spans to keep
+//! literals, comments and raw blocks exactly as written.
+//! The indentation before a tag is part of the output unless it is trimmed by whitespace control,
+//! so only trimmed indentation is replaced by the one matching the nesting of the tags.
+use crate::delimiters::Delimiters;
+use crate::errors::{Error, TeraResult};
+use crate::parsing::lexer::{Token, basic_tokenize, tokenize};
+use crate::utils::Span;
+
+const INDENT: &str = "    ";
+
+/// Idents after which an expression is expected, eg `not` in `not -a`
+const KEYWORDS: [&str; 9] = ["and", "or", "not", "in", "is", "if", "else", "elif", "for"];
+
+/// The group of tokens we are currently in, which changes how some tokens are spaced
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Group {
+    Paren,
+    Array,
+    Subscript,
+    Brace,
+    /// Between the `<` (or `</`) and the `>` of a component tag
+    Component,
+    /// The arguments of a component definition, `(label: string, size = 1)`
+    Definition,
+    /// The `<` and `>` of a type like `array<string>`
+    TypeArgs,
+    /// The `{` and `}` of a map type like `{url: string}`
+    TypeBrace,
+}
+
+/// How a `{% %}` tag changes the nesting level of what follows it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Nesting {
+    Open,
+    /// `elif` and `else`, which close a body and open another one
+    Middle,
+    Close,
+    None,
+}
+
+fn tag_nesting(tokens: &[(Token, Span)]) -> Nesting {
+    match tokens.first().map(|(t, _)| t) {
+        Some(Token::Ident("if" | "for" | "block" | "filter" | "component")) => Nesting::Open,
+        // A set is only a block if it doesn't assign a value directly
+        Some(Token::Ident("set" | "set_global")) => {
+            if matches!(tokens.get(2), Some((Token::Assign, _))) {
+                Nesting::None
+            } else {
+                Nesting::Open
+            }
+        }
+        Some(Token::Ident("elif" | "else")) => Nesting::Middle,
+        Some(Token::Ident(name)) if name.starts_with("end") => Nesting::Close,
+        Some(Token::LessThan) => Nesting::Open,
+        Some(Token::ClosingTagStart) => Nesting::Close,
+        _ => Nesting::None,
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    delimiters: &'a Delimiters,
+    output: String,
+    depth: usize,
+    /// Whether the content written since the last tag is only whitespace trimmed by its `-%}`
+    trimmed: bool,
+}
+
+impl<'a> Formatter<'a> {
+    /// Replaces the indentation of the current line if there is nothing else on it yet.
+    /// Whitespace is only changed if it's trimmed, by `ws_start` or the previous tag, since
+    /// changing it would otherwise change the output.
+    fn indent_line(&mut self, depth: usize, ws_start: bool) {
+        if !ws_start && !self.trimmed {
+            return;
+        }
+        let line_start = self.output.rfind('\n').map_or(0, |i| i + 1);
+        if self.output[line_start..]
+            .chars()
+            .all(|c| c == ' ' || c == '\t')
+        {
+            self.output.truncate(line_start);
+            for _ in 0..depth {
+                self.output.push_str(INDENT);
+            }
+        }
+    }
+
+    fn write_tag(&mut self, ws_start: bool, tokens: &[(Token<'a>, Span)], ws_end: bool) {
+        match tag_nesting(tokens) {
+            Nesting::Open => {
+                self.indent_line(self.depth, ws_start);
+                self.depth += 1;
+            }
+            Nesting::Middle => self.indent_line(self.depth.saturating_sub(1), ws_start),
+            Nesting::Close => {
+                self.depth = self.depth.saturating_sub(1);
+                self.indent_line(self.depth, ws_start);
+            }
+            Nesting::None => self.indent_line(self.depth, ws_start),
+        }
+
+        self.output.push_str(&self.delimiters.block_start);
+        self.output.push_str(if ws_start { "- " } else { " " });
+        self.write_tokens(tokens, true);
+        self.output.push_str(if ws_end { " -" } else { " " });
+        self.output.push_str(&self.delimiters.block_end);
+        self.trimmed = ws_end;
+    }
+
+    fn write_variable(&mut self, ws_start: bool, tokens: &[(Token<'a>, Span)], ws_end: bool) {
+        // Component calls are written without spaces, like `{{<button />}}`
+        let padding = if matches!(tokens.first(), Some((Token::LessThan, _)))
+            && matches!(tokens.last(), Some((Token::GreaterThan, _)))
+        {
+            ""
+        } else {
+            " "
+        };
+        self.output.push_str(&self.delimiters.variable_start);
+        if ws_start {
+            self.output.push('-');
+        }
+        self.output.push_str(padding);
+        self.write_tokens(tokens, false);
+        self.output.push_str(padding);
+        if ws_end {
+            self.output.push('-');
+        }
+        self.output.push_str(&self.delimiters.variable_end);
+        self.trimmed = ws_end;
+    }
+
+    /// Writes the tokens found in a tag with a single space between them, except where
+    /// it would look odd like after a `(` or around a `.`.
+    fn write_tokens(&mut self, tokens: &[(Token<'a>, Span)], is_tag: bool) {
+        let mut groups = Vec::new();
+        // Whether the next token starts an expression, to tell apart `-a` and `a - b`,
+        // `[1]` and `a[1]` or `<button />` and `a < b`
+        let mut expect_operand = true;
+        let mut tight_after_previous = true;
+        // Whether we are in the type of a component argument, eg after `label:`
+        let mut in_type = false;
+        let is_definition =
+            is_tag && matches!(tokens.first(), Some((Token::Ident("component"), _)));
+
+        for (i, (token, span)) in tokens.iter().enumerate() {
+            let group = groups.last().copied();
+            let mut ends_operand = false;
+            let (tight_before, tight_after) = match token {
+                // Types are written like `array<map>`, `string?` and `"a" | "b"`
+                Token::LessThan if in_type => {
+                    groups.push(Group::TypeArgs);
+                    (true, true)
+                }
+                Token::GreaterThan if group == Some(Group::TypeArgs) => {
+                    groups.pop();
+                    (true, false)
+                }
+                Token::QuestionMark if in_type => (true, false),
+                Token::LeftBrace if in_type => {
+                    groups.push(Group::TypeBrace);
+                    (false, true)
+                }
+                Token::Colon if matches!(group, Some(Group::Definition | Group::TypeBrace)) => {
+                    in_type = true;
+                    (true, false)
+                }
+                Token::Comma if matches!(group, Some(Group::Definition | Group::TypeBrace)) => {
+                    in_type = false;
+                    (true, false)
+                }
+                // Defaults are written like `size = 1`
+                Token::Assign if group == Some(Group::Definition) => {
+                    in_type = false;
+                    (false, false)
+                }
+                Token::LeftParen if is_definition && groups.is_empty() => {
+                    groups.push(Group::Definition);
+                    (true, true)
+                }
+                Token::Dot | Token::QuestionMarkDot => (true, true),
+                Token::Comma => (true, false),
+                Token::Colon if group == Some(Group::Subscript) => (true, true),
+                Token::Colon => (true, false),
+                Token::Spread => (false, true),
+                Token::LeftParen => {
+                    groups.push(Group::Paren);
+                    (!expect_operand, true)
+                }
+                Token::LeftBracket if expect_operand => {
+                    groups.push(Group::Array);
+                    (false, true)
+                }
+                Token::LeftBracket | Token::QuestionMarkLeftBracket => {
+                    groups.push(Group::Subscript);
+                    (true, true)
+                }
+                Token::LeftBrace => {
+                    groups.push(Group::Brace);
+                    (false, true)
+                }
+                Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                    if groups.pop() == Some(Group::Definition) {
+                        in_type = false;
+                    }
+                    (true, false)
+                }
+                Token::Minus if expect_operand => (false, true),
+                Token::LessThan if expect_operand => {
+                    groups.push(Group::Component);
+                    (false, true)
+                }
+                Token::ClosingTagStart => {
+                    groups.push(Group::Component);
+                    (false, true)
+                }
+                Token::GreaterThan if group == Some(Group::Component) => {
+                    groups.pop();
+                    ends_operand = true;
+                    (true, false)
+                }
+                // The `/` of `/>`
+                Token::Div if group == Some(Group::Component) => (false, true),
+                Token::Assign if matches!(group, Some(Group::Paren | Group::Component)) => {
+                    (true, true)
+                }
+                _ => (false, false),
+            };
+
+            if !tight_after_previous && !tight_before {
+                self.output.push(' ');
+            }
+            self.output.push_str(&self.source[span.range.clone()]);
+            tight_after_previous = tight_after;
+
+            expect_operand = match token {
+                // The first ident of a tag is its name
+                Token::Ident(ident) => (is_tag && i == 0) || KEYWORDS.contains(ident),
+                Token::Str(_)
+                | Token::String(_)
+                | Token::Integer(_)
+                | Token::Float(_)
+                | Token::Bool(_)
+                | Token::RightParen
+                | Token::RightBracket
+                | Token::RightBrace => false,
+                _ => !ends_operand,
+            };
+        }
+    }
+}
+
+/// Returns the tokens of the source once the whitespace control has trimmed the content,
+/// which is what gets rendered.
+fn significant_tokens<'a>(source: &'a str, delimiters: &Delimiters) -> TeraResult<Vec<Token<'a>>> {
+    let mut tokens = Vec::new();
+    for token in tokenize(source, delimiters.clone()) {
+        match token?.0 {
+            Token::Content("") => (),
+            token => tokens.push(token),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Formats an already validated template.
+pub(crate) fn format_source(
+    name: &str,
+    source: &str,
+    delimiters: &Delimiters,
+) -> TeraResult<String> {
+    let mut formatter = Formatter {
+        source,
+        delimiters,
+        output: String::with_capacity(source.len()),
+        depth: 0,
+        trimmed: false,
+    };
+    let mut tokens = basic_tokenize(source, delimiters.clone());
+
+    while let Some(token) = tokens.next() {
+        let (token, span) = token?;
+        match token {
+            Token::Content(text) => {
+                formatter.output.push_str(text);
+                formatter.trimmed &= text.trim_start().is_empty();
+            }
+            Token::Comment(ws_start, ws_end) | Token::RawContent(ws_start, _, ws_end) => {
+                formatter.indent_line(formatter.depth, ws_start);
+                formatter.output.push_str(&source[span.range]);
+                formatter.trimmed = ws_end;
+            }
+            Token::VariableStart(ws_start) | Token::TagStart(ws_start) => {
+                let mut inner = Vec::new();
+                let ws_end = loop {
+                    match tokens.next().transpose()? {
+                        Some((Token::VariableEnd(ws) | Token::TagEnd(ws), _)) => break ws,
+                        Some(inner_token) => inner.push(inner_token),
+                        None => unreachable!("the template was parsed successfully"),
+                    }
+                };
+                if matches!(token, Token::TagStart(_)) {
+                    formatter.write_tag(ws_start, &inner, ws_end);
+                } else {
+                    formatter.write_variable(ws_start, &inner, ws_end);
+                }
+            }
+            _ => unreachable!("only found inside tags"),
+        }
+    }
+
+    // The output should only differ from the source by whitespace that is not rendered
+    if significant_tokens(source, delimiters)? != significant_tokens(&formatter.output, delimiters)?
+    {
+        return Err(Error::message(format!(
+            "Failed to format `{name}`: the formatted template would not be equivalent to the original one. This is a bug in Tera."
+        )));
+    }
+
+    Ok(formatter.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Delimiters, Tera};
+
+    fn format(source: &str) -> String {
+        Tera::default().format_template("tpl", source).unwrap()
+    }
+
+    #[test]
+    fn can_normalize_spacing() {
+        let tests = [
+            ("{{a}}", "{{ a }}"),
+            ("{{-a-}}", "{{- a -}}"),
+            ("{{ a+b*  -c }}", "{{ a + b * -c }}"),
+            ("{{ (a - 1)//2 }}", "{{ (a - 1) // 2 }}"),
+            (
+                "{{a|default(value = 1)|upper}}",
+                "{{ a | default(value=1) | upper }}",
+            ),
+            (
+                "{{ a is not divisibleby( value =3 ) }}",
+                "{{ a is not divisibleby(value=3) }}",
+            ),
+            ("{{ a.b ?. c[ 0 ]?[1] }}", "{{ a.b?.c[0]?[1] }}"),
+            ("{{ a[ 1 : -1 ] ~ b[::2] }}", "{{ a[1:-1] ~ b[::2] }}"),
+            ("{{ [ 1,2 , ...a ] }}", "{{ [1, 2, ...a] }}"),
+            ("{{ { 'a' : 1,...b } }}", "{{ {'a': 1, ...b} }}"),
+            ("{{ a if b>1 else c }}", "{{ a if b > 1 else c }}"),
+            (
+                "{{ [x|upper for x in  xs if x] }}",
+                "{{ [x | upper for x in xs if x] }}",
+            ),
+            ("{{ super( ) }}", "{{ super() }}"),
+            ("{% set a=-1 %}", "{% set a = -1 %}"),
+            (
+                "{%if not(a or b)%}{%endif%}",
+                "{% if not (a or b) %}{% endif %}",
+            ),
+            (
+                "{% for k,v in m %}{% endfor %}",
+                "{% for k, v in m %}{% endfor %}",
+            ),
+            ("{% include  'a.html' %}", "{% include 'a.html' %}"),
+            (
+                "{% filter  truncate( length=2 ) %}a{% endfilter %}",
+                "{% filter truncate(length=2) %}a{% endfilter %}",
+            ),
+            (
+                "{% set   a|upper %}a{% endset %}",
+                "{% set a | upper %}a{% endset %}",
+            ),
+        ];
+        for (source, expected) in tests {
+            assert_eq!(format(source), expected, "formatting {source}");
+            assert_eq!(format(expected), expected, "formatting {expected}");
+        }
+    }
+
+    #[test]
+    fn can_format_components() {
+        let tests = [
+            (
+                "{% component ui.button( label : string,kind='a', ...rest ){ 'css': 'b.css' } %}{{ label }}{% endcomponent ui.button %}",
+                "{% component ui.button(label: string, kind = 'a', ...rest) {'css': 'b.css'} %}{{ label }}{% endcomponent ui.button %}",
+            ),
+            (
+                "{% component nav( variant : \"main\"|\"footer\"=\"main\", items:array < map >, links: array< { url:string,title :string ? } >?, size=a+1 ) %}{% endcomponent nav %}",
+                "{% component nav(variant: \"main\" | \"footer\" = \"main\", items: array<map>, links: array<{url: string, title: string?}>?, size = a + 1) %}{% endcomponent nav %}",
+            ),
+            (
+                "{% component ui.button(label: string ?, variant: string=\"primary\", n: integer = (a < b) | int) %}{% endcomponent ui.button %}",
+                "{% component ui.button(label: string?, variant: string = \"primary\", n: integer = (a < b) | int) %}{% endcomponent ui.button %}",
+            ),
+            (
+                "{{ < ui.button label = \"Hi\" size={ a>1 } { ...rest } disabled/> }}",
+                "{{<ui.button label=\"Hi\" size={a > 1} {...rest} disabled />}}",
+            ),
+            (
+                "{%<card title=\"a\" >%}{{ <icon/> | safe }}{%</ card >%}",
+                "{% <card title=\"a\"> %}{{ <icon /> | safe }}{% </card> %}",
+            ),
+            ("{% set a = < icon / > %}", "{% set a = <icon /> %}"),
+            ("{{ a < b }}", "{{ a < b }}"),
+        ];
+        for (source, expected) in tests {
+            assert_eq!(format(source), expected, "formatting {source}");
+            assert_eq!(format(expected), expected, "formatting {expected}");
+        }
+    }
+
+    #[test]
+    fn indents_nested_tags() {
+        let source = r#"{% block content %}
+{%- if a %}
+      {%- for x in xs -%}
+  <li>{{ x }}</li>
+  {#- a comment #}
+        {%- raw %}{{ x }}{% endraw %}
+{%- else %}{% if b %}{% endif %}
+  {%- endfor %}
+{%- elif b %}
+{%- set c -%}
+{% endset %}
+{%- endif %}
+{% endblock %}
+"#;
+        let expected = r#"{% block content %}
+    {%- if a %}
+        {%- for x in xs -%}
+  <li>{{ x }}</li>
+            {#- a comment #}
+            {%- raw %}{{ x }}{% endraw %}
+        {%- else %}{% if b %}{% endif %}
+        {%- endfor %}
+    {%- elif b %}
+        {%- set c -%}
+        {% endset %}
+    {%- endif %}
+{% endblock %}
+"#;
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn only_indents_trimmed_tags() {
+        let source = "{% for x in xs %}\n{% if x %}\n{{ x }}\n{% endif %}\n{% endfor %}\n";
+        assert_eq!(format(source), source);
+
+        let source = "{% for x in xs %}\n{% if x %}\n{{ x }}\n{%- endif %}\n{%- endfor %}\n";
+        assert_eq!(
+            format(source),
+            "{% for x in xs %}\n{% if x %}\n{{ x }}\n    {%- endif %}\n{%- endfor %}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_rendered_whitespace() {
+        let tests = [
+            "{% if a %}\n\n- a\n  {% endif %}",
+            "{% if a %}\n  {# a comment #}\n\n- a{% endif %}",
+            "{% if a -%}\n  b\n  {% endif %}",
+            "<ul>\n  {% for x in xs %}\n  <li>{{ x }}</li>\n  {% endfor %}\n</ul>",
+        ];
+        for source in tests {
+            assert_eq!(format(source), source);
+        }
+    }
+
+    #[test]
+    fn keeps_comments_raw_and_literals_as_written() {
+        let source = "{#  a   comment #}{%- raw  -%} {{a+b}} {%- endraw %}{{ \"a\\\"b\" ~ `c` ~ 1.50 ~ True }}";
+        assert_eq!(
+            format(source),
+            "{#  a   comment #}{%- raw  -%} {{a+b}} {%- endraw %}{{ \"a\\\"b\" ~ `c` ~ 1.50 ~ True }}"
+        );
+    }
+
+    #[test]
+    fn can_format_with_custom_delimiters() {
+        let mut tera = Tera::default();
+        tera.set_delimiters(Delimiters {
+            block_start: "<%".into(),
+            block_end: "%>".into(),
+            variable_start: "<<".into(),
+            variable_end: ">>".into(),
+            ..Delimiters::default()
+        })
+        .unwrap();
+        assert_eq!(
+            tera.format_template("tpl", "<%if a%><<a|upper>><%endif%>")
+                .unwrap(),
+            "<% if a %><< a | upper >><% endif %>"
+        );
+    }
+
+    #[test]
+    fn errors_on_invalid_templates() {
+        let err = Tera::default()
+            .format_template("tpl", "{{ a | }}")
+            .unwrap_err();
+        assert!(err.to_string().contains("tpl"));
+    }
+}
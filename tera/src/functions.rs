@@ -15,6 +15,12 @@ pub trait Function<Res: FunctionResult>: Sync + Send + 'static {
     fn is_safe(&self) -> bool {
         false
     }
+
+    /// The names of the arguments that can be given by position, in order: `["end"]` allows
+    /// `range(5)` for `range(end=5)`. Defaults to none, all arguments need to be given by name.
+    fn positional_args(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 impl<Func, Res> Function<Res> for Func
@@ -33,6 +39,7 @@ type FunctionFunc = dyn Fn(Kwargs, &State) -> TeraResult<Value> + Sync + Send +
 pub(crate) struct StoredFunction {
     func: Arc<FunctionFunc>,
     is_safe: bool,
+    positional_args: &'static [&'static str],
 }
 
 impl StoredFunction {
@@ -42,6 +49,7 @@ impl StoredFunction {
         Res: FunctionResult,
     {
         let is_safe = f.is_safe();
+        let positional_args = f.positional_args();
         let closure = move |kwargs, state: &State| -> TeraResult<Value> {
             f.call(kwargs, state).into_result()
         };
@@ -49,6 +57,7 @@ impl StoredFunction {
         StoredFunction {
             func: Arc::new(closure),
             is_safe,
+            positional_args,
         }
     }
 
@@ -59,6 +68,14 @@ impl StoredFunction {
     pub fn is_safe(&self) -> bool {
         self.is_safe
     }
+
+    pub fn positional_args(&self) -> &'static [&'static str] {
+        self.positional_args
+    }
+
+    pub(crate) fn set_positional_args(&mut self, params: &'static [&'static str]) {
+        self.positional_args = params;
+    }
 }
 
 /// Upper bound on the number of elements `range()` will produce to avoid OOM.
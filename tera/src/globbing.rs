@@ -1,70 +1,101 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use globset::GlobBuilder;
+use globset::{GlobBuilder, GlobMatcher};
 use walkdir::WalkDir;
 
 use crate::errors::{Error, TeraResult};
 
+/// A glob split between the directory it starts from and the matcher for the files in it
+pub(crate) struct Glob {
+    parent_dir: PathBuf,
+    matcher: GlobMatcher,
+}
+
+impl Glob {
+    pub(crate) fn new(glob: &str) -> TeraResult<Self> {
+        let Some(first_star) = glob.find('*') else {
+            return Err(Error::message(format!(
+                "Not a valid glob: no `*` were found in `{glob}`"
+            )));
+        };
+
+        // https://github.com/Keats/tera/pull/991
+        let split_at = glob[..first_star]
+            .rfind(std::path::is_separator)
+            .map_or(0, |i| i + 1);
+        let (parent_dir, glob_end) = glob.split_at(split_at);
+        // If no directory, we default to cwd
+        let parent_dir = if parent_dir.is_empty() {
+            "."
+        } else {
+            parent_dir
+        };
+
+        // If canonicalize fails, just abort it and resume with the given path.
+        // Consumers expect invalid globs to just return the empty set instead of failing.
+        // See https://github.com/Keats/tera/issues/819#issuecomment-1480392230
+        let parent_dir = std::fs::canonicalize(parent_dir)
+            .unwrap_or_else(|_| std::path::PathBuf::from(parent_dir));
+
+        let canonical_glob = {
+            let mut p = parent_dir.clone();
+            p.push(glob_end);
+            p.to_string_lossy().to_string()
+        };
+
+        let matcher = GlobBuilder::new(&canonical_glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| Error::message(format!("Glob is invalid: {e}")))?
+            .compile_matcher();
+
+        Ok(Self {
+            parent_dir,
+            matcher,
+        })
+    }
+
+    /// The directory the glob starts from
+    #[cfg(feature = "watch")]
+    pub(crate) fn parent_dir(&self) -> &Path {
+        &self.parent_dir
+    }
+
+    /// Returns the name of the template for that path if the glob matches it
+    pub(crate) fn template_name(&self, path: &Path) -> Option<String> {
+        if !self.matcher.is_match(path) {
+            return None;
+        }
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let relative = path.strip_prefix(&self.parent_dir).ok()?;
+        // unify on forward slash
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
 /// Loads the glob and find all files matching that glob,
 /// returning a list of (path, filename)
 pub fn load_from_glob(glob: &str) -> TeraResult<Vec<(PathBuf, String)>> {
-    let Some(first_star) = glob.find('*') else {
-        return Err(Error::message(format!(
-            "Not a valid glob: no `*` were found in `{glob}`"
-        )));
-    };
-
-    // https://github.com/Keats/tera/pull/991
-    let split_at = glob[..first_star]
-        .rfind(std::path::is_separator)
-        .map_or(0, |i| i + 1);
-    let (parent_dir, glob_end) = glob.split_at(split_at);
-    // If no directory, we default to cwd
-    let parent_dir = if parent_dir.is_empty() {
-        "."
-    } else {
-        parent_dir
-    };
-
-    // If canonicalize fails, just abort it and resume with the given path.
-    // Consumers expect invalid globs to just return the empty set instead of failing.
-    // See https://github.com/Keats/tera/issues/819#issuecomment-1480392230
-    let parent_dir =
-        std::fs::canonicalize(parent_dir).unwrap_or_else(|_| std::path::PathBuf::from(parent_dir));
-
-    let canonical_glob = {
-        let mut p = parent_dir.clone();
-        p.push(glob_end);
-        p.to_string_lossy().to_string()
-    };
-
-    let glob_matcher = GlobBuilder::new(&canonical_glob)
-        .literal_separator(true)
-        .build()
-        .map_err(|e| Error::message(format!("Glob is invalid: {e}")))?
-        .compile_matcher();
+    let glob = Glob::new(glob)?;
 
     let mut paths = Vec::new();
-    for entry in WalkDir::new(&parent_dir)
+    for entry in WalkDir::new(&glob.parent_dir)
         .follow_links(true)
         .into_iter()
         .filter_map(|e| e.ok())
     {
         let mut path = entry.path().to_path_buf();
-        if path.is_dir() || !glob_matcher.is_match(&path) {
+        if path.is_dir() {
             continue;
         }
+        let Some(filepath) = glob.template_name(&path) else {
+            continue;
+        };
 
         if path.starts_with("./") {
             path = path.strip_prefix("./").unwrap().to_path_buf();
         }
 
-        let Ok(relative) = path.strip_prefix(&parent_dir) else {
-            continue;
-        };
-        // unify on forward slash
-        let filepath = relative.to_string_lossy().replace('\\', "/");
-
         paths.push((path, filepath));
     }
 
@@ -52,6 +52,8 @@
 //!
 //! - `fast`: speed up template rendering (you can also select only some of the features from that feature group)
 //! - `glob_fs`: allows loading template on the filesystem using a glob
+//! - `loader`: allows loading templates on demand, see `Loader`
+//! - `watch`: reloads the templates loaded with a glob when their files change, see `TeraWatcher`
 //! - `unicode`: if you want Tera to work with graphemes clusters rather than utf-8 characters when iterating on strings
 //! - `preserve_order`: keep order of insertion for values
 //!
@@ -66,16 +68,24 @@
 #![deny(missing_docs)]
 
 mod args;
+mod bundle;
 mod components;
 mod context;
 mod delimiters;
+mod diagnostics;
 mod errors;
 mod filters;
+mod formatter;
 mod functions;
 #[cfg(feature = "glob_fs")]
 mod globbing;
+mod limits;
+mod lints;
+#[cfg(feature = "loader")]
+mod loader;
 mod parsing;
 mod reporting;
+mod symbols;
 mod template;
 mod tera;
 mod tests;
@@ -83,15 +93,23 @@ mod utils;
 /// The value type used by Tera and supporting types (`Key`, `Map`, `Number`, `ValueKind`).
 pub mod value;
 pub(crate) mod vm;
+#[cfg(feature = "watch")]
+mod watch;
 
 pub use crate::tera::{EscapeFn, Tera};
 pub use args::{ArgFromValue, Kwargs};
-pub use components::{ComponentArg, ComponentArgType, ComponentInfo};
+pub use bundle::compile_templates;
+pub use components::{ComponentArg, ComponentArgDefault, ComponentArgType, ComponentInfo};
 pub use context::Context;
 pub use delimiters::Delimiters;
+pub use diagnostics::{Diagnostic, Label, Severity};
 pub use errors::{Error, ErrorKind, ReportError, TeraResult};
 pub use filters::Filter;
 pub use functions::Function;
+pub use limits::{RenderLimits, ResourceLimit};
+#[cfg(feature = "loader")]
+pub use loader::{ChainLoader, FileSystemLoader, LoadedTemplate, Loader, MemoryLoader};
+pub use symbols::{Symbol, TemplateInfo, TemplateSymbols};
 pub use tests::{Test, TestResult};
 pub use utils::{Span, escape_html};
 #[doc(inline)]
@@ -99,15 +117,17 @@ pub use value::FunctionResult;
 pub use value::number::Number;
 pub use value::{Map, Value};
 pub use vm::state::State;
+#[cfg(feature = "watch")]
+pub use watch::TeraWatcher;
 
 #[cfg(feature = "glob_fs")]
 #[doc(hidden)]
 pub use globbing::load_from_glob;
 
 #[cfg(feature = "fast_hash")]
-pub(crate) use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+pub(crate) use ahash::AHashMap as HashMap;
 #[cfg(not(feature = "fast_hash"))]
-pub(crate) use std::collections::{HashMap, HashSet};
+pub(crate) use std::collections::HashMap;
 
 #[cfg(test)]
 mod snapshot_tests;
@@ -0,0 +1,245 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Write};
+
+/// The depth of `loop(...)`, lambda and component calls allowed by default, since going much
+/// deeper would overflow the stack.
+const DEFAULT_MAX_RECURSION_DEPTH: usize = 20;
+
+/// Limits on the resources a single render can use.
+///
+/// A limit set to `None` is disabled. All limits but `max_recursion_depth` are disabled by default:
+/// they are meant for rendering templates you don't control, for example written by your users,
+/// where a template could otherwise loop for a very long time or produce an enormous output.
+///
+/// Includes and components rendered by a template count against the same limits.
+///
+/// ```
+/// # use tera::{Tera, Context, RenderLimits};
+/// let mut tera = Tera::default();
+/// tera.set_render_limits(RenderLimits {
+///     max_instructions: Some(100_000),
+///     max_output_bytes: Some(1024 * 1024),
+///     ..RenderLimits::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderLimits {
+    /// Maximum number of VM instructions executed.
+    pub max_instructions: Option<u64>,
+    /// Maximum number of bytes written to the output. Strings built while rendering, by
+    /// concatenating with `~`, capturing with `{% set %}` blocks or with filters and functions,
+    /// can't be longer than that either.
+    pub max_output_bytes: Option<usize>,
+    /// Maximum nesting of includes and components. Blocks don't count.
+    /// Templates including themselves are errors even without it.
+    pub max_nesting_depth: Option<usize>,
+    /// Maximum number of elements in a list built while rendering, either from a literal,
+    /// a list comprehension or returned by a function like `range`.
+    pub max_list_length: Option<usize>,
+    /// Maximum depth of `loop(...)` calls in a recursive for loop, of lambdas calling lambdas and
+    /// of components rendering components, for example to stop a template calling `loop` on the
+    /// same data over and over until it overflows the stack.
+    /// Defaults to 20.
+    pub max_recursion_depth: Option<usize>,
+}
+
+impl Default for RenderLimits {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            max_output_bytes: None,
+            max_nesting_depth: None,
+            max_list_length: None,
+            max_recursion_depth: Some(DEFAULT_MAX_RECURSION_DEPTH),
+        }
+    }
+}
+
+/// Which of the [`RenderLimits`] was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResourceLimit {
+    /// `max_instructions`
+    Instructions,
+    /// `max_output_bytes`
+    OutputBytes,
+    /// `max_nesting_depth`
+    NestingDepth,
+    /// `max_list_length`
+    ListLength,
+    /// `max_recursion_depth`
+    RecursionDepth,
+}
+
+impl fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ResourceLimit::Instructions => "max_instructions",
+            ResourceLimit::OutputBytes => "max_output_bytes",
+            ResourceLimit::NestingDepth => "max_nesting_depth",
+            ResourceLimit::ListLength => "max_list_length",
+            ResourceLimit::RecursionDepth => "max_recursion_depth",
+        };
+        f.write_str(s)
+    }
+}
+
+/// What is left of the limits for the current render, shared by all the VMs
+/// (includes, components etc) involved in it.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    pub(crate) limits: RenderLimits,
+    instructions_left: Cell<u64>,
+    output_bytes: Cell<usize>,
+    output_exceeded: Cell<bool>,
+    depth: Cell<usize>,
+    lambda_depth: Cell<usize>,
+    component_depth: Cell<usize>,
+    /// The templates whose code included the template being rendered, outermost first
+    include_chain: RefCell<Vec<String>>,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: RenderLimits) -> Self {
+        Self {
+            limits,
+            instructions_left: Cell::new(limits.max_instructions.unwrap_or(u64::MAX)),
+            output_bytes: Cell::new(0),
+            output_exceeded: Cell::new(false),
+            depth: Cell::new(0),
+            lambda_depth: Cell::new(0),
+            component_depth: Cell::new(0),
+            include_chain: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns `false` if there are no instructions left to execute.
+    #[inline]
+    pub(crate) fn count_instruction(&self) -> bool {
+        let left = self.instructions_left.get();
+        if left == 0 {
+            return false;
+        }
+        self.instructions_left.set(left - 1);
+        true
+    }
+
+    #[inline]
+    pub(crate) fn output_exceeded(&self) -> bool {
+        self.output_exceeded.get()
+    }
+
+    /// Returns `false` if entering one more include/component goes over the limit.
+    /// `exit` needs to be called when leaving it, even if it errored.
+    pub(crate) fn enter(&self) -> bool {
+        let depth = self.depth.get() + 1;
+        self.depth.set(depth);
+        self.limits.max_nesting_depth.is_none_or(|max| depth <= max)
+    }
+
+    pub(crate) fn exit(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    /// Strings built while rendering, with `~`, `{% set %}` blocks, filters etc, count against
+    /// `max_output_bytes`: none of them can be longer than the output is allowed to be.
+    pub(crate) fn is_string_too_long(&self, len: usize) -> bool {
+        self.limits.max_output_bytes.is_some_and(|max| len > max)
+    }
+
+    pub(crate) fn is_list_too_long(&self, len: usize) -> bool {
+        self.limits.max_list_length.is_some_and(|max| len > max)
+    }
+
+    pub(crate) fn is_recursion_too_deep(&self, depth: usize) -> bool {
+        self.limits
+            .max_recursion_depth
+            .is_some_and(|max| depth > max)
+    }
+
+    /// Returns `false` if calling one more lambda from inside a lambda goes over
+    /// `max_recursion_depth`. `exit_lambda` needs to be called when it returns, even if it errored.
+    pub(crate) fn enter_lambda(&self) -> bool {
+        let depth = self.lambda_depth.get() + 1;
+        self.lambda_depth.set(depth);
+        !self.is_recursion_too_deep(depth)
+    }
+
+    pub(crate) fn exit_lambda(&self) {
+        self.lambda_depth.set(self.lambda_depth.get() - 1);
+    }
+
+    /// Returns `false` if rendering one more component from inside a component goes over
+    /// `max_recursion_depth`. `exit_component` needs to be called when it returns, even if it
+    /// errored.
+    pub(crate) fn enter_component(&self) -> bool {
+        let depth = self.component_depth.get() + 1;
+        self.component_depth.set(depth);
+        !self.is_recursion_too_deep(depth)
+    }
+
+    pub(crate) fn exit_component(&self) {
+        self.component_depth.set(self.component_depth.get() - 1);
+    }
+
+    /// Records that code from the `includer` template includes `name`, returning the include
+    /// chain instead if `name` is already being rendered, which would never end.
+    /// `exit_include` needs to be called when it returns unless it errored.
+    pub(crate) fn enter_include(&self, includer: &str, name: &str) -> Result<(), Vec<String>> {
+        let mut chain = self.include_chain.borrow_mut();
+        chain.push(includer.to_string());
+        if let Some(pos) = chain.iter().position(|t| t == name) {
+            let mut cycle = chain[pos..].to_vec();
+            cycle.push(name.to_string());
+            chain.pop();
+            return Err(cycle);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn exit_include(&self) {
+        self.include_chain.borrow_mut().pop();
+    }
+}
+
+/// Counts the bytes written to the final output.
+///
+/// Once over `max_output_bytes` nothing more gets written and the VM will notice it after the
+/// current instruction, so it can point at it in the error.
+pub(crate) struct CountingWriter<'a, W> {
+    inner: W,
+    budget: &'a Budget,
+}
+
+impl<'a, W: Write> CountingWriter<'a, W> {
+    pub(crate) fn new(inner: W, budget: &'a Budget) -> Self {
+        Self { inner, budget }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(max) = self.budget.limits.max_output_bytes else {
+            return self.inner.write(buf);
+        };
+        if self.budget.output_exceeded.get() {
+            return Ok(buf.len());
+        }
+        let written = self.budget.output_bytes.get();
+        let remaining = max - written;
+        if buf.len() > remaining {
+            self.inner.write_all(&buf[..remaining])?;
+            self.budget.output_bytes.set(max);
+            self.budget.output_exceeded.set(true);
+            return Ok(buf.len());
+        }
+        let n = self.inner.write(buf)?;
+        self.budget.output_bytes.set(written + n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,548 @@
+//! Warnings for templates that render fine but probably don't do what was intended.
+//!
+//! Most of them are found on the AST when a template is created and stored on it, the ones
+//! needing all the templates are done in `Tera::warnings`.
+use std::collections::HashSet;
+
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::parsing::ast::{ArrayEntry, Block, ComponentDefinition, Expression, MapEntry, Node};
+use crate::parsing::parser::ParserOutput;
+use crate::template::Template;
+use crate::utils::{Span, Spanned};
+use crate::vm::state::MAGICAL_DUMP_VAR;
+
+/// Whether the block body is only a `{{ super() }}`, ignoring whitespace
+fn only_calls_super(block: &Block) -> bool {
+    let mut nodes = block
+        .body
+        .iter()
+        .filter(|n| !matches!(n, Node::Content(c) if c.trim().is_empty()));
+    matches!(
+        (nodes.next(), nodes.next()),
+        (Some(Node::Expression(Expression::FunctionCall(f))), None) if f.name == "super"
+    )
+}
+
+struct Linter<'a> {
+    template: &'a str,
+    is_child: bool,
+    warnings: Vec<Diagnostic>,
+    /// The names defined by the template at the current point, innermost scope last
+    scopes: Vec<HashSet<String>>,
+    /// The loop variables defined in the current template body or component
+    loop_vars: Vec<&'a Spanned<String>>,
+    /// The variables read that were not defined by the template in the current template body
+    /// or component: they come from the context
+    context_reads: HashSet<&'a str>,
+    /// All the variables read, anywhere
+    reads: HashSet<&'a str>,
+    /// The variables set, with whether they are set at the top level of the template: blocks
+    /// of child templates can read those
+    sets: Vec<(&'a Spanned<String>, bool)>,
+    /// Whether the variables set could be read by something else: an included template or
+    /// the parent block rendered by `super()`
+    shares_context: bool,
+    /// Whether we are linting the template body rather than a component
+    in_template_body: bool,
+    /// How many blocks deep we are
+    block_depth: usize,
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, code: &'static str, span: &Span, message: String) {
+        self.warnings.push(
+            Diagnostic::new(code, Severity::Warning, message).with_location(self.template, span),
+        );
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.scopes.iter().any(|s| s.contains(name))
+    }
+
+    fn read(&mut self, name: &'a str) {
+        if name == MAGICAL_DUMP_VAR {
+            self.shares_context = true;
+        }
+        self.reads.insert(name);
+        if !name.starts_with("__tera_") && !self.is_defined(name) {
+            self.context_reads.insert(name);
+        }
+    }
+
+    fn define_loop_var(&mut self, var: &'a Spanned<String>) {
+        if self.is_defined(var) {
+            self.warn(
+                "shadowed-variable",
+                var.span(),
+                format!(
+                    "The loop variable `{}` shadows a variable defined before",
+                    **var
+                ),
+            );
+        } else {
+            self.loop_vars.push(var);
+        }
+        self.scopes.last_mut().unwrap().insert(var.to_string());
+    }
+
+    fn define_set(&mut self, name: &'a Spanned<String>, global: bool) {
+        let scope = if global {
+            self.scopes.first_mut()
+        } else {
+            self.scopes.last_mut()
+        };
+        scope.unwrap().insert(name.to_string());
+        let top_level =
+            self.in_template_body && self.block_depth == 0 && (global || self.scopes.len() == 1);
+        self.sets.push((name, top_level));
+    }
+
+    /// Lints the body of a template or of a component, which have their own context.
+    fn lint_unit(&mut self, nodes: &'a [Node], defined: HashSet<String>) {
+        self.scopes = vec![defined];
+        self.loop_vars.clear();
+        self.context_reads.clear();
+        self.visit_body(nodes);
+
+        for var in std::mem::take(&mut self.loop_vars) {
+            if self.context_reads.contains(var.as_str()) {
+                self.warn(
+                    "shadowed-variable",
+                    var.span(),
+                    format!(
+                        "The loop variable `{}` shadows the context variable of the same name",
+                        **var
+                    ),
+                );
+            }
+        }
+    }
+
+    fn visit_scoped_body(&mut self, nodes: &'a [Node]) {
+        self.scopes.push(HashSet::new());
+        self.visit_body(nodes);
+        self.scopes.pop();
+    }
+
+    fn visit_body(&mut self, nodes: &'a [Node]) {
+        let jump = nodes
+            .iter()
+            .position(|n| matches!(n, Node::Break(_) | Node::Continue(_)));
+        if let Some(idx) = jump {
+            let unreachable = nodes[idx + 1..]
+                .iter()
+                .any(|n| !matches!(n, Node::Content(c) if c.trim().is_empty()));
+            if unreachable {
+                let (keyword, span) = match &nodes[idx] {
+                    Node::Break(span) => ("break", span),
+                    Node::Continue(span) => ("continue", span),
+                    _ => unreachable!(),
+                };
+                self.warn(
+                    "unreachable-code",
+                    span,
+                    format!("Everything after this `{keyword}` in the same body is never rendered"),
+                );
+            }
+        }
+
+        for node in nodes {
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_node(&mut self, node: &'a Node) {
+        match node {
+            Node::Content(_) | Node::Break(_) | Node::Continue(_) => (),
+            Node::Expression(expr) => self.visit_expr(expr),
+            Node::Set(set) => {
+                self.visit_expr(&set.value);
+                self.define_set(&set.name, set.global);
+            }
+            Node::BlockSet(set) => {
+                for filter in &set.filters {
+                    self.visit_expr(filter);
+                }
+                self.visit_scoped_body(&set.body);
+                self.define_set(&set.name, set.global);
+            }
+            Node::Include(include) => {
+                self.visit_expr(&include.name);
+                if let Some(with) = &include.with {
+                    self.visit_expr(with);
+                }
+                if !include.only {
+                    self.shares_context = true;
+                }
+            }
+            Node::Block(block) => {
+                if self.is_child && only_calls_super(block) {
+                    self.warn(
+                        "redundant-block",
+                        block.name.span(),
+                        format!(
+                            "The block `{}` only calls `super()`: it renders the same without it",
+                            *block.name
+                        ),
+                    );
+                }
+                self.block_depth += 1;
+                self.visit_scoped_body(&block.body);
+                self.block_depth -= 1;
+            }
+            Node::ForLoop(forloop) => {
+                self.visit_expr(&forloop.target);
+                self.scopes.push(HashSet::new());
+                if let Some(key) = &forloop.key {
+                    self.define_loop_var(key);
+                }
+                self.define_loop_var(&forloop.value);
+                self.visit_body(&forloop.body);
+                self.scopes.pop();
+                self.visit_scoped_body(&forloop.else_body);
+            }
+            Node::If(cond) => {
+                if let Some(value) = cond.expr.as_value() {
+                    self.warn(
+                        "constant-condition",
+                        cond.expr.span(),
+                        format!("This condition is always `{}`", value.is_truthy()),
+                    );
+                }
+                self.visit_expr(&cond.expr);
+                self.visit_body(&cond.body);
+                self.visit_body(&cond.false_body);
+            }
+            Node::FilterSection(section) => {
+                for value in section.args.iter().chain(section.kwargs.values()) {
+                    self.visit_expr(value);
+                }
+                self.visit_scoped_body(&section.body);
+            }
+            Node::Slot(slot) => self.visit_scoped_body(&slot.body),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expression) {
+        match expr {
+            Expression::Const(_) => (),
+            Expression::Var(var) => self.read(&var.name),
+            Expression::Array(array) => {
+                for entry in &array.items {
+                    match entry {
+                        ArrayEntry::Item(e) | ArrayEntry::Spread(e) => self.visit_expr(e),
+                    }
+                }
+            }
+            Expression::Map(map) => {
+                for entry in &map.entries {
+                    match entry {
+                        MapEntry::KeyValue { value: e, .. } | MapEntry::Spread(e) => {
+                            self.visit_expr(e)
+                        }
+                    }
+                }
+            }
+            Expression::GetAttr(attr) => self.visit_expr(&attr.expr),
+            Expression::GetItem(item) => {
+                self.visit_expr(&item.expr);
+                self.visit_expr(&item.sub_expr);
+            }
+            Expression::Slice(slice) => {
+                self.visit_expr(&slice.expr);
+                for e in [&slice.start, &slice.end, &slice.step]
+                    .into_iter()
+                    .flatten()
+                {
+                    self.visit_expr(e);
+                }
+            }
+            Expression::Filter(filter) => {
+                self.visit_expr(&filter.expr);
+                for value in filter.args.iter().chain(filter.kwargs.values()) {
+                    self.visit_expr(value);
+                }
+            }
+            Expression::Test(test) => {
+                self.visit_expr(&test.expr);
+                for value in test.args.iter().chain(test.kwargs.values()) {
+                    self.visit_expr(value);
+                }
+            }
+            Expression::Ternary(ternary) => {
+                self.visit_expr(&ternary.expr);
+                self.visit_expr(&ternary.true_expr);
+                self.visit_expr(&ternary.false_expr);
+            }
+            Expression::ListComprehension(comprehension) => {
+                self.visit_expr(&comprehension.target);
+                self.scopes.push(
+                    comprehension
+                        .key
+                        .iter()
+                        .chain(Some(&comprehension.value))
+                        .cloned()
+                        .collect(),
+                );
+                self.visit_expr(&comprehension.expr);
+                if let Some(condition) = &comprehension.condition {
+                    self.visit_expr(condition);
+                }
+                self.scopes.pop();
+            }
+            Expression::ComponentCall(call) => {
+                for entry in &call.kwargs {
+                    match entry {
+                        MapEntry::KeyValue { value: e, .. } | MapEntry::Spread(e) => {
+                            self.visit_expr(e)
+                        }
+                    }
+                }
+                self.visit_scoped_body(&call.body);
+                for slot in &call.slots {
+                    self.visit_scoped_body(&slot.body);
+                }
+            }
+            Expression::FunctionCall(call) => {
+                if call.name == "super" {
+                    self.shares_context = true;
+                }
+                for value in call.args.iter().chain(call.kwargs.values()) {
+                    self.visit_expr(value);
+                }
+            }
+            Expression::LoopMethodCall(call) => {
+                for arg in &call.args {
+                    self.visit_expr(arg);
+                }
+            }
+            Expression::Lambda(lambda) => {
+                self.scopes
+                    .push(std::iter::once(lambda.param.clone()).collect());
+                self.visit_expr(&lambda.body);
+                self.scopes.pop();
+            }
+            Expression::UnaryOperation(op) => self.visit_expr(&op.expr),
+            Expression::BinaryOperation(op) => {
+                self.visit_expr(&op.left);
+                self.visit_expr(&op.right);
+            }
+        }
+    }
+}
+
+fn component_scope(component: &ComponentDefinition) -> HashSet<String> {
+    component
+        .kwargs
+        .keys()
+        .chain(&component.rest_param_name)
+        .cloned()
+        .chain(Some("body".to_string()))
+        .collect()
+}
+
+/// Lints a template that was just parsed.
+pub(crate) fn lint_template(name: &str, output: &ParserOutput) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        template: name,
+        is_child: output.parent.is_some(),
+        warnings: Vec::new(),
+        scopes: Vec::new(),
+        loop_vars: Vec::new(),
+        context_reads: HashSet::new(),
+        reads: HashSet::new(),
+        sets: Vec::new(),
+        shares_context: false,
+        in_template_body: true,
+        block_depth: 0,
+    };
+
+    linter.lint_unit(&output.nodes, HashSet::new());
+    linter.in_template_body = false;
+    for component in &output.component_definitions {
+        linter.lint_unit(&component.body, component_scope(component));
+    }
+
+    // The blocks of child templates are rendered with the top level variables of their parent
+    let defines_blocks = !output.block_spans.is_empty();
+    if !linter.shares_context {
+        for (set, top_level) in std::mem::take(&mut linter.sets) {
+            let maybe_read_by_child = top_level && defines_blocks;
+            if !maybe_read_by_child && !linter.reads.contains(set.as_str()) {
+                linter.warn(
+                    "unused-variable",
+                    set.span(),
+                    format!("The variable `{}` is set but never used", **set),
+                );
+            }
+        }
+    }
+
+    linter.warnings
+}
+
+/// Finds the components defined in that template that are not called by any template.
+pub(crate) fn unused_components(tpl: &Template, called: &HashSet<&str>) -> Vec<Diagnostic> {
+    tpl.component_definition_spans
+        .iter()
+        .filter(|(name, _)| !called.contains(name.as_str()))
+        .map(|(name, span)| {
+            Diagnostic::new(
+                "unused-component",
+                Severity::Warning,
+                format!("The component `{name}` is defined but never called"),
+            )
+            .with_location(&tpl.name, span)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Tera;
+
+    fn warnings(templates: Vec<(&str, &str)>) -> Vec<(String, &'static str, String)> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(templates).unwrap();
+        tera.warnings()
+            .into_iter()
+            .map(|w| {
+                let span = w.span().unwrap();
+                let template = w.template().unwrap();
+                let source = tera.templates[template].source.clone();
+                (
+                    template.to_string(),
+                    w.code(),
+                    source[span.range.clone()].to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn warning(code: &'static str, text: &str) -> (String, &'static str, String) {
+        ("tpl".to_string(), code, text.to_string())
+    }
+
+    #[test]
+    fn warns_about_unused_variables() {
+        let found = warnings(vec![(
+            "tpl",
+            "{% set a = 1 %}{% set b = 2 %}{% set c %}hi{% endset %}{% for x in xs %}{% set_global d = b %}{% endfor %}{{ [y for y in d] }}",
+        )]);
+        assert_eq!(
+            found,
+            vec![
+                warning("unused-variable", "a"),
+                warning("unused-variable", "c")
+            ]
+        );
+
+        // The variables might be used by something else
+        assert!(
+            warnings(vec![
+                ("tpl", "{% set a = 1 %}{% include 'other' %}"),
+                ("other", "{{ a }}")
+            ])
+            .is_empty()
+        );
+        assert!(warnings(vec![("tpl", "{% set a = 1 %}{{ __tera_context }}")]).is_empty());
+        assert_eq!(
+            warnings(vec![
+                (
+                    "tpl",
+                    "{% set a = 1 %}{% block b %}{% set c = 2 %}{% endblock %}"
+                ),
+                (
+                    "child",
+                    "{% extends 'tpl' %}{% block b %}{{ a }}{% endblock %}"
+                ),
+            ]),
+            vec![warning("unused-variable", "c")]
+        );
+    }
+
+    #[test]
+    fn warns_about_shadowed_loop_variables() {
+        let found = warnings(vec![(
+            "tpl",
+            "{{ item }}{% set i = 1 %}{% for i in items %}{% for k, item in i %}{{ k }}{% endfor %}{% endfor %}{% for item in items %}{% for item in item %}{% endfor %}{% endfor %}",
+        )]);
+        assert_eq!(
+            found,
+            vec![
+                warning("shadowed-variable", "i"),
+                warning("shadowed-variable", "item"),
+                warning("shadowed-variable", "item"),
+                warning("shadowed-variable", "item"),
+            ]
+        );
+
+        // Components have their own context
+        assert!(
+            warnings(vec![(
+                "tpl",
+                "{% component list(items) %}{% for item in items %}{{ item }}{% endfor %}{% endcomponent %}{{ item }}{{<list items={[]} />}}"
+            )])
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn warns_about_unused_components() {
+        let found = warnings(vec![
+            (
+                "components",
+                "{% component used() %}{{<nested />}}{% endcomponent %}{% component nested() %}{% endcomponent %}{% component ui.unused() %}{% endcomponent %}",
+            ),
+            ("tpl", "{{<used />}}"),
+        ]);
+        assert_eq!(
+            found,
+            vec![(
+                "components".to_string(),
+                "unused-component",
+                "ui.unused".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn warns_about_blocks_only_calling_super() {
+        let found = warnings(vec![
+            (
+                "base",
+                "{% block a %}{% endblock %}{% block b %}{% endblock %}{% block c %}{{ super() }}{% endblock %}",
+            ),
+            (
+                "tpl",
+                "{% extends 'base' %}{% block a %}\n  {{ super() }}\n{% endblock %}{% block b %}{{ super() }}!{% endblock %}",
+            ),
+        ]);
+        assert_eq!(found, vec![warning("redundant-block", "a")]);
+    }
+
+    #[test]
+    fn warns_about_unreachable_code() {
+        let found = warnings(vec![(
+            "tpl",
+            "{% for x in xs %}{% if x %}{% break %}\n{% endif %}{% continue %}{{ x }}{% endfor %}{% for x in xs %}{% if x %}{% continue %}{% else %}{% break %}{% endif %}{% endfor %}",
+        )]);
+        assert_eq!(found, vec![warning("unreachable-code", "continue")]);
+    }
+
+    #[test]
+    fn warns_about_constant_conditions() {
+        let found = warnings(vec![(
+            "tpl",
+            "{% if true %}{% elif 0 %}{% elif a %}{% endif %}{% if [1, 2] %}{% endif %}{{ 1 if true else 2 }}",
+        )]);
+        assert_eq!(
+            found,
+            vec![
+                warning("constant-condition", "true"),
+                warning("constant-condition", "0"),
+                warning("constant-condition", "[1, 2]"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use elsa::sync::FrozenMap;
+
+use crate::HashMap;
+use crate::errors::{Error, TeraResult};
+use crate::template::Template;
+
+/// A template returned by a [`Loader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedTemplate {
+    pub(crate) source: String,
+    pub(crate) path: Option<String>,
+}
+
+impl LoadedTemplate {
+    /// Creates a template from its source.
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            path: None,
+        }
+    }
+
+    /// Sets the path on disk this template was read from.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// The source of the template.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The path this template was read from, if any.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+}
+
+/// Something Tera can ask for templates it doesn't know about yet.
+///
+/// When a template is requested (by `render`, `{% extends %}` or `{% include %}`) and Tera
+/// doesn't have it, it will ask the loader for it and for any of its parents/includes it
+/// doesn't know about either, before parsing and validating them.
+///
+/// Returning `Ok(None)` means the loader doesn't have this template.
+pub trait Loader: Send + Sync {
+    /// Returns the template with that name, if the loader has it.
+    fn load(&self, name: &str) -> TeraResult<Option<LoadedTemplate>>;
+}
+
+impl<F> Loader for F
+where
+    F: Fn(&str) -> TeraResult<Option<LoadedTemplate>> + Send + Sync,
+{
+    fn load(&self, name: &str) -> TeraResult<Option<LoadedTemplate>> {
+        self(name)
+    }
+}
+
+/// A loader keeping templates in memory.
+///
+/// ```
+/// # use tera::{Tera, Context, MemoryLoader};
+/// let mut loader = MemoryLoader::new();
+/// loader.add("hello.txt", "Hello {{ name }}!");
+///
+/// let mut tera = Tera::default();
+/// tera.set_loader(loader);
+/// let out = tera.render("hello.txt", &tera::context! { name => "Bob" }).unwrap();
+/// assert_eq!(out, "Hello Bob!");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    templates: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    /// Creates an empty loader.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a template to the loader, replacing any existing one with that name.
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> &mut Self {
+        self.templates.insert(name.into(), source.into());
+        self
+    }
+}
+
+impl<N: Into<String>, S: Into<String>> FromIterator<(N, S)> for MemoryLoader {
+    fn from_iter<I: IntoIterator<Item = (N, S)>>(iter: I) -> Self {
+        Self {
+            templates: iter
+                .into_iter()
+                .map(|(n, s)| (n.into(), s.into()))
+                .collect(),
+        }
+    }
+}
+
+impl Loader for MemoryLoader {
+    fn load(&self, name: &str) -> TeraResult<Option<LoadedTemplate>> {
+        Ok(self.templates.get(name).map(LoadedTemplate::new))
+    }
+}
+
+/// A loader reading templates from a directory.
+///
+/// The template name is the path of the file relative to that directory, using `/` as
+/// separator. Names trying to escape the directory, eg `../secrets.txt`, are never loaded.
+#[derive(Debug, Clone)]
+pub struct FileSystemLoader {
+    root: PathBuf,
+}
+
+impl FileSystemLoader {
+    /// Creates a loader for templates in `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, name: &str) -> Option<PathBuf> {
+        let relative = Path::new(name);
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+        Some(self.root.join(relative))
+    }
+}
+
+impl Loader for FileSystemLoader {
+    fn load(&self, name: &str) -> TeraResult<Option<LoadedTemplate>> {
+        let Some(path) = self.path_for(name) else {
+            return Ok(None);
+        };
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| Error::chain(format!("Failed to read template '{:?}'", path), e))?;
+        let path_str = path.to_str().ok_or_else(|| {
+            Error::message(format!("Template path is not valid UTF-8: {:?}", path))
+        })?;
+        Ok(Some(LoadedTemplate::new(source).with_path(path_str)))
+    }
+}
+
+/// A loader asking several loaders in order, returning the first template found.
+#[derive(Default)]
+pub struct ChainLoader {
+    loaders: Vec<Box<dyn Loader>>,
+}
+
+impl ChainLoader {
+    /// Creates an empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a loader at the end of the chain.
+    pub fn with(mut self, loader: impl Loader + 'static) -> Self {
+        self.loaders.push(Box::new(loader));
+        self
+    }
+}
+
+impl Loader for ChainLoader {
+    fn load(&self, name: &str) -> TeraResult<Option<LoadedTemplate>> {
+        for loader in &self.loaders {
+            if let Some(tpl) = loader.load(name)? {
+                return Ok(Some(tpl));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl fmt::Debug for ChainLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainLoader")
+            .field("loaders", &self.loaders.len())
+            .finish()
+    }
+}
+
+/// Templates loaded on demand while rendering.
+///
+/// Entries are only ever added while the Tera instance is shared, never removed or replaced,
+/// so references to them can be handed out for as long as the instance is borrowed.
+#[derive(Default, Clone)]
+pub(crate) struct LazyTemplates {
+    templates: FrozenMap<String, Arc<Template>>,
+}
+
+impl LazyTemplates {
+    pub(crate) fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    /// Adds the templates, keeping the existing one if a template was loaded concurrently.
+    pub(crate) fn extend(&self, new_templates: impl IntoIterator<Item = Template>) {
+        for tpl in new_templates {
+            self.templates.insert(tpl.name.clone(), Arc::new(tpl));
+        }
+    }
+
+    pub(crate) fn names(&self) -> Vec<&str> {
+        self.templates
+            .keys_cloned()
+            .iter()
+            .filter_map(|name| self.templates.get(name))
+            .map(|tpl| tpl.name.as_str())
+            .collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.templates.as_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filesystem_loader_stays_in_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub").join("hello.html"), "hello").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let loader = FileSystemLoader::new(dir.path().join("sub"));
+        let loaded = loader.load("hello.html").unwrap().unwrap();
+        assert_eq!(loaded.source(), "hello");
+        assert!(loaded.path().unwrap().ends_with("hello.html"));
+        assert!(loader.load("missing.html").unwrap().is_none());
+        assert!(loader.load("../secret.txt").unwrap().is_none());
+        assert!(loader.load("/etc/passwd").unwrap().is_none());
+    }
+
+    #[test]
+    fn chain_loader_returns_first_match() {
+        let loader = ChainLoader::new()
+            .with(MemoryLoader::from_iter([("a", "first")]))
+            .with(MemoryLoader::from_iter([("a", "second"), ("b", "b")]))
+            .with(|name: &str| Ok((name == "c").then(|| LoadedTemplate::new("from fn"))));
+
+        assert_eq!(loader.load("a").unwrap().unwrap().source(), "first");
+        assert_eq!(loader.load("b").unwrap().unwrap().source(), "b");
+        assert_eq!(loader.load("c").unwrap().unwrap().source(), "from fn");
+        assert!(loader.load("d").unwrap().is_none());
+    }
+}
@@ -112,6 +112,10 @@ pub enum Expression {
     ListComprehension(Spanned<ListComprehension>),
     ComponentCall(Spanned<ComponentCall>),
     FunctionCall(Spanned<FunctionCall>),
+    /// `loop.changed(value)`, `loop.cycle("odd", "even")` or `loop(children)`
+    LoopMethodCall(Spanned<LoopMethodCall>),
+    /// `p => p.price * p.qty`, given to filters like `sort(key=...)`
+    Lambda(Spanned<Lambda>),
     UnaryOperation(Spanned<UnaryOperation>),
     BinaryOperation(Spanned<BinaryOperation>),
 }
@@ -136,6 +140,8 @@ impl Expression {
             Expression::Test(s) => s.span(),
             Expression::ComponentCall(s) => s.span(),
             Expression::FunctionCall(s) => s.span(),
+            Expression::LoopMethodCall(s) => s.span(),
+            Expression::Lambda(s) => s.span(),
             Expression::UnaryOperation(s) => s.span(),
             Expression::BinaryOperation(s) => s.span(),
             Expression::Var(s) => s.span(),
@@ -156,6 +162,8 @@ impl Expression {
             Expression::Test(s) => s.span_mut().expand(span),
             Expression::ComponentCall(s) => s.span_mut().expand(span),
             Expression::FunctionCall(s) => s.span_mut().expand(span),
+            Expression::LoopMethodCall(s) => s.span_mut().expand(span),
+            Expression::Lambda(s) => s.span_mut().expand(span),
             Expression::UnaryOperation(s) => s.span_mut().expand(span),
             Expression::BinaryOperation(s) => s.span_mut().expand(span),
             Expression::Var(s) => s.span_mut().expand(span),
@@ -188,6 +196,8 @@ impl fmt::Debug for Expression {
             Expression::ComponentCall(i) => fmt::Debug::fmt(i, f),
             Expression::Filter(i) => fmt::Debug::fmt(i, f),
             Expression::FunctionCall(i) => fmt::Debug::fmt(i, f),
+            Expression::LoopMethodCall(i) => fmt::Debug::fmt(i, f),
+            Expression::Lambda(i) => fmt::Debug::fmt(i, f),
             Expression::UnaryOperation(i) => fmt::Debug::fmt(i, f),
             Expression::BinaryOperation(i) => fmt::Debug::fmt(i, f),
             Expression::Var(i) => fmt::Debug::fmt(i, f),
@@ -227,6 +237,7 @@ impl fmt::Display for Expression {
                 ValueInner::None => write!(f, "null"),
                 ValueInner::Undefined => write!(f, "undefined"),
                 ValueInner::Bytes(_) => write!(f, "<bytes>"),
+                ValueInner::Lambda(_) => write!(f, "<lambda>"),
                 ValueInner::Map(s) => {
                     let mut buf: Vec<u8> = Vec::new();
                     format_map(s, &mut buf).expect("failed to write map to vec");
@@ -243,6 +254,8 @@ impl fmt::Display for Expression {
             Expression::ComponentCall(i) => write!(f, "{}", **i),
             Expression::Filter(i) => write!(f, "{}", **i),
             Expression::FunctionCall(i) => write!(f, "{}", **i),
+            Expression::LoopMethodCall(i) => write!(f, "{}", **i),
+            Expression::Lambda(i) => write!(f, "{}", **i),
             Expression::UnaryOperation(i) => write!(f, "{}", **i),
             Expression::BinaryOperation(i) => write!(f, "{}", **i),
             Expression::Var(i) => write!(f, "{}", **i),
@@ -255,10 +268,33 @@ impl fmt::Display for Expression {
     }
 }
 
+/// Writes the positional arguments and then the keyword arguments, sorted by name
+fn write_args(
+    f: &mut fmt::Formatter<'_>,
+    args: &[Expression],
+    kwargs: &HashMap<String, Expression>,
+) -> fmt::Result {
+    let mut keys = kwargs.keys().collect::<Vec<_>>();
+    keys.sort();
+    let args = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain(keys.into_iter().map(|k| format!("{}={}", k, kwargs[k])));
+    for (i, arg) in args.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{arg}")?;
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Filter {
     pub expr: Expression,
     pub name: String,
+    /// The arguments given by position, before the keyword ones
+    pub args: Vec<Expression>,
     pub kwargs: HashMap<String, Expression>,
 }
 
@@ -267,15 +303,7 @@ impl fmt::Display for Filter {
         write!(f, "(| {}", self.expr)?;
         write!(f, " {}", self.name)?;
         write!(f, "{{",)?;
-        let mut keys = self.kwargs.keys().collect::<Vec<_>>();
-        keys.sort();
-        for (i, k) in keys.iter().enumerate() {
-            if i == self.kwargs.len() - 1 {
-                write!(f, "{}={}", k, self.kwargs[*k])?
-            } else {
-                write!(f, "{}={}, ", k, self.kwargs[*k])?
-            }
-        }
+        write_args(f, &self.args, &self.kwargs)?;
         write!(f, "}})",)
     }
 }
@@ -398,6 +426,8 @@ impl Array {
 pub struct Test {
     pub expr: Expression,
     pub name: String,
+    /// The arguments given by position, before the keyword ones
+    pub args: Vec<Expression>,
     pub kwargs: HashMap<String, Expression>,
 }
 
@@ -407,15 +437,7 @@ impl fmt::Display for Test {
         write!(f, " {}", self.name)?;
         write!(f, "{{",)?;
 
-        let mut keys = self.kwargs.keys().collect::<Vec<_>>();
-        keys.sort();
-        for (i, k) in keys.iter().enumerate() {
-            if i == self.kwargs.len() - 1 {
-                write!(f, "{}={}", k, self.kwargs[*k])?
-            } else {
-                write!(f, "{}={}, ", k, self.kwargs[*k])?
-            }
-        }
+        write_args(f, &self.args, &self.kwargs)?;
 
         write!(f, "}})",)?;
         Ok(())
@@ -427,6 +449,8 @@ pub struct ComponentCall {
     pub name: String,
     pub kwargs: Vec<MapEntry>,
     pub body: Vec<Node>,
+    /// The named slots filled with `{% fill %}`
+    pub slots: Vec<Slot>,
     pub self_closing: bool,
 }
 
@@ -452,6 +476,9 @@ impl fmt::Display for ComponentCall {
                 write!(f, "{:?}", node)?;
             }
             write!(f, "]",)?;
+            for slot in &self.slots {
+                write!(f, "{:?}", slot)?;
+            }
 
             write!(f, "<{}/>", self.name)?;
         }
@@ -463,6 +490,8 @@ impl fmt::Display for ComponentCall {
 #[derive(Clone, Debug, PartialEq)]
 pub struct FunctionCall {
     pub name: String,
+    /// The arguments given by position, before the keyword ones
+    pub args: Vec<Expression>,
     pub kwargs: HashMap<String, Expression>,
 }
 
@@ -470,16 +499,41 @@ impl fmt::Display for FunctionCall {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)?;
         write!(f, "{{",)?;
-        let mut keys = self.kwargs.keys().collect::<Vec<_>>();
-        keys.sort();
-        for (i, k) in keys.iter().enumerate() {
-            if i == self.kwargs.len() - 1 {
-                write!(f, "{}={}", k, self.kwargs[*k])?
-            } else {
-                write!(f, "{}={}, ", k, self.kwargs[*k])?
+        write_args(f, &self.args, &self.kwargs)?;
+        write!(f, "}}",)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopMethod {
+    /// Whether the value is different from the one it was called with in the previous iteration
+    Changed,
+    /// Picks one of the values in turn
+    Cycle,
+    /// `loop(children)` in a recursive loop: renders the loop body for each of the children
+    Recurse,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopMethodCall {
+    pub method: LoopMethod,
+    pub args: Vec<Expression>,
+}
+
+impl fmt::Display for LoopMethodCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.method {
+            LoopMethod::Changed => write!(f, "loop.changed(")?,
+            LoopMethod::Cycle => write!(f, "loop.cycle(")?,
+            LoopMethod::Recurse => write!(f, "loop(")?,
+        };
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
             }
+            write!(f, "{arg}")?;
         }
-        write!(f, "}}",)
+        write!(f, ")")
     }
 }
 
@@ -582,7 +636,7 @@ impl fmt::Display for GetItem {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Set {
     /// The name for that value in the context
-    pub name: String,
+    pub name: Spanned<String>,
     /// The value to assign
     pub value: Expression,
     /// Whether we want to set the variable globally or locally
@@ -594,7 +648,7 @@ pub struct Set {
 #[derive(Clone, Debug, PartialEq)]
 pub struct BlockSet {
     /// The name for that value in the context
-    pub name: String,
+    pub name: Spanned<String>,
     /// The filters to apply to the block, with a dummy source set to null
     pub filters: Vec<Expression>,
     /// The content of the block
@@ -607,7 +661,22 @@ pub struct BlockSet {
 /// A template to include
 #[derive(Clone, Debug, PartialEq)]
 pub struct Include {
+    /// The template name: a string or an array of names to try in order
+    pub name: Expression,
+    /// Whether to render nothing if none of the templates exist
+    pub ignore_missing: bool,
+    /// A map of variables to add to the ones the included template can see
+    pub with: Option<Expression>,
+    /// Whether the included template can only see the variables given with `with`
+    pub only: bool,
+}
+
+/// A named slot: its fallback content in a component definition with `{% slot %}`, or the
+/// content filling it in a component call with `{% fill %}`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slot {
     pub name: Spanned<String>,
+    pub body: Vec<Node>,
 }
 
 /// A block definition
@@ -634,12 +703,14 @@ pub struct If {
 #[derive(Clone, Debug, PartialEq)]
 pub struct FilterSection {
     pub name: Spanned<String>,
+    /// The arguments given by position, before the keyword ones
+    pub args: Vec<Expression>,
     pub kwargs: HashMap<String, Expression>,
     /// The filter body
     pub body: Vec<Node>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Type {
     String,
     Bool,
@@ -649,6 +720,15 @@ pub enum Type {
     Array,
     Map,
     Bytes,
+    /// `"primary" | "secondary"`: one of the given literals
+    OneOf(Vec<Value>),
+    /// `array<map>`: an array where all items are of the given type
+    ArrayOf(Box<Type>),
+    /// `{title: string, url: string?}`: a map with the given keys. Keys with an optional type can
+    /// be missing and other keys are allowed.
+    MapWithKeys(Vec<(String, Type)>),
+    /// `string?`: the given type or `none`
+    Optional(Box<Type>),
 }
 
 impl FromStr for Type {
@@ -671,21 +751,21 @@ impl FromStr for Type {
     }
 }
 
-impl Type {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Type::String => "string",
-            Type::Bool => "bool",
-            Type::Integer => "integer",
-            Type::Float => "float",
-            Type::Number => "number",
-            Type::Array => "array",
-            Type::Map => "map",
-            Type::Bytes => "bytes",
-        }
+/// Formats a literal the way it's written in templates, eg with quotes for strings
+pub(crate) fn fmt_literal(val: &Value) -> String {
+    match val.as_str() {
+        Some(s) => format!("{s:?}"),
+        None => val.to_string(),
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::ComponentArgType::from(self).fmt(f)
     }
+}
 
-    #[inline]
+impl Type {
     pub fn matches_value(&self, value: &Value) -> bool {
         use crate::value::ValueKind;
         match self {
@@ -700,6 +780,71 @@ impl Type {
             Type::Map => value.is_map(),
             Type::Array => value.is_array(),
             Type::Bytes => value.is_bytes(),
+            Type::OneOf(values) => values.contains(value),
+            Type::ArrayOf(t) => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(|i| t.matches_value(i))),
+            Type::MapWithKeys(keys) => value.as_map().is_some_and(|map| {
+                keys.iter().all(|(k, t)| match map.get(&Key::Str(k)) {
+                    Some(v) => t.matches_value(v),
+                    None => t.is_optional(),
+                })
+            }),
+            Type::Optional(t) => value.is_none() || t.matches_value(value),
+        }
+    }
+
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Type::Optional(_))
+    }
+
+    /// Explains why a value that doesn't match this type doesn't match it, `path` being how to
+    /// refer to that value.
+    fn mismatch(&self, value: &Value, path: &str) -> Mismatch {
+        let inner = |t: &Type, v: &Value, path: String| match t.mismatch(v, &path) {
+            Mismatch::Kind => Mismatch::Inner(format!(
+                "`{path}` is of type `{}` instead of `{t}`",
+                v.name()
+            )),
+            m => m,
+        };
+
+        match self {
+            Type::OneOf(_) if value.is_string() || value.is_number() => Mismatch::Inner(format!(
+                "`{path}` is {} which is not one of the allowed values",
+                fmt_literal(value)
+            )),
+            Type::ArrayOf(t) => value
+                .as_array()
+                .and_then(|items| items.iter().enumerate().find(|(_, i)| !t.matches_value(i)))
+                .map(|(i, item)| inner(t, item, format!("{path}[{i}]")))
+                .unwrap_or(Mismatch::Kind),
+            Type::MapWithKeys(keys) => value
+                .as_map()
+                .and_then(|map| {
+                    keys.iter().find_map(|(k, t)| match map.get(&Key::Str(k)) {
+                        Some(v) if !t.matches_value(v) => Some(inner(t, v, format!("{path}.{k}"))),
+                        None if !t.is_optional() => {
+                            Some(Mismatch::Inner(format!("`{path}.{k}` is missing")))
+                        }
+                        _ => None,
+                    })
+                })
+                .unwrap_or(Mismatch::Kind),
+            Type::Optional(t) => t.mismatch(value, path),
+            _ => Mismatch::Kind,
+        }
+    }
+
+    /// The error message for the value of a component argument not matching this type
+    pub(crate) fn arg_mismatch_message(&self, key: &str, value: &Value) -> String {
+        let msg = format!(
+            "Component argument `{key}` (type: `{}`) does not match expected type: `{self}`",
+            value.name()
+        );
+        match self.mismatch(value, key) {
+            Mismatch::Kind => msg,
+            Mismatch::Inner(reason) => format!("{msg}: {reason}"),
         }
     }
 
@@ -716,37 +861,97 @@ impl Type {
             ValueKind::Array => Some(Type::Array),
             ValueKind::Map => Some(Type::Map),
             ValueKind::Bytes => Some(Type::Bytes),
-            ValueKind::Undefined | ValueKind::None => None,
+            ValueKind::Undefined | ValueKind::None | ValueKind::Lambda => None,
         }
     }
 }
 
+/// Why a value does not match a type
+enum Mismatch {
+    /// The value is not of the right kind, eg a string instead of an array
+    Kind,
+    /// Something about the value itself or what it contains, eg an item of an array
+    Inner(String),
+}
+
+/// The default value of a component argument
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgDefault {
+    /// A literal, applied as is
+    Value(Value),
+    /// An expression evaluated when the component is called, with its source.
+    /// The component chunk starts by evaluating it if the argument was not passed.
+    Expression(String),
+}
+
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct ComponentArgument {
-    pub default: Option<Value>,
+    pub default: Option<ArgDefault>,
     pub typ: Option<Type>,
 }
 
 impl ComponentArgument {
+    /// Arguments without a default are required, unless their type is optional in which case
+    /// they are `none` when missing
+    pub fn is_required(&self) -> bool {
+        self.default.is_none() && !self.typ.as_ref().is_some_and(Type::is_optional)
+    }
+
     #[inline]
     pub fn type_matches(&self, value: &Value) -> bool {
-        self.typ.map(|t| t.matches_value(value)).unwrap_or(true)
+        self.typ
+            .as_ref()
+            .map(|t| t.matches_value(value))
+            .unwrap_or(true)
+    }
+
+    fn type_mismatch_message(&self, key: &str, value: &Value) -> String {
+        self.typ.as_ref().unwrap().arg_mismatch_message(key, value)
+    }
+
+    fn missing_message(&self, key: &str) -> String {
+        let typ_msg = self
+            .typ
+            .as_ref()
+            .map(|t| format!(" (type: `{t}`)"))
+            .unwrap_or_default();
+        format!("Argument `{key}`{typ_msg} missing.")
     }
 }
 
+/// What is known before rendering about the arguments of a component call.
+/// Not present in the AST, the compiler records them so they can be checked once all the
+/// components are known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComponentCallSite {
+    pub name: String,
+    pub span: Span,
+    /// The arguments passed by name, in order, with their value if it's a literal that no
+    /// map spread after it could override
+    pub kwargs: Vec<(String, Option<Value>)>,
+    /// Whether a map is spread in the arguments, in which case we can't know all of them
+    pub has_spread: bool,
+    /// The slots filled, with the span of their name
+    pub slots: Vec<(String, Span)>,
+}
+
 /// A component definition `{% component hello() %}...{% endcomponent %}`
 /// Not present in the AST, we extract them during parsing
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct ComponentDefinition {
     pub name: String,
     /// The args for that component: name -> optional default value
-    /// Expression for default args can only be literals
     pub kwargs: BTreeMap<String, ComponentArgument>,
+    /// The default expressions of the arguments that have one, in definition order.
+    /// Like the body, they are only needed until the component is compiled
+    pub default_exprs: Vec<(String, Expression)>,
     /// Rest parameter name (e.g., `...rest` collects extra kwargs into `rest`)
     /// If None, unknown kwargs will error.
     pub rest_param_name: Option<String>,
     /// Component metadata that you might need at compile time
     pub metadata: BTreeMap<String, Value>,
+    /// The names of the slots defined in the body, in order
+    pub slots: Vec<String>,
     pub body: Vec<Node>,
 }
 
@@ -755,7 +960,92 @@ impl ComponentDefinition {
         self.kwargs.keys().map(|k| k.as_str()).collect()
     }
 
+    fn unknown_args_message<'a>(&self, unknown_keys: impl Iterator<Item = &'a str>) -> String {
+        let kwargs_list = self.kwargs_list();
+        let kwargs_msg = if kwargs_list.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " Possible argument(s) are: {}",
+                kwargs_list
+                    .iter()
+                    .map(|s| format!("`{s}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        let unknown_list = unknown_keys
+            .map(|s| format!("`{s}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Unknown argument(s) {unknown_list} in component call.{kwargs_msg}")
+    }
+
+    /// Checks what can be known before rendering about a call to that component: the arguments
+    /// that don't exist, the literal values of the wrong type and, if no map is spread in the
+    /// call, the required arguments missing.
+    /// It returns the same errors `build_context` would return when rendering.
+    pub(crate) fn check_call(&self, call: &ComponentCallSite) -> Result<(), String> {
+        if self.rest_param_name.is_none() {
+            let unknown_keys: Vec<_> = call
+                .kwargs
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .filter(|key| !self.kwargs.contains_key(*key))
+                .collect();
+            if !unknown_keys.is_empty() {
+                return Err(self.unknown_args_message(unknown_keys.into_iter()));
+            }
+        }
+
+        for (key, arg_def) in &self.kwargs {
+            match call.kwargs.iter().rev().find(|(k, _)| k == key) {
+                Some((_, Some(value))) if !arg_def.type_matches(value) => {
+                    return Err(arg_def.type_mismatch_message(key, value));
+                }
+                None if !call.has_spread && arg_def.is_required() => {
+                    return Err(arg_def.missing_message(key));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the slots filled in a call are defined, returning the error message and the
+    /// span of the first unknown one otherwise.
+    pub(crate) fn check_slots<'a>(
+        &self,
+        call: &'a ComponentCallSite,
+    ) -> Result<(), (String, &'a Span)> {
+        let Some((name, span)) = call
+            .slots
+            .iter()
+            .find(|(name, _)| !self.slots.contains(name))
+        else {
+            return Ok(());
+        };
+        let slots_msg = if self.slots.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " Possible slot(s) are: {}",
+                self.slots
+                    .iter()
+                    .map(|s| format!("`{s}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        Err((
+            format!("Unknown slot `{name}` in component call.{slots_msg}"),
+            span,
+        ))
+    }
+
     /// Builds a validated context from provided kwargs, checking types and applying defaults.
+    /// Default expressions are not applied: the component evaluates them when rendering.
     /// If rest_param_name is defined, unknown kwargs are collected into it.
     /// Otherwise, unknown kwargs will error.
     pub fn build_context<'a>(
@@ -784,27 +1074,7 @@ impl ComponentDefinition {
         }
 
         if !unknown_keys.is_empty() {
-            let kwargs_list = self.kwargs_list();
-            let kwargs_msg = if kwargs_list.is_empty() {
-                String::new()
-            } else {
-                format!(
-                    " Possible argument(s) are: {}",
-                    kwargs_list
-                        .iter()
-                        .map(|s| format!("`{s}`"))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
-            };
-            let unknown_list = unknown_keys
-                .iter()
-                .map(|s| format!("`{s}`"))
-                .collect::<Vec<_>>()
-                .join(", ");
-            return Err(format!(
-                "Unknown argument(s) {unknown_list} in component call.{kwargs_msg}"
-            ));
+            return Err(self.unknown_args_message(unknown_keys.iter().map(|k| k.as_str())));
         }
 
         // Validate and apply each expected argument
@@ -812,25 +1082,20 @@ impl ComponentDefinition {
             match get_value(key) {
                 Some(value) => {
                     if !arg_def.type_matches(&value) {
-                        return Err(format!(
-                            "Component argument `{key}` (type: `{}`) does not match expected type: `{}`",
-                            value.name(),
-                            arg_def.typ.unwrap().as_str()
-                        ));
+                        return Err(arg_def.type_mismatch_message(key, &value));
                     }
                     context.insert_value(key.clone(), value);
                 }
                 None => match &arg_def.default {
-                    Some(default_value) => {
+                    Some(ArgDefault::Value(default_value)) => {
                         context.insert_value(key.clone(), default_value.clone());
                     }
-                    None => {
-                        let typ_msg = arg_def
-                            .typ
-                            .map(|t| format!(" (type: `{}`)", t.as_str()))
-                            .unwrap_or_default();
-                        return Err(format!("Argument `{key}`{typ_msg} missing."));
+                    // Evaluated by the component itself
+                    Some(ArgDefault::Expression(_)) => (),
+                    None if !arg_def.is_required() => {
+                        context.insert_value(key.clone(), Value::none());
                     }
+                    None => return Err(arg_def.missing_message(key)),
                 },
             }
         }
@@ -853,15 +1118,17 @@ impl ComponentDefinition {
 #[derive(Clone, Debug, PartialEq)]
 pub struct ForLoop {
     /// Name of the key in the loop (only when iterating on map-like objects)
-    pub key: Option<String>,
+    pub key: Option<Spanned<String>>,
     /// Name of the local variable for the value in the loop
-    pub value: String,
+    pub value: Spanned<String>,
     /// Expression being iterated on
     pub target: Expression,
     /// What's in the forloop itself
     pub body: Vec<Node>,
     /// The body to execute in case of an empty object in the `{% for .. %}{% else %}{% endfor %}` construct
     pub else_body: Vec<Node>,
+    /// `{% for .. in .. recursive %}`: the body can call `loop(children)` to iterate on children
+    pub recursive: bool,
 }
 
 /// A `[id | str for id in ids if id > 0]` construct like in Python
@@ -899,6 +1166,21 @@ impl fmt::Display for ListComprehension {
     }
 }
 
+/// A `p => p.price * p.qty` lambda, compiled to its own chunk
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lambda {
+    /// The `p` in the example
+    pub param: String,
+    /// The `p.price * p.qty` in the example
+    pub body: Expression,
+}
+
+impl fmt::Display for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({} => {})", self.param, self.body)
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum Node {
     Content(String),
@@ -908,10 +1190,14 @@ pub enum Node {
     Include(Include),
     Block(Block),
     ForLoop(ForLoop),
-    Break,
-    Continue,
+    /// `{% break %}`, with the span of the tag name
+    Break(Span),
+    /// `{% continue %}`, with the span of the tag name
+    Continue(Span),
     If(If),
     FilterSection(FilterSection),
+    /// `{% slot %}` in a component definition
+    Slot(Slot),
 }
 
 impl fmt::Debug for Node {
@@ -928,8 +1214,9 @@ impl fmt::Debug for Node {
             ForLoop(s) => fmt::Debug::fmt(s, f),
             If(s) => fmt::Debug::fmt(s, f),
             FilterSection(s) => fmt::Debug::fmt(s, f),
-            Break => fmt::Debug::fmt("{% break %}", f),
-            Continue => fmt::Debug::fmt("{% continue %}", f),
+            Slot(s) => fmt::Debug::fmt(s, f),
+            Break(_) => fmt::Debug::fmt("{% break %}", f),
+            Continue(_) => fmt::Debug::fmt("{% continue %}", f),
         }
     }
 }
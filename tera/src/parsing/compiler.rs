@@ -4,9 +4,10 @@ use std::collections::HashSet;
 
 use crate::HashMap;
 use crate::parsing::ast::{
-    ArrayEntry, BinaryOperator, Block, Expression, MapEntry, Node, UnaryOperator,
+    ArrayEntry, BinaryOperator, Block, ComponentCallSite, ComponentDefinition, Expression,
+    LoopMethod, MapEntry, Node, Slot, UnaryOperator,
 };
-use crate::parsing::instructions::{Chunk, Instruction};
+use crate::parsing::instructions::{Chunk, IncludeContext, Instruction, Lambda};
 use crate::utils::Span;
 use crate::value::Value;
 
@@ -31,13 +32,18 @@ pub(crate) struct Compiler {
     block_depth: usize,
     /// Tracks all various calls with their location for error reporting
     pub(crate) component_calls: HashMap<String, Vec<Span>>,
+    pub(crate) component_call_sites: Vec<ComponentCallSite>,
     pub(crate) filter_calls: HashMap<String, Vec<Span>>,
     pub(crate) test_calls: HashMap<String, Vec<Span>>,
     pub(crate) function_calls: HashMap<String, Vec<Span>>,
     pub(crate) include_calls: HashMap<String, Vec<Span>>,
+    /// Includes that can be missing: with `ignore missing` or with a list of candidates
+    pub(crate) optional_include_calls: HashMap<String, Vec<Span>>,
     pub(crate) top_level_variables: HashSet<String>,
     /// Represents variables set by a loop or by set
     pub(crate) temp_variables: Vec<HashSet<String>>,
+    /// The idx of the `RecursiveLoop` instructions of the recursive loops being compiled
+    recursive_loops: Vec<usize>,
 }
 
 impl Compiler {
@@ -46,20 +52,32 @@ impl Compiler {
             chunk: Chunk::new(name),
             processing_bodies: Vec::new(),
             component_calls: HashMap::new(),
+            component_call_sites: Vec::new(),
             filter_calls: HashMap::new(),
             test_calls: HashMap::new(),
             function_calls: HashMap::new(),
             include_calls: HashMap::new(),
+            optional_include_calls: HashMap::new(),
             blocks: HashMap::new(),
             block_name_spans: HashMap::new(),
             top_level_variables: HashSet::default(),
             temp_variables: vec![HashSet::new()],
+            recursive_loops: Vec::new(),
             block_depth: 0,
         }
     }
 
-    fn compile_kwargs(&mut self, kwargs: HashMap<String, Expression>) {
-        let num_args = kwargs.len();
+    /// The positional arguments are stored in the kwargs map under their index and get their
+    /// name at runtime from the filter/test/function called.
+    fn compile_kwargs(&mut self, args: Vec<Expression>, kwargs: HashMap<String, Expression>) {
+        let num_args = args.len() + kwargs.len();
+        for (idx, value) in args.into_iter().enumerate() {
+            self.chunk.add(
+                Instruction::LoadConst(Value::from(idx)),
+                Some(value.span().clone()),
+            );
+            self.compile_expr(value);
+        }
         // TODO: push a single instr for all keys as a Vec<String> like Python? bench first
         for (key, value) in kwargs {
             self.chunk.add(
@@ -71,6 +89,39 @@ impl Compiler {
         self.chunk.add(Instruction::BuildMap(num_args), None);
     }
 
+    fn record_component_call_site(
+        &mut self,
+        name: &str,
+        entries: &[MapEntry],
+        slots: &[Slot],
+        span: &Span,
+    ) {
+        let mut call_site = ComponentCallSite {
+            name: name.to_string(),
+            span: span.clone(),
+            kwargs: Vec::with_capacity(entries.len()),
+            has_spread: false,
+            slots: slots
+                .iter()
+                .map(|s| (s.name.to_string(), s.name.span().clone()))
+                .collect(),
+        };
+        for entry in entries {
+            match entry {
+                MapEntry::KeyValue { key, value } => {
+                    call_site.kwargs.push((key.to_string(), value.as_value()));
+                }
+                MapEntry::Spread(_) => {
+                    call_site.has_spread = true;
+                    for (_, value) in &mut call_site.kwargs {
+                        *value = None;
+                    }
+                }
+            }
+        }
+        self.component_call_sites.push(call_site);
+    }
+
     fn compile_map_entries(&mut self, entries: Vec<MapEntry>, span: Option<Span>) {
         let has_spreads = entries.iter().any(|e| matches!(e, MapEntry::Spread(_)));
 
@@ -222,7 +273,7 @@ impl Compiler {
             Expression::Filter(e) => {
                 let (filter, span) = e.into_parts();
                 self.compile_expr(filter.expr);
-                self.compile_kwargs(filter.kwargs);
+                self.compile_kwargs(filter.args, filter.kwargs);
                 self.filter_calls
                     .entry(filter.name.clone())
                     .or_default()
@@ -233,7 +284,7 @@ impl Compiler {
             Expression::Test(e) => {
                 let (test, span) = e.into_parts();
                 self.compile_expr(test.expr);
-                self.compile_kwargs(test.kwargs);
+                self.compile_kwargs(test.args, test.kwargs);
                 self.test_calls
                     .entry(test.name.clone())
                     .or_default()
@@ -308,8 +359,27 @@ impl Compiler {
                     .entry(component_call.name.clone())
                     .or_default()
                     .push(span.clone());
+                self.record_component_call_site(
+                    &component_call.name,
+                    &component_call.kwargs,
+                    &component_call.slots,
+                    &span,
+                );
 
                 if !component_call.self_closing {
+                    let num_slots = component_call.slots.len();
+                    for slot in component_call.slots {
+                        let (name, name_span) = slot.name.into_parts();
+                        self.chunk
+                            .add(Instruction::LoadConst(Value::from(name)), None);
+                        self.chunk.add(Instruction::Capture, None);
+                        for node in slot.body {
+                            self.compile_node(node);
+                        }
+                        self.chunk.add(Instruction::EndCapture, Some(name_span));
+                    }
+                    self.chunk.add(Instruction::BuildMap(num_slots), None);
+
                     self.chunk.add(Instruction::Capture, None);
                     for node in component_call.body {
                         self.compile_node(node);
@@ -333,7 +403,7 @@ impl Compiler {
             }
             Expression::FunctionCall(e) => {
                 let (func, span) = e.into_parts();
-                self.compile_kwargs(func.kwargs);
+                self.compile_kwargs(func.args, func.kwargs);
                 self.function_calls
                     .entry(func.name.clone())
                     .or_default()
@@ -341,6 +411,52 @@ impl Compiler {
                 self.chunk
                     .add(Instruction::CallFunction(func.name), Some(span));
             }
+            Expression::LoopMethodCall(e) => {
+                let (call, span) = e.into_parts();
+                let num_args = call.args.len();
+                for arg in call.args {
+                    self.compile_expr(arg);
+                }
+                let instr = match call.method {
+                    LoopMethod::Changed => Instruction::LoopChanged,
+                    LoopMethod::Cycle => Instruction::LoopCycle,
+                    // The parser ensures it has a single argument: the value to iterate on
+                    LoopMethod::Recurse => Instruction::RecurseLoop(
+                        *self
+                            .recursive_loops
+                            .last()
+                            .expect("loop() to only be used in recursive loops"),
+                    ),
+                };
+                if call.method != LoopMethod::Recurse {
+                    self.chunk
+                        .add(Instruction::BuildList(num_args), Some(span.clone()));
+                }
+                self.chunk.add(instr, Some(span));
+            }
+            Expression::Lambda(e) => {
+                let (lambda, span) = e.into_parts();
+                let chunk_name = self.chunk.name.clone();
+                let parent_chunk = std::mem::replace(&mut self.chunk, Chunk::new(&chunk_name));
+                let parent_bodies = std::mem::take(&mut self.processing_bodies);
+                self.temp_variables
+                    .push(HashSet::from([lambda.param.clone()]));
+                self.compile_expr(lambda.body);
+                self.temp_variables.pop();
+                let mut chunk = std::mem::replace(&mut self.chunk, parent_chunk);
+                self.processing_bodies = parent_bodies;
+                chunk.optimize();
+
+                let lambda = Lambda::new(lambda.param, chunk);
+                let captures = !lambda.captures.is_empty();
+                self.chunk.add(
+                    Instruction::LoadConst(Value::lambda(lambda)),
+                    Some(span.clone()),
+                );
+                if captures {
+                    self.chunk.add(Instruction::CaptureLambda, Some(span));
+                }
+            }
             Expression::UnaryOperation(e) => {
                 let (op, span) = e.into_parts();
                 self.compile_expr(op.expr);
@@ -434,10 +550,12 @@ impl Compiler {
         self.processing_bodies = parent_bodies;
 
         if is_top_level {
-            self.block_name_spans.insert(block_name.clone(), block_span);
+            self.block_name_spans
+                .insert(block_name.clone(), block_span.clone());
         }
         self.blocks.insert(block_name.clone(), block_chunk);
-        self.chunk.add(Instruction::RenderBlock(block_name), None);
+        self.chunk
+            .add(Instruction::RenderBlock(block_name), Some(block_span));
     }
 
     fn end_branch(&mut self, idx: usize) {
@@ -476,11 +594,12 @@ impl Compiler {
                 } else {
                     self.temp_variables.last_mut()
                 };
-                scope.unwrap().insert(s.name.clone());
+                let (name, _) = s.name.into_parts();
+                scope.unwrap().insert(name.clone());
                 let instr = if s.global {
-                    Instruction::SetGlobal(s.name)
+                    Instruction::SetGlobal(name)
                 } else {
-                    Instruction::Set(s.name)
+                    Instruction::Set(name)
                 };
                 self.chunk.add(instr, None);
             }
@@ -495,7 +614,7 @@ impl Compiler {
                 for expr in b.filters {
                     if let Expression::Filter(f) = expr {
                         let (filter, span) = f.into_parts();
-                        self.compile_kwargs(filter.kwargs);
+                        self.compile_kwargs(filter.args, filter.kwargs);
                         self.filter_calls
                             .entry(filter.name.clone())
                             .or_default()
@@ -509,34 +628,75 @@ impl Compiler {
                 } else {
                     self.temp_variables.last_mut()
                 };
-                scope.unwrap().insert(b.name.clone());
+                let (name, _) = b.name.into_parts();
+                scope.unwrap().insert(name.clone());
                 let instr = if b.global {
-                    Instruction::SetGlobal(b.name)
+                    Instruction::SetGlobal(name)
                 } else {
-                    Instruction::Set(b.name)
+                    Instruction::Set(name)
                 };
                 self.chunk.add(instr, None);
             }
             Node::Include(i) => {
-                let (name, span) = i.name.into_parts();
-                self.include_calls
-                    .entry(name.clone())
-                    .or_default()
-                    .push(span.clone());
-                self.chunk.add(Instruction::Include(name), Some(span));
+                let span = i.name.span().clone();
+                // We can only know about the templates included when the names are literals
+                if let Some(value) = i.name.as_value() {
+                    let (calls, names) = match value.as_array() {
+                        Some(names) => (&mut self.optional_include_calls, names),
+                        None if i.ignore_missing => (
+                            &mut self.optional_include_calls,
+                            std::slice::from_ref(&value),
+                        ),
+                        None => (&mut self.include_calls, std::slice::from_ref(&value)),
+                    };
+                    for name in names.iter().filter_map(|n| n.as_str()) {
+                        calls
+                            .entry(name.to_string())
+                            .or_default()
+                            .push(span.clone());
+                    }
+                }
+                self.compile_expr(i.name);
+                let context = match (i.with, i.only) {
+                    (Some(with), only) => {
+                        self.compile_expr(with);
+                        if only {
+                            IncludeContext::Only
+                        } else {
+                            IncludeContext::Extend
+                        }
+                    }
+                    (None, true) => {
+                        self.chunk
+                            .add(Instruction::LoadConst(Value::empty_map()), None);
+                        IncludeContext::Only
+                    }
+                    (None, false) => IncludeContext::Inherit,
+                };
+                self.chunk
+                    .add(Instruction::Include(i.ignore_missing, context), Some(span));
             }
             Node::Block(b) => {
                 self.compile_block(b);
             }
             Node::ForLoop(forloop) => {
                 self.compile_expr(forloop.target);
-                self.chunk
-                    .add(Instruction::StartIterate(forloop.key.is_some()), None);
+                if forloop.recursive {
+                    let idx = self
+                        .chunk
+                        .add(Instruction::RecursiveLoop(forloop.key.is_some(), 0), None);
+                    self.recursive_loops.push(idx as usize);
+                } else {
+                    self.chunk
+                        .add(Instruction::StartIterate(forloop.key.is_some()), None);
+                }
                 // The value is sent before the key to be consistent with a value only loop
                 let mut loop_vars = HashSet::new();
-                loop_vars.insert(forloop.value.clone());
-                self.chunk.add(Instruction::StoreLocal(forloop.value), None);
+                let (value_var, _) = forloop.value.into_parts();
+                loop_vars.insert(value_var.clone());
+                self.chunk.add(Instruction::StoreLocal(value_var), None);
                 if let Some(key_var) = forloop.key {
+                    let (key_var, _) = key_var.into_parts();
                     loop_vars.insert(key_var.clone());
                     self.chunk.add(Instruction::StoreLocal(key_var), None);
                 }
@@ -583,11 +743,26 @@ impl Compiler {
                     }
                     self.end_branch(self.chunk.len());
                 }
+
+                if forloop.recursive {
+                    let idx = self
+                        .recursive_loops
+                        .pop()
+                        .expect("to have a recursive loop");
+                    let end = self.chunk.len();
+                    if let Some((Instruction::RecursiveLoop(_, loop_end), _)) =
+                        self.chunk.get_mut(idx)
+                    {
+                        *loop_end = end;
+                    } else {
+                        unreachable!();
+                    }
+                }
             }
-            Node::Break => {
+            Node::Break(_) => {
                 self.chunk.add(Instruction::Break, None);
             }
-            Node::Continue => {
+            Node::Continue(_) => {
                 if let ProcessingBody::Loop(idx) = self.get_current_loop().unwrap() {
                     self.chunk.add(Instruction::Jump(*idx), None);
                 }
@@ -614,6 +789,17 @@ impl Compiler {
 
                 self.end_branch(self.chunk.len());
             }
+            Node::Slot(slot) => {
+                let (name, span) = slot.name.into_parts();
+                let idx = self.chunk.add(Instruction::RenderSlot(name, 0), Some(span)) as usize;
+                for node in slot.body {
+                    self.compile_node(node);
+                }
+                let end = self.chunk.len();
+                if let Some((Instruction::RenderSlot(_, target), _)) = self.chunk.get_mut(idx) {
+                    *target = end;
+                }
+            }
             Node::FilterSection(f) => {
                 self.chunk.add(Instruction::Capture, None);
                 for node in f.body {
@@ -621,7 +807,7 @@ impl Compiler {
                 }
                 self.chunk
                     .add(Instruction::EndCapture, Some(f.name.span().clone()));
-                self.compile_kwargs(f.kwargs);
+                self.compile_kwargs(f.args, f.kwargs);
                 let (filter_name, span) = f.name.into_parts();
                 self.filter_calls
                     .entry(filter_name.clone())
@@ -634,6 +820,30 @@ impl Compiler {
         }
     }
 
+    /// Evaluates the default expressions of the arguments not passed to the component, before
+    /// its body. They run in definition order so they can use the arguments before them.
+    pub(crate) fn compile_arg_defaults(&mut self, def: &ComponentDefinition) {
+        for (name, expr) in &def.default_exprs {
+            let idx = self
+                .chunk
+                .add(Instruction::JumpIfArgGiven(name.as_str().into(), 0), None)
+                as usize;
+            let span = expr.span().clone();
+            self.compile_expr(expr.clone());
+            self.chunk.add(
+                Instruction::SetArgDefault(
+                    name.as_str().into(),
+                    def.kwargs[name].typ.clone().map(Box::new),
+                ),
+                Some(span),
+            );
+            let end = self.chunk.len();
+            if let Some((Instruction::JumpIfArgGiven(_, target), _)) = self.chunk.get_mut(idx) {
+                *target = end;
+            }
+        }
+    }
+
     pub fn compile(&mut self, nodes: Vec<Node>) {
         for node in nodes {
             self.compile_node(node);
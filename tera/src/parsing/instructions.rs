@@ -1,9 +1,12 @@
+use crate::parsing::ast::Type;
 use crate::utils::Span;
 use crate::value::Value;
 use crate::vm::state::MAGICAL_DUMP_VAR;
+use std::collections::{BTreeSet, HashSet};
 use std::fmt;
 use std::fmt::Formatter;
 use std::ops::RangeInclusive;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Instruction {
@@ -31,8 +34,9 @@ pub(crate) enum Instruction {
     Set(String),
     /// Set the last value on the stack in the global context. Same as Set outside of loops.
     SetGlobal(String),
-    /// Include the given template
-    Include(String),
+    /// Include the first existing template named by a value on the stack, a string or an
+    /// array of strings. The bool is whether to render nothing if none of them exist
+    Include(bool, IncludeContext),
 
     /// Create a map for the kwargs of a function or for inline maps.
     /// Inner field is the number of values
@@ -51,6 +55,22 @@ pub(crate) enum Instruction {
     RenderInlineComponent(String),
     /// Render the given component with body
     RenderBodyComponent(String),
+    /// Write the content of the given slot if the component call filled it and jump to the
+    /// given idx, skipping the fallback content
+    RenderSlot(String, usize),
+    /// Jump to the given idx if the component argument was passed in the call, skipping the
+    /// evaluation of its default expression.
+    /// The names of these 2 are boxed to keep the instructions small.
+    JumpIfArgGiven(Box<str>, usize),
+    /// Set the value on the top of the stack as the value of the component argument, erroring if
+    /// it doesn't match the argument type
+    SetArgDefault(Box<str>, Option<Box<Type>>),
+    /// Push whether the value on the top of the stack is different from the one given the
+    /// previous time, for `loop.changed`
+    LoopChanged,
+    /// Replace the list on the top of the stack by the element to use in the current iteration,
+    /// for `loop.cycle`
+    LoopCycle,
     /// Apply the given filter
     ApplyFilter(String),
     /// Run the given test
@@ -78,6 +98,12 @@ pub(crate) enum Instruction {
     /// Like `StartIterate` but for list comprehension. It's a separate instruction
     /// since we don't expose loop.* things in it despite desugaring to a loop
     StartIterateComprehension(bool),
+    /// Start a recursive for loop. Like `StartIterate` but the loop, up to the given idx, is run
+    /// as its own unit so `RecurseLoop` can run it again on other values
+    RecursiveLoop(bool, usize),
+    /// Render the recursive loop starting at the given idx for the value on the top of the stack
+    /// and push the output, for `loop(children)`
+    RecurseLoop(usize),
     /// Start to iterate on the value at the top of the stack. The integer is the ip to jump to
     /// when the for loop is over
     Iterate(usize),
@@ -93,6 +119,9 @@ pub(crate) enum Instruction {
 
     /// Used by list comprehensions
     AppendToList,
+    /// Replace the lambda on the top of the stack by a copy holding the values of the
+    /// variables it uses, so it doesn't depend on the scope it is called from
+    CaptureLambda,
 
     // math
     Mul,
@@ -128,6 +157,58 @@ pub(crate) enum Instruction {
     WritePath(Vec<String>),
 }
 
+/// The variables an included template can see
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum IncludeContext {
+    /// The ones of the template including it
+    Inherit,
+    /// The ones of the template including it and the map on the top of the stack, which
+    /// takes precedence
+    Extend,
+    /// Only the ones of the map on the top of the stack
+    Only,
+}
+
+/// A compiled `x => ...` lambda: running its chunk leaves the result on the stack.
+/// It is passed around as a value and run by filters with `State::call_lambda`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Lambda {
+    pub(crate) param: String,
+    pub(crate) chunk: Arc<Chunk>,
+    /// The variables of the template used by the body, with their values in the scope where
+    /// the lambda is written once `CaptureLambda` ran. They are undefined in the compiled
+    /// constant.
+    pub(crate) captures: Vec<(String, Value)>,
+}
+
+impl Lambda {
+    pub(crate) fn new(param: String, chunk: Chunk) -> Self {
+        let captures = chunk
+            .used_variables(&param)
+            .into_iter()
+            .map(|name| (name, Value::undefined()))
+            .collect();
+        Self {
+            param,
+            chunk: Arc::new(chunk),
+            captures,
+        }
+    }
+
+    /// A copy of this lambda with the values of its variables taken from `get`
+    pub(crate) fn capture(&self, get: impl Fn(&str) -> Value) -> Self {
+        Self {
+            param: self.param.clone(),
+            chunk: self.chunk.clone(),
+            captures: self
+                .captures
+                .iter()
+                .map(|(name, _)| (name.clone(), get(name)))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Default)]
 pub(crate) struct Chunk {
     /// Instructions with their associated spans.
@@ -153,6 +234,17 @@ impl Chunk {
         idx as u32
     }
 
+    pub(crate) fn from_instructions(
+        name: String,
+        instructions: Vec<(Instruction, Vec<Span>)>,
+    ) -> Self {
+        Self { instructions, name }
+    }
+
+    pub(crate) fn instructions(&self) -> &[(Instruction, Vec<Span>)] {
+        &self.instructions
+    }
+
     pub(crate) fn get(&self, idx: usize) -> Option<&(Instruction, Vec<Span>)> {
         self.instructions.get(idx)
     }
@@ -186,6 +278,19 @@ impl Chunk {
             .and_then(|(_, spans)| spans.get(span_idx))
     }
 
+    /// Get the span of the closest instruction at or before `idx` that has one, falling back
+    /// to the first one after it.
+    /// Used to report errors on instructions without spans, like jumps.
+    pub(crate) fn closest_span(&self, idx: u32) -> Option<&Span> {
+        let split = (idx as usize + 1).min(self.instructions.len());
+        let (before, after) = self.instructions.split_at(split);
+        before
+            .iter()
+            .rev()
+            .chain(after)
+            .find_map(|(_, spans)| spans.first())
+    }
+
     /// Expand a range of span indices into a single Span.
     /// Takes the start position from the first span and end position from the last span.
     pub(crate) fn expand_span(&self, range: &RangeInclusive<u32>) -> Option<Span> {
@@ -204,6 +309,34 @@ impl Chunk {
         Some(expanded)
     }
 
+    /// The variables read by the chunk that are not set by it or the lambda parameter given,
+    /// including the ones read by the lambdas it creates.
+    pub(crate) fn used_variables(&self, param: &str) -> BTreeSet<String> {
+        let mut used = BTreeSet::new();
+        let mut locals = HashSet::from([param, MAGICAL_DUMP_VAR]);
+        for (instr, _) in &self.instructions {
+            match instr {
+                Instruction::LoadName(name) => {
+                    used.insert(name.clone());
+                }
+                Instruction::LoadPath(path) | Instruction::WritePath(path) => {
+                    used.insert(path[0].clone());
+                }
+                Instruction::StoreLocal(name) => {
+                    locals.insert(name);
+                }
+                Instruction::LoadConst(val) => {
+                    if let Some(lambda) = val.as_lambda() {
+                        used.extend(lambda.captures.iter().map(|(name, _)| name.clone()));
+                    }
+                }
+                _ => (),
+            }
+        }
+        used.retain(|name| !locals.contains(name.as_str()));
+        used
+    }
+
     /// Optimize bytecode by combining common instruction patterns to avoid pushing/popping
     /// so much on the stack in the VM when we can
     pub(crate) fn optimize(&mut self) {
@@ -223,7 +356,11 @@ impl Chunk {
             | Instruction::PopJumpIfFalse(t)
             | Instruction::JumpIfFalseOrPop(t)
             | Instruction::JumpIfTrueOrPop(t)
-            | Instruction::Iterate(t) = instr
+            | Instruction::Iterate(t)
+            | Instruction::RenderSlot(_, t)
+            | Instruction::JumpIfArgGiven(_, t)
+            | Instruction::RecursiveLoop(_, t)
+            | Instruction::RecurseLoop(t) = instr
                 && *t < is_jump_target.len()
             {
                 is_jump_target[*t] = true;
@@ -321,7 +458,11 @@ impl Chunk {
                 | Instruction::PopJumpIfFalse(target)
                 | Instruction::JumpIfFalseOrPop(target)
                 | Instruction::JumpIfTrueOrPop(target)
-                | Instruction::Iterate(target) => {
+                | Instruction::Iterate(target)
+                | Instruction::RenderSlot(_, target)
+                | Instruction::JumpIfArgGiven(_, target)
+                | Instruction::RecursiveLoop(_, target)
+                | Instruction::RecurseLoop(target) => {
                     *target = index_map[*target];
                 }
                 _ => {}
@@ -105,9 +105,11 @@ pub enum Token<'a> {
     Tilde,
     Pipe,
     Assign,
+    FatArrow,
 
     // Rest
     Dot,
+    QuestionMark,
     QuestionMarkDot,
     QuestionMarkLeftBracket,
     Comma,
@@ -149,12 +151,14 @@ impl<'a> fmt::Debug for Token<'a> {
             Token::Mod => write!(f, "MOD"),
             Token::Bang => write!(f, "BANG"),
             Token::Dot => write!(f, "DOT"),
+            Token::QuestionMark => write!(f, "QUESTION_MARK"),
             Token::QuestionMarkDot => write!(f, "QUESTION_MARK_DOT"),
             Token::QuestionMarkLeftBracket => write!(f, "QUESTION_MARK_LEFT_BRACKET"),
             Token::Comma => write!(f, "COMMA"),
             Token::Colon => write!(f, "COLON"),
             Token::Tilde => write!(f, "TILDE"),
             Token::Assign => write!(f, "ASSIGN"),
+            Token::FatArrow => write!(f, "FAT_ARROW"),
             Token::Pipe => write!(f, "PIPE"),
             Token::Equal => write!(f, "EQ"),
             Token::NotEqual => write!(f, "NE"),
@@ -199,12 +203,14 @@ impl<'a> fmt::Display for Token<'a> {
             Token::Mod => write!(f, "`%`"),
             Token::Bang => write!(f, "`!`"),
             Token::Dot => write!(f, "`.`"),
+            Token::QuestionMark => write!(f, "`?`"),
             Token::QuestionMarkDot => write!(f, "`?.`"),
             Token::QuestionMarkLeftBracket => write!(f, "`?[`"),
             Token::Comma => write!(f, "`,`"),
             Token::Colon => write!(f, "`:`"),
             Token::Tilde => write!(f, "`~`"),
             Token::Assign => write!(f, "`=`"),
+            Token::FatArrow => write!(f, "`=>`"),
             Token::Pipe => write!(f, "`|`"),
             Token::Equal => write!(f, "`==`"),
             Token::NotEqual => write!(f, "`!=`"),
@@ -224,7 +230,8 @@ impl<'a> fmt::Display for Token<'a> {
     }
 }
 
-fn basic_tokenize(
+/// Tokenizes the input without applying the whitespace control.
+pub(crate) fn basic_tokenize(
     input: &str,
     delimiters: Delimiters,
 ) -> impl Iterator<Item = Result<(Token<'_>, Span), Error>> {
@@ -563,6 +570,7 @@ fn basic_tokenize(
                         Some(b"</") => Some(Token::ClosingTagStart),
                         Some(b"?.") => Some(Token::QuestionMarkDot),
                         Some(b"?[") => Some(Token::QuestionMarkLeftBracket),
+                        Some(b"=>") => Some(Token::FatArrow),
                         _ => None,
                     };
                     if let Some(op) = op {
@@ -584,6 +592,7 @@ fn basic_tokenize(
                         Some(b':') => Some(Token::Colon),
                         Some(b'~') => Some(Token::Tilde),
                         Some(b'|') => Some(Token::Pipe),
+                        Some(b'?') => Some(Token::QuestionMark),
                         Some(b'=') => Some(Token::Assign),
                         Some(b'>') => Some(Token::GreaterThan),
                         Some(b'<') => Some(Token::LessThan),
@@ -3,19 +3,19 @@ use std::iter::Peekable;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::HashMap;
 use crate::delimiters::Delimiters;
 use crate::errors::{Error, ErrorKind, ReportError, TeraResult};
 use crate::parsing::ast::{
-    Array, ArrayEntry, BinaryOperation, Block, BlockSet, ComponentArgument, ComponentCall,
-    ComponentDefinition, Expression, Filter, FilterSection, ForLoop, FunctionCall, GetAttr,
-    GetItem, If, Include, ListComprehension, Map, MapEntry, Set, Slice, Ternary, Test, Type,
-    UnaryOperation, Var,
+    ArgDefault, Array, ArrayEntry, BinaryOperation, Block, BlockSet, ComponentArgument,
+    ComponentCall, ComponentDefinition, Expression, Filter, FilterSection, ForLoop, FunctionCall,
+    GetAttr, GetItem, If, Include, Lambda, ListComprehension, LoopMethod, LoopMethodCall, Map,
+    MapEntry, Set, Slice, Slot, Ternary, Test, Type, UnaryOperation, Var,
 };
 use crate::parsing::ast::{BinaryOperator, Node, UnaryOperator};
 use crate::parsing::lexer::{Token, tokenize};
 use crate::utils::{Span, Spanned};
 use crate::value::{Key, Value};
-use crate::{HashMap, HashSet};
 
 /// Maximum recursion depth for the parser, shared between expression and statement parsing
 const MAX_RECURSION_DEPTH: usize = 40;
@@ -89,12 +89,16 @@ const RESERVED_NAMES: [&str; 16] = [
 /// or continue/break in for
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum BodyContext {
-    ForLoop,
+    ForLoop {
+        recursive: bool,
+    },
     Block,
     If,
     ComponentDefinition,
     /// A filter section/set block/component call
     Capture,
+    /// The body of a `x => ...` lambda, which runs outside of the loops around it
+    Lambda,
 }
 
 impl BodyContext {
@@ -107,9 +111,14 @@ impl BodyContext {
 pub struct ParserOutput {
     // filled when we encounter a {% extends %}
     pub(crate) parent: Option<String>,
+    pub(crate) parent_span: Option<Span>,
     // The AST for the body
     pub(crate) nodes: Vec<Node>,
     pub(crate) component_definitions: Vec<ComponentDefinition>,
+    // The span of the name of every block defined, including nested ones
+    pub(crate) block_spans: HashMap<String, Span>,
+    // The span of the name of every component defined
+    pub(crate) component_spans: HashMap<String, Span>,
 }
 
 pub struct Parser<'a> {
@@ -130,8 +139,10 @@ pub struct Parser<'a> {
     recursion_depth: usize,
     // We limit the number of nesting for brackets in idents
     num_left_brackets: usize,
-    blocks_seen: HashSet<String>,
+    blocks_seen: HashMap<String, Span>,
     components_seen: HashMap<String, Span>,
+    // The slots of the component definition being parsed
+    slots_seen: Vec<(String, Span)>,
     output: ParserOutput,
 }
 
@@ -148,8 +159,9 @@ impl<'a> Parser<'a> {
             recursion_depth: 0,
             array_dimension: 0,
             num_left_brackets: 0,
-            blocks_seen: HashSet::with_capacity(10),
+            blocks_seen: HashMap::with_capacity(10),
             components_seen: HashMap::with_capacity(10),
+            slots_seen: Vec::new(),
             output: ParserOutput::default(),
         }
     }
@@ -205,7 +217,9 @@ impl<'a> Parser<'a> {
     }
 
     fn is_in_loop(&self) -> bool {
-        self.body_contexts.contains(&BodyContext::ForLoop)
+        self.body_contexts
+            .iter()
+            .any(|c| matches!(c, BodyContext::ForLoop { .. }))
     }
 
     // Parse something in brackets [..] after an ident or a literal array/map
@@ -290,13 +304,21 @@ impl<'a> Parser<'a> {
     fn parse_ident(&mut self, ident: &str) -> TeraResult<Expression> {
         let mut start_span = self.current_span.clone();
 
+        if matches!(self.next, Some(Ok((Token::FatArrow, _)))) {
+            return self.parse_lambda(ident, start_span);
+        }
+
         // A function call must come directly after the ident: `foo(...)`.
         if matches!(self.next, Some(Ok((Token::LeftParen, _)))) {
-            let kwargs = self.parse_kwargs()?;
+            if ident == "loop" {
+                return self.parse_recursive_loop_call(start_span);
+            }
+            let (args, kwargs) = self.parse_kwargs()?;
             start_span.expand(&self.current_span);
             return Ok(Expression::FunctionCall(Spanned::new(
                 FunctionCall {
                     name: ident.to_owned(),
+                    args,
                     kwargs,
                 },
                 start_span,
@@ -320,28 +342,20 @@ impl<'a> Parser<'a> {
                         expect_token!(self, Token::Dot, ".")?;
                     }
                     let (attr, span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
-                    if ident == "loop" && self.is_in_loop() {
-                        let new_name = match attr {
-                            "index" => "__tera_loop_index",
-                            "index0" => "__tera_loop_index0",
-                            "first" => "__tera_loop_first",
-                            "last" => "__tera_loop_last",
-                            "length" => "__tera_loop_length",
-                            _ => {
-                                return Err(Error::syntax_error(
-                                    format!(
-                                        "Found invalid field of `loop`: {attr}. Only `index`, `index0`, `first`, `last` and `length` exist."
-                                    ),
-                                    &self.current_span,
-                                ));
-                            }
-                        };
-
-                        expr = Expression::Var(Spanned::new(
-                            Var {
-                                name: new_name.to_string(),
-                            },
-                            span.clone(),
+                    // Only the first attribute of `loop` is special
+                    if ident == "loop"
+                        && self.is_in_loop()
+                        && matches!(&expr, Expression::Var(v) if v.name == "loop")
+                    {
+                        expr = self.parse_loop_attribute(attr, span)?;
+                    } else if ident == "loop"
+                        && matches!(attr, "changed" | "cycle")
+                        && matches!(&expr, Expression::Var(v) if v.name == "loop")
+                        && matches!(self.next, Some(Ok((Token::LeftParen, _))))
+                    {
+                        return Err(Error::syntax_error(
+                            format!("`loop.{attr}` can only be used inside a for loop."),
+                            &span,
                         ));
                     } else {
                         expr = Expression::GetAttr(Spanned::new(
@@ -373,7 +387,174 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    fn parse_kwargs(&mut self) -> TeraResult<HashMap<String, Expression>> {
+    /// Parses what comes after `loop.`, `attr` being the first attribute.
+    /// They are turned into magic variables read from the for loops, apart from the methods.
+    fn parse_loop_attribute(
+        &mut self,
+        mut attr: &'a str,
+        mut span: Span,
+    ) -> TeraResult<Expression> {
+        let num_loops = self
+            .body_contexts
+            .iter()
+            .filter(|c| matches!(c, BodyContext::ForLoop { .. }))
+            .count();
+        let mut num_parents = 0;
+        while attr == "parent" {
+            num_parents += 1;
+            if num_parents >= num_loops {
+                return Err(Error::syntax_error(
+                    "There is no parent loop here: `loop.parent` can only be used in nested loops."
+                        .to_string(),
+                    &self.current_span,
+                ));
+            }
+            expect_token!(self, Token::Dot, ".")?;
+            (attr, _) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+        }
+        span.expand(&self.current_span);
+
+        let method = match attr {
+            "index" | "index0" | "first" | "last" | "length" | "revindex" | "revindex0"
+            | "previtem" | "nextitem" | "depth" | "depth0" => {
+                return Ok(Expression::Var(Spanned::new(
+                    Var {
+                        name: format!("__tera_loop_{}{attr}", "parent_".repeat(num_parents)),
+                    },
+                    span,
+                )));
+            }
+            "changed" | "cycle" if num_parents == 0 && self.is_in_lambda() => {
+                return Err(Error::syntax_error(
+                    format!("`loop.{attr}` cannot be used in a lambda."),
+                    &self.current_span,
+                ));
+            }
+            "changed" if num_parents == 0 => LoopMethod::Changed,
+            "cycle" if num_parents == 0 => LoopMethod::Cycle,
+            _ => {
+                let methods = if num_parents == 0 {
+                    ", as well as the `changed` and `cycle` methods"
+                } else {
+                    ""
+                };
+                return Err(Error::syntax_error(
+                    format!(
+                        "Found invalid field of `loop{}`: {attr}. Only `index`, `index0`, `first`, `last`, `length`, `revindex`, `revindex0`, `previtem`, `nextitem`, `depth`, `depth0` and `parent` exist{methods}.",
+                        ".parent".repeat(num_parents)
+                    ),
+                    &self.current_span,
+                ));
+            }
+        };
+
+        let args = self.parse_positional_args()?;
+        span.expand(&self.current_span);
+        if args.is_empty() {
+            return Err(Error::syntax_error(
+                format!("`loop.{attr}` needs to be given at least one value."),
+                &span,
+            ));
+        }
+
+        Ok(Expression::LoopMethodCall(Spanned::new(
+            LoopMethodCall { method, args },
+            span,
+        )))
+    }
+
+    /// Parses `loop(children)` in the body of a recursive for loop, which renders the loop
+    /// body again for each of the children.
+    fn parse_recursive_loop_call(&mut self, mut span: Span) -> TeraResult<Expression> {
+        let innermost_loop = self.body_contexts.iter().rev().find_map(|c| match c {
+            BodyContext::ForLoop { recursive } => Some(*recursive),
+            BodyContext::Lambda => Some(false),
+            _ => None,
+        });
+        if innermost_loop != Some(true) {
+            return Err(Error::syntax_error(
+                "`loop(...)` can only be used directly in the body of a `recursive` for loop."
+                    .to_string(),
+                &self.current_span,
+            ));
+        }
+        let args = self.parse_positional_args()?;
+        span.expand(&self.current_span);
+        if args.len() != 1 {
+            return Err(Error::syntax_error(
+                format!(
+                    "`loop(...)` needs to be given exactly one value to iterate on, got {}.",
+                    args.len()
+                ),
+                &span,
+            ));
+        }
+
+        Ok(Expression::LoopMethodCall(Spanned::new(
+            LoopMethodCall {
+                method: LoopMethod::Recurse,
+                args,
+            },
+            span,
+        )))
+    }
+
+    /// Parses `x => <expr>`, `param` being the `x` that was already consumed.
+    fn parse_lambda(&mut self, param: &str, mut span: Span) -> TeraResult<Expression> {
+        if RESERVED_NAMES.contains(&param) {
+            return Err(Error::syntax_error(
+                format!(
+                    "{param} is a reserved keyword of Tera, it cannot be used as a lambda parameter."
+                ),
+                &self.current_span,
+            ));
+        }
+        expect_token!(self, Token::FatArrow, "=>")?;
+        self.body_contexts.push(BodyContext::Lambda);
+        let body = self.parse_expression(0)?;
+        self.body_contexts.pop();
+        span.expand(body.span());
+
+        Ok(Expression::Lambda(Spanned::new(
+            Lambda {
+                param: param.to_string(),
+                body,
+            },
+            span,
+        )))
+    }
+
+    /// Whether we are in a lambda inside the innermost loop
+    fn is_in_lambda(&self) -> bool {
+        self.body_contexts
+            .iter()
+            .rev()
+            .take_while(|c| !matches!(c, BodyContext::ForLoop { .. }))
+            .any(|c| *c == BodyContext::Lambda)
+    }
+
+    /// Parses `(a, b, ...)`, allowing a trailing comma.
+    fn parse_positional_args(&mut self) -> TeraResult<Vec<Expression>> {
+        expect_token!(self, Token::LeftParen, "(")?;
+        let mut args = Vec::new();
+        while !matches!(self.next, Some(Ok((Token::RightParen, _)))) {
+            if !args.is_empty() {
+                expect_token!(self, Token::Comma, ",")?;
+                // trailing comma
+                if matches!(self.next, Some(Ok((Token::RightParen, _)))) {
+                    break;
+                }
+            }
+            args.push(self.parse_expression(0)?);
+        }
+        expect_token!(self, Token::RightParen, ")")?;
+        Ok(args)
+    }
+
+    /// Parses the arguments of a filter/test/function call: the positional ones first, eg `(2)`,
+    /// followed by the keyword ones, eg `(precision=2)`.
+    fn parse_kwargs(&mut self) -> TeraResult<(Vec<Expression>, HashMap<String, Expression>)> {
+        let mut args = Vec::new();
         let mut kwargs = HashMap::new();
         let mut kwarg_spans: HashMap<&str, Span> = HashMap::new();
         expect_token!(self, Token::LeftParen, "(")?;
@@ -383,7 +564,7 @@ impl<'a> Parser<'a> {
                 break;
             }
 
-            if !kwargs.is_empty() {
+            if !args.is_empty() || !kwargs.is_empty() {
                 expect_token!(self, Token::Comma, ",")?;
             }
 
@@ -391,6 +572,21 @@ impl<'a> Parser<'a> {
                 break;
             }
 
+            let is_kwarg = matches!(self.next, Some(Ok((Token::Ident(_), _))))
+                && matches!(self.lexer.peek(), Some(Ok((Token::Assign, _))));
+            if !is_kwarg {
+                let value = self.parse_expression(0)?;
+                if !kwargs.is_empty() {
+                    return Err(Error::syntax_error(
+                        "Positional arguments need to be given before keyword arguments."
+                            .to_string(),
+                        value.span(),
+                    ));
+                }
+                args.push(value);
+                continue;
+            }
+
             let (arg_name, arg_name_span) =
                 expect_token!(self, Token::Ident(id) => id, "identifier")?;
             if let Some(prev_span) = kwarg_spans.get(arg_name) {
@@ -409,21 +605,22 @@ impl<'a> Parser<'a> {
 
         expect_token!(self, Token::RightParen, ")")?;
 
-        Ok(kwargs)
+        Ok((args, kwargs))
     }
 
-    fn parse_dotted_component_name(&mut self) -> TeraResult<String> {
-        let (first, _) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+    fn parse_dotted_component_name(&mut self) -> TeraResult<(String, Span)> {
+        let (first, mut span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
         let mut name = first.to_string();
 
         while matches!(self.next, Some(Ok((Token::Dot, _)))) {
             self.next_or_error()?; // consume dot
-            let (part, _) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+            let (part, part_span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
             name.push('.');
             name.push_str(part);
+            span.expand(&part_span);
         }
 
-        Ok(name)
+        Ok((name, span))
     }
 
     fn parse_component_attributes(&mut self) -> TeraResult<Vec<MapEntry>> {
@@ -511,11 +708,12 @@ impl<'a> Parser<'a> {
 
     fn parse_filter(&mut self, expr: Expression) -> TeraResult<Expression> {
         let (name, mut span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+        let mut args = Vec::new();
         let mut kwargs = HashMap::new();
 
         // We have potentially args to handle
         if matches!(self.next, Some(Ok((Token::LeftParen, _)))) {
-            kwargs = self.parse_kwargs()?;
+            (args, kwargs) = self.parse_kwargs()?;
         }
         span.expand(&self.current_span);
 
@@ -523,6 +721,7 @@ impl<'a> Parser<'a> {
             Filter {
                 expr,
                 name: name.to_string(),
+                args,
                 kwargs,
             },
             span,
@@ -531,11 +730,12 @@ impl<'a> Parser<'a> {
 
     fn parse_test(&mut self, expr: Expression) -> TeraResult<Expression> {
         let (name, mut span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+        let mut args = Vec::new();
         let mut kwargs = HashMap::new();
 
         // We have potentially args to handle
         if matches!(self.next, Some(Ok((Token::LeftParen, _)))) {
-            kwargs = self.parse_kwargs()?;
+            (args, kwargs) = self.parse_kwargs()?;
         }
         span.expand(&self.current_span);
 
@@ -543,6 +743,7 @@ impl<'a> Parser<'a> {
             Test {
                 expr,
                 name: name.to_string(),
+                args,
                 kwargs,
             },
             span,
@@ -897,7 +1098,7 @@ impl<'a> Parser<'a> {
         let mut start_span = self.current_span.clone();
         // The '<' token was already consumed in inner_parse_expression,
         // so the next token should be the component name
-        let name = self.parse_dotted_component_name()?;
+        let (name, _) = self.parse_dotted_component_name()?;
 
         // Parse attributes: name="string" or name={expression}
         let kwargs = self.parse_component_attributes()?;
@@ -944,6 +1145,7 @@ impl<'a> Parser<'a> {
                 name: name.to_string(),
                 kwargs,
                 body,
+                slots: Vec::new(),
                 self_closing: true,
             },
             start_span,
@@ -955,7 +1157,7 @@ impl<'a> Parser<'a> {
         let mut start_span = self.current_span.clone();
         // The '<' token was already consumed in parse_tag,
         // so the next token should be the component name
-        let name = self.parse_dotted_component_name()?;
+        let (name, _) = self.parse_dotted_component_name()?;
 
         // Parse attributes: name="string" or name={expression}
         let kwargs = self.parse_component_attributes()?;
@@ -966,9 +1168,44 @@ impl<'a> Parser<'a> {
         // Close the opening tag
         expect_token!(self, Token::TagEnd(..), "%}")?;
 
-        // Parse body content until {% </component> %}
+        // Parse body content until {% </component> %}, with the slots filled along the way
         self.body_contexts.push(BodyContext::Capture);
-        let body = self.parse_until(|tok| matches!(tok, Token::ClosingTagStart))?;
+        let mut body = Vec::new();
+        let mut slots: Vec<Slot> = Vec::new();
+        loop {
+            body.extend(
+                self.parse_until(|tok| {
+                    matches!(tok, Token::ClosingTagStart | Token::Ident("fill"))
+                })?,
+            );
+            if !matches!(self.next, Some(Ok((Token::Ident("fill"), _)))) {
+                break;
+            }
+            self.next_or_error()?;
+            let (slot_name, slot_span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+            if let Some(prev) = slots.iter().find(|s| *s.name == slot_name) {
+                return Err(self.syntax_error_with_note(
+                    format!("Slot `{slot_name}` is already filled in that component call"),
+                    &slot_span,
+                    "first filled here",
+                    prev.name.span(),
+                ));
+            }
+            expect_token!(self, Token::TagEnd(..), "%}")?;
+            let slot_body = self.parse_until(|tok| matches!(tok, Token::Ident("endfill")))?;
+            self.next_or_error()?;
+            if let Some(Ok((Token::Ident(end_name), _))) = self.next {
+                self.next_or_error()?;
+                if end_name != slot_name {
+                    return Err(self.different_name_end_tag(slot_name, end_name, "fill"));
+                }
+            }
+            expect_token!(self, Token::TagEnd(..), "%}")?;
+            slots.push(Slot {
+                name: Spanned::new(slot_name.to_string(), slot_span),
+                body: slot_body,
+            });
+        }
         self.body_contexts.pop();
 
         // Check for unclosed component (EOF reached)
@@ -981,7 +1218,7 @@ impl<'a> Parser<'a> {
 
         // Parse the closing tag: </component>
         expect_token!(self, Token::ClosingTagStart, "</")?;
-        let end_name = self.parse_dotted_component_name()?;
+        let (end_name, _) = self.parse_dotted_component_name()?;
         if end_name != name {
             return Err(Error::syntax_error(
                 format!("Closing tag '{end_name}' doesn't match opening tag '{name}'"),
@@ -996,6 +1233,7 @@ impl<'a> Parser<'a> {
                 name: name.to_string(),
                 kwargs,
                 body,
+                slots,
                 self_closing: false,
             },
             start_span,
@@ -1076,8 +1314,9 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_for_loop(&mut self) -> TeraResult<ForLoop> {
-        self.body_contexts.push(BodyContext::ForLoop);
-        let (mut name, _) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+        self.body_contexts
+            .push(BodyContext::ForLoop { recursive: false });
+        let (mut name, mut name_span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
         if RESERVED_NAMES.contains(&name) {
             return Err(Error::syntax_error(
                 format!(
@@ -1090,7 +1329,7 @@ impl<'a> Parser<'a> {
         let mut key = None;
         if matches!(self.next, Some(Ok((Token::Comma, _)))) {
             self.next_or_error()?;
-            let (val, _) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+            let (val, val_span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
             if RESERVED_NAMES.contains(&val) {
                 return Err(Error::syntax_error(
                     format!(
@@ -1099,11 +1338,17 @@ impl<'a> Parser<'a> {
                     &self.current_span,
                 ));
             }
-            key = Some(name.to_string());
+            key = Some(Spanned::new(name.to_string(), name_span));
             name = val;
+            name_span = val_span;
         }
         expect_token!(self, Token::Ident("in"), "in")?;
         let target = self.parse_expression(0)?;
+        let recursive = matches!(self.next, Some(Ok((Token::Ident("recursive"), _))));
+        if recursive {
+            self.next_or_error()?;
+            *self.body_contexts.last_mut().unwrap() = BodyContext::ForLoop { recursive };
+        }
         expect_token!(self, Token::TagEnd(..), "%}")?;
         let body =
             self.parse_until(|tok| matches!(tok, Token::Ident("endfor") | Token::Ident("else")))?;
@@ -1119,10 +1364,11 @@ impl<'a> Parser<'a> {
 
         Ok(ForLoop {
             key,
-            value: name.to_string(),
+            value: Spanned::new(name.to_string(), name_span),
             target,
             body,
             else_body: else_body.unwrap_or_default(),
+            recursive,
         })
     }
 
@@ -1184,6 +1430,115 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses the type of a component argument: a type name, `array<type>`, literals separated
+    /// by `|` or a map of keys to types like `{title: string}`, optionally followed by `?`
+    fn parse_type(&mut self) -> TeraResult<Type> {
+        self.recursion_depth += 1;
+        if self.recursion_depth > MAX_RECURSION_DEPTH {
+            self.recursion_depth -= 1;
+            return Err(Error::syntax_error(
+                "The type is too complex".to_string(),
+                &self.current_span,
+            ));
+        }
+        let res = self.parse_type_inner();
+        self.recursion_depth -= 1;
+        res
+    }
+
+    fn parse_type_inner(&mut self) -> TeraResult<Type> {
+        fn literal(token: &Token) -> Option<Value> {
+            match token {
+                Token::Str(s) => Some(Value::from(*s)),
+                Token::String(s) => Some(Value::from(s.clone())),
+                Token::Integer(i) => Some(Value::from(*i)),
+                _ => None,
+            }
+        }
+
+        let (token, span) = self.next_or_error()?;
+        let typ = match token {
+            Token::Ident(type_str) => {
+                let typ = Type::from_str(type_str)
+                    .map_err(|e| Error::syntax_error(format!("{e}"), &span))?;
+                if typ == Type::Array && matches!(self.next, Some(Ok((Token::LessThan, _)))) {
+                    self.next_or_error()?;
+                    let item_type = self.parse_type()?;
+                    expect_token!(self, Token::GreaterThan, ">")?;
+                    Type::ArrayOf(Box::new(item_type))
+                } else {
+                    typ
+                }
+            }
+            Token::LeftBrace => {
+                let mut keys: Vec<(String, Type)> = Vec::new();
+                loop {
+                    if matches!(self.next, Some(Ok((Token::RightBrace, _)))) {
+                        self.next_or_error()?;
+                        break;
+                    }
+                    if !keys.is_empty() {
+                        expect_token!(self, Token::Comma, ",")?;
+                        if matches!(self.next, Some(Ok((Token::RightBrace, _)))) {
+                            self.next_or_error()?;
+                            break;
+                        }
+                    }
+                    let (key, key_span) = match self.next_or_error()? {
+                        (Token::Ident(key) | Token::Str(key), span) => (key.to_string(), span),
+                        (Token::String(key), span) => (key, span),
+                        (token, span) => {
+                            return Err(Error::syntax_error(
+                                format!("Found {token} but expected a key name."),
+                                &span,
+                            ));
+                        }
+                    };
+                    if keys.iter().any(|(k, _)| *k == key) {
+                        return Err(Error::syntax_error(
+                            format!("The key `{key}` is defined more than once in this map type."),
+                            &key_span,
+                        ));
+                    }
+                    expect_token!(self, Token::Colon, ":")?;
+                    keys.push((key, self.parse_type()?));
+                }
+                Type::MapWithKeys(keys)
+            }
+            token => {
+                let Some(first) = literal(&token) else {
+                    return Err(Error::syntax_error(
+                        format!(
+                            "Found {token} but expected a type: string, bool, integer, float, number, array, map, bytes, `array<type>`, literals separated by `|` or a map like `{{key: type}}`"
+                        ),
+                        &span,
+                    ));
+                };
+                let mut values = vec![first];
+                while matches!(self.next, Some(Ok((Token::Pipe, _)))) {
+                    self.next_or_error()?;
+                    let (token, span) = self.next_or_error()?;
+                    let Some(value) = literal(&token) else {
+                        return Err(Error::syntax_error(
+                            format!(
+                                "Found {token} but only string and integer literals can be combined with `|`."
+                            ),
+                            &span,
+                        ));
+                    };
+                    values.push(value);
+                }
+                Type::OneOf(values)
+            }
+        };
+
+        if matches!(self.next, Some(Ok((Token::QuestionMark, _)))) {
+            self.next_or_error()?;
+            return Ok(Type::Optional(Box::new(typ)));
+        }
+        Ok(typ)
+    }
+
     fn parse_component_definition(&mut self) -> TeraResult<ComponentDefinition> {
         if !self.body_contexts.is_empty() {
             return Err(Error::syntax_error(
@@ -1192,8 +1547,7 @@ impl<'a> Parser<'a> {
             ));
         }
         self.body_contexts.push(BodyContext::ComponentDefinition);
-        let name = self.parse_dotted_component_name()?;
-        let name_span = self.current_span.clone();
+        let (name, name_span) = self.parse_dotted_component_name()?;
         if let Some(prev_span) = self.components_seen.get(&name) {
             return Err(self.syntax_error_with_note(
                 format!("Template already contains a component named `{name}`"),
@@ -1283,85 +1637,41 @@ impl<'a> Parser<'a> {
             // First a potential type
             if let Some(Ok((Token::Colon, _))) = self.next {
                 self.next_or_error()?;
-                match &self.next {
-                    Some(Ok((Token::Ident(type_str), span))) => {
-                        let typ = Type::from_str(type_str)
-                            .map_err(|e| Error::syntax_error(format!("{e}"), span))?;
-                        self.next_or_error()?;
-                        kwarg.typ = Some(typ);
-                    }
-                    Some(Ok((token, span))) => {
-                        return Err(Error::syntax_error(
-                            format!(
-                                "Found {token} but the only types allowed are: string, bool, integer, float, number, array and map"
-                            ),
-                            span,
-                        ));
-                    }
-                    Some(Err(e)) => {
-                        return Err(Error {
-                            kind: e.kind.clone(),
-                            source: None,
-                        });
-                    }
-                    None => return Err(self.eoi()),
-                }
+                kwarg.typ = Some(self.parse_type()?);
             }
 
             // Then a potential default value
             if let Some(Ok((Token::Assign, _))) = self.next {
                 self.next_or_error()?;
 
-                let (val, eat_next) = match &self.next {
-                    Some(Ok((Token::Bool(b), _))) => (Value::from(*b), true),
-                    Some(Ok((Token::Str(b), _))) => (Value::from(*b), true),
-                    Some(Ok((Token::String(b), _))) => (Value::from(b.clone()), true),
-                    Some(Ok((Token::Integer(b), _))) => (Value::from(*b), true),
-                    Some(Ok((Token::Float(b), _))) => (Value::from(*b), true),
-                    Some(Ok((
-                        Token::Ident("none") | Token::Ident("None") | Token::Ident("null"),
-                        _,
-                    ))) => (Value::none(), true),
-                    Some(Ok((Token::LeftBracket, _))) => {
-                        expect_token!(self, Token::LeftBracket, "[")?;
-                        let array = self.parse_array()?;
-                        if let Some(val) = array.as_value() {
-                            (val, false)
-                        } else {
+                let start = match &self.next {
+                    Some(Ok((_, span))) => span.range.start,
+                    _ => self.current_span.range.end,
+                };
+                let expr = self.parse_expression(0)?;
+                if let Some(val) = expr.as_value() {
+                    match &kwarg.typ {
+                        Some(typ) if !typ.matches_value(&val) => {
                             return Err(Error::syntax_error(
-                                "Invalid default argument: this array should only contain literal values.".to_string(),
-                                array.span(),
+                                format!(
+                                    "Invalid default value. {}",
+                                    typ.arg_mismatch_message(arg_name, &val)
+                                ),
+                                expr.span(),
                             ));
                         }
+                        Some(_) => (),
+                        // Infer type from default value if not explicitly specified
+                        None => kwarg.typ = Type::from_value(&val),
                     }
-                    Some(Ok((Token::LeftBrace, _))) => {
-                        (self.parse_literal_map("default argument")?, false)
-                    }
-                    Some(Ok((token, span))) => {
-                        return Err(Error::syntax_error(
-                            format!(
-                                "Found {token} but component default arguments can only be one of: string, bool, integer, float, array or map"
-                            ),
-                            span,
-                        ));
-                    }
-                    Some(Err(e)) => {
-                        return Err(Error {
-                            kind: e.kind.clone(),
-                            source: None,
-                        });
-                    }
-                    None => return Err(self.eoi()),
-                };
-                if eat_next {
-                    self.next_or_error()?;
-                }
-
-                kwarg.default = Some(val.clone());
-
-                // Infer type from default value if not explicitly specified
-                if kwarg.typ.is_none() {
-                    kwarg.typ = Type::from_value(&val);
+                    kwarg.default = Some(ArgDefault::Value(val));
+                } else {
+                    // The span of the expression doesn't always cover it fully, eg for filters
+                    let source = self.source[start..self.current_span.range.end].to_string();
+                    kwarg.default = Some(ArgDefault::Expression(source));
+                    component_def
+                        .default_exprs
+                        .push((arg_name.to_string(), expr));
                 }
             }
 
@@ -1385,7 +1695,7 @@ impl<'a> Parser<'a> {
         self.next_or_error()?;
 
         if matches!(self.next, Some(Ok((Token::Ident(..), _)))) {
-            let end_name = self.parse_dotted_component_name()?;
+            let (end_name, _) = self.parse_dotted_component_name()?;
             if name != end_name {
                 return Err(self.different_name_end_tag(&name, &end_name, "component"));
             }
@@ -1393,12 +1703,16 @@ impl<'a> Parser<'a> {
 
         self.body_contexts.pop();
         component_def.body = body;
+        component_def.slots = std::mem::take(&mut self.slots_seen)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
 
         Ok(component_def)
     }
 
     fn parse_set(&mut self, global: bool) -> TeraResult<Node> {
-        let (name, _) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+        let (name, name_span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
         if RESERVED_NAMES.contains(&name) {
             return Err(Error::syntax_error(
                 format!("{name} is a reserved keyword of Tera, it cannot be assigned to."),
@@ -1412,7 +1726,7 @@ impl<'a> Parser<'a> {
                 expect_token!(self, Token::Assign, "=")?;
                 let value = self.parse_expression(0)?;
                 Node::Set(Set {
-                    name: name.to_string(),
+                    name: Spanned::new(name.to_string(), name_span),
                     value,
                     global,
                 })
@@ -1438,7 +1752,7 @@ impl<'a> Parser<'a> {
                 self.body_contexts.pop();
                 self.next_or_error()?;
                 Node::BlockSet(BlockSet {
-                    name: name.to_string(),
+                    name: Spanned::new(name.to_string(), name_span),
                     filters,
                     body,
                     global,
@@ -1464,24 +1778,33 @@ impl<'a> Parser<'a> {
                 self.parse_set(tag_token == Token::Ident("set_global"))?,
             )),
             Token::Ident("include") => {
-                let (name, span) = match self.next_or_error()? {
-                    (Token::Str(s), span) => (s.to_string(), span),
-                    (Token::String(s), span) => (s, span),
-                    (token, _) => {
-                        return Err(Error::syntax_error(
-                            format!("Found {} but expected string.", token),
-                            &self.current_span,
-                        ));
-                    }
+                let name = self.parse_expression(0)?;
+                let ignore_missing = matches!(self.next, Some(Ok((Token::Ident("ignore"), _))));
+                if ignore_missing {
+                    self.next_or_error()?;
+                    expect_token!(self, Token::Ident("missing"), "missing")?;
+                }
+                let with = if matches!(self.next, Some(Ok((Token::Ident("with"), _)))) {
+                    self.next_or_error()?;
+                    Some(self.parse_expression(0)?)
+                } else {
+                    None
                 };
+                let only = matches!(self.next, Some(Ok((Token::Ident("only"), _))));
+                if only {
+                    self.next_or_error()?;
+                }
                 Ok(Some(Node::Include(Include {
-                    name: Spanned::new(name, span),
+                    name,
+                    ignore_missing,
+                    with,
+                    only,
                 })))
             }
             Token::Ident("extends") => {
-                let name = match self.next_or_error()? {
-                    (Token::Str(s), _) => s.to_string(),
-                    (Token::String(s), _) => s,
+                let (name, span) = match self.next_or_error()? {
+                    (Token::Str(s), span) => (s.to_string(), span),
+                    (Token::String(s), span) => (s, span),
                     (token, _) => {
                         return Err(Error::syntax_error(
                             format!("Found {} but expected string.", token),
@@ -1508,6 +1831,7 @@ impl<'a> Parser<'a> {
                     ));
                 }
                 self.output.parent = Some(name.to_string());
+                self.output.parent_span = Some(span);
                 Ok(None)
             }
             Token::Ident("block") => {
@@ -1520,13 +1844,13 @@ impl<'a> Parser<'a> {
                 }
                 self.body_contexts.push(BodyContext::Block);
                 let (name, name_span) = expect_token!(self, Token::Ident(s) => s, "identifier")?;
-                if self.blocks_seen.contains(name) {
+                if self.blocks_seen.contains_key(name) {
                     return Err(Error::syntax_error(
                         format!("Template already contains a block named `{name}`"),
                         &self.current_span,
                     ));
                 } else {
-                    self.blocks_seen.insert(name.to_string());
+                    self.blocks_seen.insert(name.to_string(), name_span.clone());
                 }
 
                 expect_token!(self, Token::TagEnd(..), "%}")?;
@@ -1541,7 +1865,6 @@ impl<'a> Parser<'a> {
 
                 self.body_contexts.pop();
 
-                self.blocks_seen.insert(name.to_string());
                 Ok(Some(Node::Block(Block {
                     name: Spanned::new(name.to_string(), name_span),
                     body,
@@ -1560,10 +1883,10 @@ impl<'a> Parser<'a> {
                 self.body_contexts.push(BodyContext::Capture);
                 let (name, ident_span) = expect_token!(self, Token::Ident(s) => s, "identifier")?;
 
-                let kwargs = if matches!(self.next, Some(Ok((Token::LeftParen, _)))) {
+                let (args, kwargs) = if matches!(self.next, Some(Ok((Token::LeftParen, _)))) {
                     self.parse_kwargs()?
                 } else {
-                    HashMap::new()
+                    (Vec::new(), HashMap::new())
                 };
                 let mut fn_span = ident_span.clone();
                 fn_span.expand(&self.current_span);
@@ -1573,10 +1896,55 @@ impl<'a> Parser<'a> {
                 self.body_contexts.pop();
                 Ok(Some(Node::FilterSection(FilterSection {
                     name: Spanned::new(name.to_owned(), ident_span),
+                    args,
                     kwargs,
                     body,
                 })))
             }
+            Token::Ident("slot") => {
+                if !self
+                    .body_contexts
+                    .contains(&BodyContext::ComponentDefinition)
+                {
+                    return Err(Error::syntax_error(
+                        "Slots can only be defined in component definitions.".to_string(),
+                        &self.current_span,
+                    ));
+                }
+                let (name, name_span) = expect_token!(self, Token::Ident(id) => id, "identifier")?;
+                if name == "body" {
+                    return Err(Error::syntax_error(
+                        "The name `body` is reserved for component body content.".to_string(),
+                        &name_span,
+                    ));
+                }
+                if let Some((_, prev_span)) = self.slots_seen.iter().find(|(n, _)| n == name) {
+                    return Err(self.syntax_error_with_note(
+                        format!("Component already contains a slot named `{name}`"),
+                        &name_span,
+                        "first defined here",
+                        prev_span,
+                    ));
+                }
+                self.slots_seen.push((name.to_string(), name_span.clone()));
+                expect_token!(self, Token::TagEnd(..), "%}")?;
+                let body = self.parse_until(|tok| matches!(tok, Token::Ident("endslot")))?;
+                self.next_or_error()?;
+                if let Some(Ok((Token::Ident(end_name), _))) = self.next {
+                    self.next_or_error()?;
+                    if end_name != name {
+                        return Err(self.different_name_end_tag(name, end_name, "slot"));
+                    }
+                }
+                Ok(Some(Node::Slot(Slot {
+                    name: Spanned::new(name.to_string(), name_span),
+                    body,
+                })))
+            }
+            Token::Ident("fill") => Err(Error::syntax_error(
+                "`fill` can only be used directly in the body of a component call.".to_string(),
+                &self.current_span,
+            )),
             Token::Ident("component") => {
                 let component_def = self.parse_component_definition()?;
                 self.output.component_definitions.push(component_def);
@@ -1587,7 +1955,7 @@ impl<'a> Parser<'a> {
                 let kw = if is_break { "break" } else { "continue" };
                 let mut in_loop = false;
                 for ctx in self.body_contexts.iter().rev() {
-                    if *ctx == BodyContext::ForLoop {
+                    if matches!(ctx, BodyContext::ForLoop { .. }) {
                         in_loop = true;
                         break;
                     }
@@ -1608,9 +1976,9 @@ impl<'a> Parser<'a> {
                 }
                 // TODO: add a Node::Keyword if we have more than one like that
                 Ok(Some(if is_break {
-                    Node::Break
+                    Node::Break(self.current_span.clone())
                 } else {
-                    Node::Continue
+                    Node::Continue(self.current_span.clone())
                 }))
             }
             Token::LessThan => {
@@ -1707,6 +2075,8 @@ impl<'a> Parser<'a> {
         // get the first token
         self.next()?;
         self.output.nodes = self.parse_until(|_| false)?;
+        self.output.block_spans = self.blocks_seen;
+        self.output.component_spans = self.components_seen;
         Ok(self.output)
     }
 }
@@ -76,8 +76,13 @@ pub(crate) fn generate_report(error: &ReportError) -> String {
         let note_loc = SourceLocation::new(&note.source, &note.span);
         let note_line_num_width = note_loc.start_line.to_string().len();
         let note_padding = " ".repeat(note_line_num_width);
+        let repeated = if note.times > 1 {
+            format!(" ({} times)", note.times)
+        } else {
+            String::new()
+        };
         output.push_str(&format!(
-            "\n\nnote: {} {}:{}:{}\n\
+            "\n\nnote: {} {}:{}:{}{repeated}\n\
              {note_padding} |\n\
              {} | {}\n\
              {note_padding} | {}",
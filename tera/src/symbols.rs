@@ -0,0 +1,213 @@
+//! What a template defines and references, with where it is in the source, for tools like editors
+//! or component catalogs.
+use crate::components::ComponentInfo;
+use crate::template::Template;
+use crate::utils::Span;
+
+/// A name found in a template source along with its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    name: String,
+    span: Span,
+}
+
+impl Symbol {
+    pub(crate) fn new(name: &str, span: &Span) -> Self {
+        Self {
+            name: name.to_string(),
+            span: span.clone(),
+        }
+    }
+
+    /// The name of the template, block or component.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Where it is in the template source.
+    ///
+    /// For templates, this is the span of the string literal, including the quotes, or of the
+    /// whole array for includes with fallbacks.
+    /// For component calls, this is the span of the whole call.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+/// The templates, blocks and components defined and referenced by a template.
+///
+/// Everything is sorted by position in the template source.
+#[derive(Debug, Clone)]
+pub struct TemplateSymbols {
+    extends: Option<Symbol>,
+    includes: Vec<Symbol>,
+    blocks: Vec<Symbol>,
+    components: Vec<(Symbol, ComponentInfo)>,
+    component_calls: Vec<Symbol>,
+}
+
+impl TemplateSymbols {
+    /// The template this one extends, if any.
+    pub fn extends(&self) -> Option<&Symbol> {
+        self.extends.as_ref()
+    }
+
+    /// The templates included.
+    pub fn includes(&self) -> &[Symbol] {
+        &self.includes
+    }
+
+    /// The blocks defined, including the ones nested in other blocks.
+    pub fn blocks(&self) -> &[Symbol] {
+        &self.blocks
+    }
+
+    /// The components defined.
+    pub fn components(&self) -> &[(Symbol, ComponentInfo)] {
+        &self.components
+    }
+
+    /// The components called, including from inside component definitions.
+    pub fn component_calls(&self) -> &[Symbol] {
+        &self.component_calls
+    }
+}
+
+fn sorted(mut symbols: Vec<Symbol>) -> Vec<Symbol> {
+    symbols.sort_by_key(|s| s.span.range.start);
+    symbols
+}
+
+fn calls<'a>(calls: impl Iterator<Item = (&'a String, &'a Vec<Span>)>) -> Vec<Symbol> {
+    let symbols = calls
+        .flat_map(|(name, spans)| spans.iter().map(|span| Symbol::new(name, span)))
+        .collect();
+    sorted(symbols)
+}
+
+impl From<&Template> for TemplateSymbols {
+    fn from(tpl: &Template) -> Self {
+        let mut components: Vec<_> = tpl
+            .components
+            .iter()
+            .filter_map(|(name, (def, _))| {
+                let span = tpl.component_definition_spans.get(name)?;
+                Some((Symbol::new(name, span), ComponentInfo::from(def)))
+            })
+            .collect();
+        components.sort_by_key(|(s, _)| s.span.range.start);
+
+        Self {
+            extends: tpl
+                .extends
+                .as_deref()
+                .zip(tpl.extends_span.as_ref())
+                .map(|(name, span)| Symbol::new(name, span)),
+            includes: calls(tpl.include_calls.iter().chain(&tpl.optional_include_calls)),
+            blocks: sorted(
+                tpl.block_definition_spans
+                    .iter()
+                    .map(|(name, span)| Symbol::new(name, span))
+                    .collect(),
+            ),
+            components,
+            component_calls: calls(tpl.component_calls.iter()),
+        }
+    }
+}
+
+/// Information about a template loaded in a [`Tera`](crate::Tera) instance.
+#[derive(Debug, Clone)]
+pub struct TemplateInfo {
+    name: String,
+    path: Option<String>,
+    parents: Vec<String>,
+    autoescape: bool,
+    symbols: TemplateSymbols,
+}
+
+impl TemplateInfo {
+    /// The template name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The path of the file it was loaded from, if it was loaded from a file.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// The names of the templates it extends, directly or not, from the root template to the
+    /// direct parent.
+    pub fn parents(&self) -> &[String] {
+        &self.parents
+    }
+
+    /// Whether its output is auto-escaped.
+    pub fn autoescape(&self) -> bool {
+        self.autoescape
+    }
+
+    /// The templates, blocks and components defined and referenced by the template itself:
+    /// the blocks of its parents are not included.
+    pub fn symbols(&self) -> &TemplateSymbols {
+        &self.symbols
+    }
+}
+
+impl From<&Template> for TemplateInfo {
+    fn from(tpl: &Template) -> Self {
+        Self {
+            name: tpl.name.clone(),
+            path: tpl.path.clone(),
+            parents: tpl.parents.clone(),
+            autoescape: tpl.autoescape_enabled,
+            symbols: TemplateSymbols::from(tpl),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Tera;
+
+    #[test]
+    fn can_get_symbols() {
+        let source = r#"{% extends "base.html" %}
+{% component ui.button(label: string, size="big") %}{% slot icon %}{% endslot %}{{ label }}{% endcomponent ui.button %}
+{% block content %}{% block inner %}{% include "a.html" %}{% endblock %}{% endblock %}
+{% block footer %}{{<ui.button label="hi" />}}{% include 'b.html' %}{% endblock %}"#;
+        let symbols = Tera::default()
+            .parse_template_symbols("page.html", source)
+            .unwrap();
+
+        let extends = symbols.extends().unwrap();
+        assert_eq!(extends.name(), "base.html");
+        assert_eq!(&source[extends.span().range.clone()], r#""base.html""#);
+
+        let names =
+            |s: &[super::Symbol]| s.iter().map(|s| s.name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(symbols.blocks()), vec!["content", "inner", "footer"]);
+        assert_eq!(names(symbols.includes()), vec!["a.html", "b.html"]);
+        assert_eq!(names(symbols.component_calls()), vec!["ui.button"]);
+        assert_eq!(
+            &source[symbols.component_calls()[0].span().range.clone()],
+            r#"<ui.button label="hi" />"#
+        );
+
+        let (def, info) = &symbols.components()[0];
+        assert_eq!(&source[def.span().range.clone()], "ui.button");
+        assert_eq!(info.args().len(), 2);
+        assert_eq!(info.slots(), ["icon"]);
+    }
+
+    #[test]
+    fn only_returns_syntax_errors() {
+        let tera = Tera::default();
+        assert!(
+            tera.parse_template_symbols("a", "{% include 'missing' %}{{ a | nope }}")
+                .is_ok()
+        );
+        assert!(tera.parse_template_symbols("a", "{% if %}").is_err());
+    }
+}
@@ -1,10 +1,11 @@
 use crate::HashMap;
 use crate::delimiters::Delimiters;
+use crate::diagnostics::Diagnostic;
 use crate::errors::{Error, ErrorKind, TeraResult};
-use crate::parsing::ast::ComponentDefinition;
+use crate::lints::lint_template;
+use crate::parsing::ast::{ComponentCallSite, ComponentDefinition};
 use crate::parsing::parser::Parser;
 use crate::parsing::{Chunk, Compiler};
-use crate::tera::Tera;
 use crate::utils::Span;
 use std::collections::HashSet;
 
@@ -20,16 +21,26 @@ pub struct Template {
     pub(crate) blocks: HashMap<String, Chunk>,
     /// Block definitions with their spans for error reporting
     pub(crate) block_name_spans: HashMap<String, Span>,
+    /// The span of the name of all blocks defined, including the nested ones
+    pub(crate) block_definition_spans: HashMap<String, Span>,
     pub(crate) components: HashMap<String, (ComponentDefinition, Chunk)>,
+    /// The span of the name of the components defined
+    pub(crate) component_definition_spans: HashMap<String, Span>,
     pub(crate) component_calls: HashMap<String, Vec<Span>>,
+    /// The arguments of every component call, to type-check them when finalizing
+    pub(crate) component_call_sites: Vec<ComponentCallSite>,
     pub(crate) filter_calls: HashMap<String, Vec<Span>>,
     pub(crate) test_calls: HashMap<String, Vec<Span>>,
     pub(crate) function_calls: HashMap<String, Vec<Span>>,
     pub(crate) include_calls: HashMap<String, Vec<Span>>,
+    /// Includes that can be missing: with `ignore missing` or with a list of candidates
+    pub(crate) optional_include_calls: HashMap<String, Vec<Span>>,
     /// The number of bytes of raw content in its parents and itself
     pub(crate) total_content_num_bytes: usize,
     /// The exact `{% extends %}` target in the source
     pub(crate) extends: Option<String>,
+    /// Where the `{% extends %}` target is in the source
+    pub(crate) extends_span: Option<Span>,
     /// The full list of parent templates names
     pub(crate) parents: Vec<String>,
     pub(crate) block_lineage: HashMap<String, Vec<Chunk>>,
@@ -38,6 +49,8 @@ pub struct Template {
     pub(crate) autoescape_enabled: bool,
     /// The top level variables used by the template
     pub(crate) top_level_variables: HashSet<String>,
+    /// The lint warnings found when parsing the template
+    pub(crate) warnings: Vec<Diagnostic>,
 }
 
 impl Template {
@@ -46,6 +59,26 @@ impl Template {
         source: &str,
         path: Option<String>,
         delimiters: Delimiters,
+    ) -> TeraResult<Self> {
+        Self::build(tpl_name, source, path, delimiters, true)
+    }
+
+    /// Same as `new` but without linting: the warnings of a template rendered once with
+    /// `render_str` are never looked at.
+    pub(crate) fn new_one_off(
+        tpl_name: &str,
+        source: &str,
+        delimiters: Delimiters,
+    ) -> TeraResult<Self> {
+        Self::build(tpl_name, source, None, delimiters, false)
+    }
+
+    fn build(
+        tpl_name: &str,
+        source: &str,
+        path: Option<String>,
+        delimiters: Delimiters,
+        lint: bool,
     ) -> TeraResult<Self> {
         let parser = Parser::new(tpl_name, source, delimiters);
         let parser_output = match parser.parse() {
@@ -61,7 +94,15 @@ impl Template {
                 _ => unreachable!("Parser got something other than a SyntaxError: {e}"),
             },
         };
+        let warnings = if lint {
+            lint_template(tpl_name, &parser_output)
+        } else {
+            Vec::new()
+        };
         let extends = parser_output.parent;
+        let extends_span = parser_output.parent_span;
+        let block_definition_spans = parser_output.block_spans;
+        let component_definition_spans = parser_output.component_spans;
 
         let mut body_compiler = Compiler::new(tpl_name);
         body_compiler.compile(parser_output.nodes);
@@ -84,7 +125,9 @@ impl Template {
         let mut test_calls = body_compiler.test_calls;
         let mut function_calls = body_compiler.function_calls;
         let mut include_calls = body_compiler.include_calls;
+        let mut optional_include_calls = body_compiler.optional_include_calls;
         let mut component_calls = body_compiler.component_calls;
+        let mut component_call_sites = body_compiler.component_call_sites;
         let top_level_variables = body_compiler.top_level_variables;
 
         let components = parser_output
@@ -93,6 +136,7 @@ impl Template {
             .map(|c| {
                 let mut compiler = Compiler::new(tpl_name);
                 // We don't need the nodes again after it's compiled
+                compiler.compile_arg_defaults(&c);
                 compiler.compile(c.body.clone());
                 // Collect filter/test/function/include/component calls from component body
                 for (name, spans) in compiler.filter_calls {
@@ -107,9 +151,16 @@ impl Template {
                 for (name, spans) in compiler.include_calls {
                     include_calls.entry(name).or_default().extend(spans);
                 }
+                for (name, spans) in compiler.optional_include_calls {
+                    optional_include_calls
+                        .entry(name)
+                        .or_default()
+                        .extend(spans);
+                }
                 for (name, spans) in compiler.component_calls {
                     component_calls.entry(name).or_default().extend(spans);
                 }
+                component_call_sites.extend(compiler.component_call_sites);
                 let mut chunk = compiler.chunk;
                 chunk.optimize();
                 (c.name.clone(), (c, chunk))
@@ -124,17 +175,23 @@ impl Template {
             from_glob: false,
             blocks,
             block_name_spans,
+            block_definition_spans,
             total_content_num_bytes: source.len(),
             chunk,
             extends,
+            extends_span,
             parents: Vec::new(),
             components,
+            component_definition_spans,
             component_calls,
+            component_call_sites,
             filter_calls,
             test_calls,
             function_calls,
             include_calls,
+            optional_include_calls,
             top_level_variables,
+            warnings,
             block_lineage: HashMap::new(),
             autoescape_enabled: true,
         })
@@ -143,24 +200,36 @@ impl Template {
     pub(crate) fn size_hint(&self) -> usize {
         (self.total_content_num_bytes * 2).next_power_of_two()
     }
+
+    /// The names of all the templates included with a literal name, even the ones that can
+    /// be missing.
+    pub(crate) fn included_names(&self) -> impl Iterator<Item = &String> {
+        self.include_calls
+            .keys()
+            .chain(self.optional_include_calls.keys())
+    }
 }
 
 /// Recursive fn that finds all the includes to detect if there are some cycles
-pub(crate) fn check_include_cycles(tera: &Tera, start: &Template) -> Result<(), Error> {
+pub(crate) fn check_include_cycles<'a>(
+    lookup: &impl Fn(&str) -> Option<&'a Template>,
+    start: &Template,
+) -> Result<(), Error> {
     let mut stack: Vec<String> = vec![start.name.clone()];
     let mut visited: HashSet<String> = HashSet::new();
-    fn walk(
-        tera: &Tera,
+    fn walk<'a>(
+        lookup: &impl Fn(&str) -> Option<&'a Template>,
         current: &Template,
         stack: &mut Vec<String>,
         visited: &mut HashSet<String>,
     ) -> Result<(), Error> {
-        let mut names: Vec<&String> = current.include_calls.keys().collect();
+        let mut names: Vec<&String> = current.included_names().collect();
         names.sort();
         for include_name in names {
-            let Some(resolved) = tera.resolve_template_name(include_name) else {
+            let Some(included) = lookup(include_name) else {
                 continue;
             };
+            let resolved = included.name.as_str();
             if stack.iter().any(|s| s == resolved) {
                 let mut chain = stack.clone();
                 chain.push(resolved.to_string());
@@ -170,34 +239,34 @@ pub(crate) fn check_include_cycles(tera: &Tera, start: &Template) -> Result<(),
                 continue;
             }
             stack.push(resolved.to_string());
-            walk(tera, &tera.templates[resolved], stack, visited)?;
+            walk(lookup, included, stack, visited)?;
             stack.pop();
             visited.insert(resolved.to_string());
         }
         Ok(())
     }
-    walk(tera, start, &mut stack, &mut visited)
+    walk(lookup, start, &mut stack, &mut visited)
 }
 
 /// Recursive fn that finds all the parents and put them in an ordered Vec from closest to first parent
 /// parent template
-pub(crate) fn find_parents(
-    tera: &Tera,
+pub(crate) fn find_parents<'a>(
+    lookup: &impl Fn(&str) -> Option<&'a Template>,
     start: &Template,
     template: &Template,
     mut parents: Vec<String>,
 ) -> Result<Vec<String>, Error> {
     match &template.extends {
-        Some(p) => match tera.resolve_template_name(p) {
-            Some(resolved) => {
+        Some(p) => match lookup(p) {
+            Some(parent) => {
+                let resolved = parent.name.as_str();
                 if resolved == start.name || parents.iter().any(|name| name == resolved) {
                     let mut chain = parents.clone();
                     chain.push(resolved.to_string());
                     return Err(Error::circular_extend(&start.name, chain));
                 }
-                let parent = &tera.templates[resolved];
                 parents.push(parent.name.clone());
-                find_parents(tera, start, parent, parents)
+                find_parents(lookup, start, parent, parents)
             }
             None => Err(Error::missing_parent(&template.name, p)),
         },
@@ -208,9 +277,45 @@ pub(crate) fn find_parents(
     }
 }
 
+/// Finds, for every block a template can render, the chunks to use: the most specific definition
+/// first, followed by the ones reachable through `super()`.
+/// `parents` is ordered from the first parent to the closest one, like `Template::parents`.
+pub(crate) fn build_block_lineage(
+    tpl: &Template,
+    parents: &[&Template],
+) -> HashMap<String, Vec<Chunk>> {
+    let mut lineage: HashMap<String, Vec<Chunk>> = HashMap::with_capacity(tpl.blocks.len());
+    let definers: Vec<&Template> = std::iter::once(tpl)
+        .chain(parents.iter().rev().copied())
+        .collect();
+
+    for (i, current) in definers.iter().enumerate() {
+        for (block_name, chunk) in &current.blocks {
+            if lineage.contains_key(block_name) {
+                continue;
+            }
+            let mut all_blocks = vec![chunk.clone()];
+            if chunk.is_calling_function("super") {
+                for ancestor in &definers[i + 1..] {
+                    if let Some(parent_chunk) = ancestor.blocks.get(block_name) {
+                        all_blocks.push(parent_chunk.clone());
+                        if !parent_chunk.is_calling_function("super") {
+                            break;
+                        }
+                    }
+                }
+            }
+            lineage.insert(block_name.clone(), all_blocks);
+        }
+    }
+
+    lineage
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tera::Tera;
 
     #[test]
     fn can_find_parents() {
@@ -222,16 +327,17 @@ mod tests {
         ])
         .unwrap();
 
+        let lookup = |name: &str| tera.templates.get(name).map(AsRef::as_ref);
         let parents_a =
-            find_parents(&tera, &tera.templates["a"], &tera.templates["a"], vec![]).unwrap();
+            find_parents(&lookup, &tera.templates["a"], &tera.templates["a"], vec![]).unwrap();
         assert!(parents_a.is_empty());
 
         let parents_b =
-            find_parents(&tera, &tera.templates["b"], &tera.templates["b"], vec![]).unwrap();
+            find_parents(&lookup, &tera.templates["b"], &tera.templates["b"], vec![]).unwrap();
         assert_eq!(parents_b, vec!["a".to_string()]);
 
         let parents_c =
-            find_parents(&tera, &tera.templates["c"], &tera.templates["c"], vec![]).unwrap();
+            find_parents(&lookup, &tera.templates["c"], &tera.templates["c"], vec![]).unwrap();
         assert_eq!(parents_c, vec!["a".to_string(), "b".to_string()]);
     }
 
@@ -1,31 +1,56 @@
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::fmt;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::args::ArgFromValue;
+use crate::bundle;
 use crate::errors::{Error, ReportError, TeraResult};
 use crate::filters::{Filter, StoredFilter};
 use crate::functions::{Function, StoredFunction};
-use crate::template::{Template, check_include_cycles, find_parents};
+use crate::limits::{Budget, CountingWriter, RenderLimits};
+#[cfg(feature = "loader")]
+use crate::loader::{LazyTemplates, LoadedTemplate, Loader};
+use crate::symbols::{Symbol, TemplateInfo, TemplateSymbols};
+use crate::template::{Template, build_block_lineage, check_include_cycles, find_parents};
 use crate::tests::{StoredTest, Test, TestResult};
 use crate::value::FunctionResult;
 use crate::value::Value;
 use crate::vm::interpreter::VirtualMachine;
 use crate::vm::state::State;
-use crate::{ComponentInfo, Context, HashMap, escape_html};
+use crate::{ComponentInfo, Context, Diagnostic, HashMap, escape_html};
 
 use crate::delimiters::Delimiters;
 #[cfg(feature = "glob_fs")]
-use crate::globbing::load_from_glob;
+use crate::globbing::{Glob, load_from_glob};
 use crate::parsing::Chunk;
 use crate::parsing::ast::ComponentDefinition;
 
 /// Default template name used for `Tera::render_str` and `Tera::one_off`.
 const ONE_OFF_TEMPLATE_NAME: &str = "__tera_one_off";
 
+/// Makes paths to the same file comparable, even when the file doesn't exist anymore.
+fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = std::fs::canonicalize(path) {
+        return canonical;
+    }
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    std::fs::canonicalize(parent)
+        .map(|p| p.join(file_name))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
 /// The escape function type definition
 pub type EscapeFn = fn(&str, &mut dyn Write) -> std::io::Result<()>;
 
@@ -69,7 +94,8 @@ pub struct Tera {
     /// Only used if the `glob_fs` feature is turned on
     #[allow(dead_code)]
     pub(crate) glob: Option<String>,
-    pub(crate) templates: HashMap<String, Template>,
+    /// Behind an `Arc` so cloning the instance, eg to reload it while it's being used, is cheap
+    pub(crate) templates: HashMap<String, Arc<Template>>,
     /// Which extensions does Tera automatically autoescape on.
     /// Defaults to [".html", ".htm", ".xml"]
     pub(crate) autoescape_suffixes: Vec<Cow<'static, str>>,
@@ -80,10 +106,20 @@ pub struct Tera {
     pub(crate) tests: HashMap<Cow<'static, str>, StoredTest>,
     pub(crate) functions: HashMap<Cow<'static, str>, StoredFunction>,
     pub(crate) components: HashMap<String, (ComponentDefinition, Chunk)>,
+    /// The name of the template defining each component
+    component_sources: HashMap<String, String>,
     /// Custom delimiters for template syntax
     delimiters: Delimiters,
     /// Fallback prefixes to try when a template is not found by exact name.
     fallback_prefixes: Vec<Cow<'static, str>>,
+    /// Where to look for templates that haven't been added
+    #[cfg(feature = "loader")]
+    loader: Option<Arc<dyn Loader>>,
+    /// Templates that were loaded on demand from the loader
+    #[cfg(feature = "loader")]
+    lazy_templates: LazyTemplates,
+    /// The limits used by all renders, unless given explicitly
+    render_limits: RenderLimits,
 }
 
 impl Tera {
@@ -125,24 +161,31 @@ impl Tera {
             .collect();
 
         let result = match load_from_glob(glob) {
-            Ok(entries) => {
-                let mut errors = Vec::new();
+            Ok(entries) => (|| -> TeraResult<()> {
+                // Report the syntax errors of all the files at once
+                let mut errors: Vec<ReportError> = Vec::new();
+                let mut inserted = Vec::with_capacity(entries.len());
                 for (path, name) in entries {
                     match self.add_file(&path, Some(&name)) {
-                        Ok((key, _)) => {
+                        Ok((key, previous)) => {
                             if let Some(tpl) = self.templates.get_mut(&key) {
-                                tpl.from_glob = true;
+                                Arc::make_mut(tpl).from_glob = true;
                             }
+                            inserted.push((key, previous));
                         }
-                        Err(e) => errors.push(format!("Failed to load {}: {e}", path.display())),
+                        Err(Error {
+                            kind: crate::ErrorKind::SyntaxError(report),
+                            ..
+                        }) => errors.push(*report),
+                        Err(e) => return Err(e),
                     }
                 }
                 if !errors.is_empty() {
-                    Err(Error::message(errors.join("\n")))
-                } else {
-                    self.finalize_templates()
+                    return Err(Error::invalid_templates(errors));
                 }
-            }
+                self.load_missing_dependencies(&mut inserted)?;
+                self.finalize_templates()
+            })(),
             Err(e) => Err(e),
         };
 
@@ -173,12 +216,135 @@ impl Tera {
         }
     }
 
+    /// Reloads the templates read from the given files, for example after a file watcher
+    /// reported them as changed.
+    ///
+    /// Only those files are parsed again and only the templates depending on them are checked
+    /// again, which is much faster than `Tera::full_reload` when there are many templates.
+    /// Templates whose file doesn't exist anymore are removed and, if the templates were loaded
+    /// with a glob, new files matching it are added. Other paths are ignored.
+    /// Templates loaded on demand by a `Loader` are dropped and will be loaded again.
+    ///
+    /// Nothing is changed if there is an error, eg if a deleted template is still included by
+    /// another one.
+    ///
+    /// ```no_run
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_template_files(vec![
+    ///     ("templates/base.html", Some("base.html")),
+    ///     ("templates/old.html", Some("old.html")),
+    /// ]).unwrap();
+    /// // `templates/base.html` was edited and `templates/old.html` deleted
+    /// tera.reload_paths(&["templates/base.html", "templates/old.html"]).unwrap();
+    /// ```
+    pub fn reload_paths<P: AsRef<Path>>(&mut self, paths: &[P]) -> TeraResult<()> {
+        // The name and path of the template for each normalized path
+        let known_paths: HashMap<PathBuf, (&str, &str)> = self
+            .templates
+            .values()
+            .filter_map(|tpl| {
+                let path = tpl.path.as_deref()?;
+                Some((normalize_path(Path::new(path)), (tpl.name.as_str(), path)))
+            })
+            .collect();
+        #[cfg(feature = "glob_fs")]
+        let glob = self.glob.as_deref().map(Glob::new).transpose()?;
+
+        let mut to_reload: Vec<(PathBuf, String)> = Vec::new();
+        let mut to_remove: Vec<String> = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let normalized = normalize_path(path);
+            match known_paths.get(&normalized) {
+                Some((name, _)) if to_reload.iter().any(|(_, n)| n == name) => (),
+                Some((name, _)) if to_remove.iter().any(|n| n == name) => (),
+                Some((name, tpl_path)) if path.is_file() => {
+                    to_reload.push((PathBuf::from(tpl_path), name.to_string()));
+                }
+                Some((name, _)) => to_remove.push(name.to_string()),
+                None => {
+                    #[cfg(feature = "glob_fs")]
+                    if path.is_file()
+                        && let Some(name) = glob.as_ref().and_then(|g| g.template_name(&normalized))
+                        && !to_reload.iter().any(|(_, n)| *n == name)
+                    {
+                        to_reload.push((normalized, name));
+                    }
+                }
+            }
+        }
+
+        if to_reload.is_empty() && to_remove.is_empty() {
+            self.clear_lazy_templates();
+            return Ok(());
+        }
+
+        let touched: Vec<&str> = to_reload
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .chain(to_remove.iter().map(|name| name.as_str()))
+            .collect();
+        // What depended on the templates before the changes need to be checked again
+        let previous_dependents: Vec<String> = self
+            .find_dependents(&touched, &self.component_sources)
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let mut changes: Vec<(String, Option<Arc<Template>>)> = Vec::new();
+        let result = (|| -> TeraResult<()> {
+            for (path, name) in to_reload {
+                let (key, previous) = self.add_file(&path, Some(&name))?;
+                // Files we didn't know about can only come from the glob
+                let from_glob = previous.as_ref().is_none_or(|tpl| tpl.from_glob);
+                Arc::make_mut(self.templates.get_mut(&key).unwrap()).from_glob = from_glob;
+                changes.push((key, previous));
+            }
+            self.load_missing_dependencies(&mut changes)?;
+            for name in to_remove {
+                let previous = self.templates.remove(&name);
+                changes.push((name, previous));
+            }
+
+            let component_sources = self.find_component_sources()?;
+            let touched: Vec<&str> = changes.iter().map(|(name, _)| name.as_str()).collect();
+            let mut names: HashSet<String> = self
+                .find_dependents(&touched, &component_sources)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            names.extend(touched.into_iter().map(String::from));
+            names.extend(previous_dependents);
+            names.retain(|name| self.templates.contains_key(name));
+            self.finalize_only(names.into_iter().collect(), component_sources)
+        })();
+
+        if result.is_err() {
+            for (key, previous) in changes.into_iter().rev() {
+                match previous {
+                    Some(old) => {
+                        self.templates.insert(key, old);
+                    }
+                    None => {
+                        self.templates.remove(&key);
+                    }
+                }
+            }
+        }
+        result
+    }
+
     fn set_templates_auto_escape(&mut self) {
         for (tpl_name, tpl) in self.templates.iter_mut() {
-            tpl.autoescape_enabled = self
+            let autoescape_enabled = self
                 .autoescape_suffixes
                 .iter()
                 .any(|s| tpl_name.ends_with(s.as_ref()));
+            // Only copy the templates shared with a clone of this instance if they change
+            if tpl.autoescape_enabled != autoescape_enabled {
+                Arc::make_mut(tpl).autoescape_enabled = autoescape_enabled;
+            }
         }
     }
 
@@ -205,6 +371,7 @@ impl Tera {
     ) {
         self.autoescape_suffixes = suffixes.into_iter().map(Into::into).collect();
         self.set_templates_auto_escape();
+        self.clear_lazy_templates();
     }
 
     /// Set custom delimiters for template syntax.
@@ -236,6 +403,7 @@ impl Tera {
         }
         delimiters.validate()?;
         self.delimiters = delimiters;
+        self.clear_lazy_templates();
         Ok(())
     }
 
@@ -399,11 +567,137 @@ impl Tera {
                 let included = self.must_get_template(include_name)?;
                 templates_to_visit.push(included);
             }
+            for include_name in current_template.optional_include_calls.keys() {
+                if let Some(included) = self.get_template(include_name) {
+                    templates_to_visit.push(included);
+                }
+            }
         }
 
         Ok(vars)
     }
 
+    /// Returns the names of all the templates needed to render the given one: the templates it
+    /// extends, includes or calls components from, and their own dependencies recursively.
+    ///
+    /// The names are the ones the templates were registered with, after resolving the
+    /// fallback prefixes.
+    ///
+    /// Only the templates included with a literal name are known: a template included through
+    /// a variable, like `{% include tpl_name %}`, is not part of the dependencies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("base.html", "{% include 'nav.html' %}{% block content %}{% endblock %}"),
+    ///     ("nav.html", "{{<link />}}"),
+    ///     ("components.html", "{% component link() %}<a>{% endcomponent %}"),
+    ///     ("page.html", "{% extends 'base.html' %}"),
+    /// ]).unwrap();
+    ///
+    /// let mut dependencies: Vec<_> = tera.get_template_dependencies("page.html").unwrap().into_iter().collect();
+    /// dependencies.sort();
+    /// assert_eq!(dependencies, vec!["base.html", "components.html", "nav.html"]);
+    /// ```
+    pub fn get_template_dependencies(&self, template_name: &str) -> TeraResult<HashSet<&str>> {
+        let template = self.must_get_template(template_name)?;
+        let mut dependencies: HashSet<&str> = HashSet::new();
+        let mut templates_to_visit: Vec<&str> =
+            self.direct_dependencies(template, &self.component_sources);
+
+        while let Some(name) = templates_to_visit.pop() {
+            if name == template.name || !dependencies.insert(name) {
+                continue;
+            }
+            let dependency = self.must_get_template(name)?;
+            templates_to_visit
+                .extend(self.direct_dependencies(dependency, &self.component_sources));
+        }
+
+        Ok(dependencies)
+    }
+
+    /// Returns the names of all the templates that depend on the given one, directly or not:
+    /// the ones that might render differently if it changes.
+    ///
+    /// This is the reverse of [`Tera::get_template_dependencies`] and has the same limitation:
+    /// a template including the given one through a variable, like `{% include tpl_name %}`,
+    /// is not returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("base.html", "{% include 'nav.html' %}{% block content %}{% endblock %}"),
+    ///     ("nav.html", "<nav></nav>"),
+    ///     ("page.html", "{% extends 'base.html' %}"),
+    ///     ("about.html", "About"),
+    /// ]).unwrap();
+    ///
+    /// let mut dependents: Vec<_> = tera.get_template_dependents("nav.html").unwrap().into_iter().collect();
+    /// dependents.sort();
+    /// assert_eq!(dependents, vec!["base.html", "page.html"]);
+    /// ```
+    pub fn get_template_dependents(&self, template_name: &str) -> TeraResult<HashSet<&str>> {
+        let template = self.must_get_template(template_name)?;
+        Ok(self.find_dependents(&[template.name.as_str()], &self.component_sources))
+    }
+
+    /// The templates the given one uses directly: its parents, the templates it includes with
+    /// a literal name and the templates defining the components it calls.
+    fn direct_dependencies<'a>(
+        &'a self,
+        template: &'a Template,
+        component_sources: &'a HashMap<String, String>,
+    ) -> Vec<&'a str> {
+        let mut dependencies: Vec<&str> = template.parents.iter().map(|p| p.as_str()).collect();
+        for include_name in template.included_names() {
+            if let Some(included) = self.get_template(include_name) {
+                dependencies.push(included.name.as_str());
+            }
+        }
+        for component_name in template.component_calls.keys() {
+            if let Some(source) = component_sources.get(component_name) {
+                dependencies.push(source.as_str());
+            }
+        }
+        dependencies
+    }
+
+    /// The templates depending on any of the given ones, directly or not, excluding them.
+    fn find_dependents<'a>(
+        &'a self,
+        names: &[&str],
+        component_sources: &'a HashMap<String, String>,
+    ) -> HashSet<&'a str> {
+        let mut reverse_graph: HashMap<&str, Vec<&str>> = HashMap::new();
+        for name in self.get_template_names() {
+            let tpl = self.get_template(name).expect("template to exist");
+            for dependency in self.direct_dependencies(tpl, component_sources) {
+                reverse_graph
+                    .entry(dependency)
+                    .or_default()
+                    .push(tpl.name.as_str());
+            }
+        }
+
+        let mut dependents: HashSet<&str> = HashSet::new();
+        let mut templates_to_visit: Vec<&str> = names.to_vec();
+        while let Some(name) = templates_to_visit.pop() {
+            for dependent in reverse_graph.get(name).into_iter().flatten() {
+                if !names.contains(dependent) && dependents.insert(dependent) {
+                    templates_to_visit.push(dependent);
+                }
+            }
+        }
+        dependents
+    }
+
     /// Returns information about a registered component definition.
     ///
     /// Returns `None` if no component with the given name is found.
@@ -428,6 +722,164 @@ impl Tera {
             .map(|(def, _)| ComponentInfo::from(def))
     }
 
+    /// Returns an iterator over all the components that can be called, in an unspecified order,
+    /// along with the name of the template defining them and where in that template.
+    ///
+    /// When several templates define the same component, only the one that is used is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template(
+    ///     "components.html",
+    ///     r#"{% component Button(label) %}<button>{{ label }}</button>{% endcomponent Button %}"#,
+    /// ).unwrap();
+    ///
+    /// let (template_name, symbol, info) = tera.get_components().next().unwrap();
+    /// assert_eq!(template_name, "components.html");
+    /// assert_eq!(symbol.span().start_col, 13);
+    /// assert_eq!(info.name(), "Button");
+    /// ```
+    pub fn get_components(&self) -> impl Iterator<Item = (&str, Symbol, ComponentInfo)> {
+        self.component_sources
+            .iter()
+            .filter_map(|(component_name, tpl_name)| {
+                let tpl = &self.templates[tpl_name];
+                let span = tpl.component_definition_spans.get(component_name)?;
+                let (def, _) = &tpl.components[component_name];
+                Some((
+                    tpl.name.as_str(),
+                    Symbol::new(component_name, span),
+                    ComponentInfo::from(def),
+                ))
+            })
+    }
+
+    /// Returns information about a template: where it was loaded from, what it extends and what
+    /// it defines and references.
+    ///
+    /// If a loader is set, it will be asked for the template if it's not loaded yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("base.html", "{% block content %}{% endblock %}"),
+    ///     ("page.html", r#"{% extends "base.html" %}{% block content %}{% include "nav.html" %}{% endblock %}"#),
+    ///     ("nav.html", "<nav></nav>"),
+    /// ]).unwrap();
+    ///
+    /// let info = tera.get_template_info("page.html").unwrap();
+    /// assert_eq!(info.parents(), ["base.html"]);
+    /// assert_eq!(info.symbols().blocks()[0].name(), "content");
+    /// assert_eq!(info.symbols().includes()[0].name(), "nav.html");
+    /// assert!(info.autoescape());
+    /// ```
+    pub fn get_template_info(&self, template_name: &str) -> TeraResult<TemplateInfo> {
+        self.must_get_template(template_name)
+            .map(TemplateInfo::from)
+    }
+
+    /// Returns an iterator over information about all registered templates, in an unspecified
+    /// order.
+    ///
+    /// Like [`Tera::get_template_names`], this includes the templates loaded on demand from the
+    /// loader so far.
+    pub fn get_templates(&self) -> impl Iterator<Item = TemplateInfo> {
+        self.get_template_names()
+            .filter_map(|name| self.get_template(name))
+            .map(TemplateInfo::from)
+    }
+
+    /// Parses the given template source and returns what it defines and references, without
+    /// adding it to this instance.
+    ///
+    /// Only syntax errors are returned: the references are not checked. This is meant for tools
+    /// like editors that need to work with templates that are not valid yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let tera = Tera::default();
+    /// let symbols = tera
+    ///     .parse_template_symbols("page.html", r#"{% extends "base.html" %}{% block content %}{% endblock %}"#)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(symbols.extends().unwrap().name(), "base.html");
+    /// assert_eq!(symbols.blocks()[0].name(), "content");
+    /// ```
+    pub fn parse_template_symbols(&self, name: &str, source: &str) -> TeraResult<TemplateSymbols> {
+        let template = Template::new(name, source, None, self.delimiters.clone())?;
+        Ok(TemplateSymbols::from(&template))
+    }
+
+    /// Formats the source of a template with a canonical layout and returns it.
+    ///
+    /// Tags get a single space inside their delimiters and between their tokens, except where
+    /// it would look odd (eg `a.b`, `f(x=1)` or `a[1:2]`), and tags starting a line are indented
+    /// according to how deeply they are nested if that indentation is trimmed by whitespace
+    /// control, eg `{%- if %}`. Comments, raw blocks, literals and the content outside of tags
+    /// are left as written so the rendered output is the same: nested tags without whitespace
+    /// control keep their indentation, since it is rendered.
+    ///
+    /// The template needs to be valid to be formatted: an error is returned otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let tera = Tera::default();
+    /// let formatted = tera.format_template("hello.html", "{{name|upper}}").unwrap();
+    ///
+    /// assert_eq!(formatted, "{{ name | upper }}");
+    /// ```
+    pub fn format_template(&self, name: &str, source: &str) -> TeraResult<String> {
+        Template::new(name, source, None, self.delimiters.clone())?;
+        crate::formatter::format_source(name, source, &self.delimiters)
+    }
+
+    /// Returns the warnings about the templates loaded: things that render fine but are likely
+    /// mistakes, such as variables set but never used or components never called.
+    ///
+    /// The warnings are sorted by template name and then position in the template.
+    /// Templates added with `add_compiled_templates` don't have warnings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("hello.html", "{% set name = 'Bob' %}Hello").unwrap();
+    ///
+    /// let warnings = tera.warnings();
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(warnings[0].code(), "unused-variable");
+    /// ```
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        let called: HashSet<&str> = self
+            .templates
+            .values()
+            .flat_map(|tpl| tpl.component_calls.keys().map(|name| name.as_str()))
+            .collect();
+
+        let mut templates: Vec<_> = self.templates.values().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut warnings = Vec::new();
+        for tpl in templates {
+            let mut tpl_warnings = tpl.warnings.clone();
+            tpl_warnings.extend(crate::lints::unused_components(tpl, &called));
+            tpl_warnings.sort_by_key(|w| w.span().map(|s| s.range.start));
+            warnings.extend(tpl_warnings);
+        }
+        warnings
+    }
+
     fn register_builtin_filters(&mut self) {
         self.register_filter("safe", crate::filters::safe);
         self.register_filter("default", crate::filters::default);
@@ -465,6 +917,48 @@ impl Tera {
         self.register_filter("keys", crate::filters::keys);
         self.register_filter("pairs", crate::filters::pairs);
         self.register_filter("group_by", crate::filters::group_by);
+        self.register_filter("map", crate::filters::map);
+        self.register_filter("filter", crate::filters::filter);
+        self.register_filter("select", crate::filters::select);
+        self.register_filter("reject", crate::filters::reject);
+        self.register_filter("sum", crate::filters::sum);
+        self.register_filter("min", crate::filters::min);
+        self.register_filter("max", crate::filters::max);
+        self.register_filter("batch", crate::filters::batch);
+
+        // The arguments of the built-in filters that can be given by position, eg `round(2)`
+        for (name, params) in [
+            ("default", &["value", "boolean"][..]),
+            ("pluralize", &["singular", "plural"]),
+            ("trim", &["pat"]),
+            ("trim_start", &["pat"]),
+            ("trim_end", &["pat"]),
+            ("replace", &["from", "to"]),
+            ("truncate", &["length", "end"]),
+            ("indent", &["width", "first", "blank"]),
+            ("int", &["base"]),
+            ("split", &["pat"]),
+            ("round", &["precision", "method"]),
+            ("nth", &["n"]),
+            ("join", &["sep"]),
+            ("sort", &["attribute", "key"]),
+            ("unique", &["attribute", "key"]),
+            ("get", &["key", "default"]),
+            ("group_by", &["attribute", "key"]),
+            ("map", &["filter", "args"]),
+            ("filter", &["attribute", "value"]),
+            ("select", &["test", "args"]),
+            ("reject", &["test", "args"]),
+            ("sum", &["attribute", "start"]),
+            ("min", &["attribute", "key"]),
+            ("max", &["attribute", "key"]),
+            ("batch", &["size", "fill_with"]),
+        ] {
+            self.filters
+                .get_mut(name)
+                .expect("to be a built-in filter")
+                .set_positional_args(params);
+        }
     }
 
     fn register_builtin_tests(&mut self) {
@@ -485,20 +979,46 @@ impl Tera {
         self.register_test("starting_with", crate::tests::is_starting_with);
         self.register_test("ending_with", crate::tests::is_ending_with);
         self.register_test("containing", crate::tests::is_containing);
+
+        // The arguments of the built-in tests that can be given by position, eg `divisible_by(3)`
+        for (name, params) in [
+            ("divisible_by", &["divisor"][..]),
+            ("starting_with", &["pat"]),
+            ("ending_with", &["pat"]),
+            ("containing", &["pat"]),
+        ] {
+            self.tests
+                .get_mut(name)
+                .expect("to be a built-in test")
+                .set_positional_args(params);
+        }
     }
 
     fn register_builtin_functions(&mut self) {
         self.register_function("range", crate::functions::range);
         self.register_function("throw", crate::functions::throw);
+
+        // Only `end` for `range` so `range(2, 5)` is an error rather than being read as
+        // `range(end=2, start=5)`
+        for (name, params) in [("range", &["end"][..]), ("throw", &["message"])] {
+            self.functions
+                .get_mut(name)
+                .expect("to be a built-in function")
+                .set_positional_args(params);
+        }
     }
 
-    /// Validates that all filters/tests/functions/components/includes referenced by a template exist.
+    /// Validates that all filters/tests/functions/components/includes referenced by a template exist,
+    /// that the component calls arguments match what we know of them before rendering and that
+    /// the blocks it defines exist in one of its parents, if it has any.
     /// Returns a vec of (source_position, error_report) for any missing references.
-    fn validate_template_references(
+    fn validate_template_references<'c>(
         &self,
         tpl: &Template,
-        is_known_component: impl Fn(&str) -> bool,
-    ) -> Vec<(usize, String)> {
+        parents: &[&Template],
+        get_component: impl Fn(&str) -> Option<&'c ComponentDefinition>,
+        is_known_template: impl Fn(&str) -> bool,
+    ) -> Vec<ReportError> {
         let mut errors = Vec::new();
 
         for (filter, spans) in &tpl.filter_calls {
@@ -509,8 +1029,9 @@ impl Tera {
                         &tpl.name,
                         &tpl.source,
                         span,
-                    );
-                    errors.push((span.range.start, err.generate_report()));
+                    )
+                    .with_code("unknown-filter");
+                    errors.push(err);
                 }
             }
         }
@@ -523,8 +1044,9 @@ impl Tera {
                         &tpl.name,
                         &tpl.source,
                         span,
-                    );
-                    errors.push((span.range.start, err.generate_report()));
+                    )
+                    .with_code("unknown-test");
+                    errors.push(err);
                 }
             }
         }
@@ -537,36 +1059,71 @@ impl Tera {
                         &tpl.name,
                         &tpl.source,
                         span,
-                    );
-                    errors.push((span.range.start, err.generate_report()));
+                    )
+                    .with_code("unknown-function");
+                    errors.push(err);
                 }
             }
         }
 
         for (component, spans) in &tpl.component_calls {
-            if !is_known_component(component.as_str()) {
+            if get_component(component.as_str()).is_none() {
                 for span in spans {
                     let err = ReportError::new(
                         format!("Unknown component `{component}`"),
                         &tpl.name,
                         &tpl.source,
                         span,
-                    );
-                    errors.push((span.range.start, err.generate_report()));
+                    )
+                    .with_code("unknown-component");
+                    errors.push(err);
                 }
             }
         }
 
+        for call in &tpl.component_call_sites {
+            let Some(component) = get_component(&call.name) else {
+                continue;
+            };
+            if let Err(msg) = component.check_call(call) {
+                let err = ReportError::new(msg, &tpl.name, &tpl.source, &call.span)
+                    .with_code("invalid-component-call");
+                errors.push(err);
+            }
+            if let Err((msg, span)) = component.check_slots(call) {
+                let err = ReportError::new(msg, &tpl.name, &tpl.source, span)
+                    .with_code("invalid-component-call");
+                errors.push(err);
+            }
+        }
+
         for (include_name, spans) in &tpl.include_calls {
-            if self.resolve_template_name(include_name).is_none() {
+            if !is_known_template(include_name) {
                 for span in spans {
                     let err = ReportError::new(
                         format!("Unknown template `{include_name}`"),
                         &tpl.name,
                         &tpl.source,
                         span,
-                    );
-                    errors.push((span.range.start, err.generate_report()));
+                    )
+                    .with_code("unknown-template");
+                    errors.push(err);
+                }
+            }
+        }
+
+        // Check that blocks in child templates exist in at least one parent
+        if !parents.is_empty() {
+            for (block_name, span) in &tpl.block_name_spans {
+                if !parents.iter().any(|p| p.blocks.contains_key(block_name)) {
+                    let err = ReportError::new(
+                        format!("Block `{block_name}` is not defined in any parent template"),
+                        &tpl.name,
+                        &tpl.source,
+                        span,
+                    )
+                    .with_code("unknown-block");
+                    errors.push(err);
                 }
             }
         }
@@ -577,21 +1134,22 @@ impl Tera {
     /// Optimizes the templates when possible and doing some light
     /// checks like whether blocks/macros/templates all exist when they are used
     fn finalize_templates(&mut self) -> TeraResult<()> {
-        let mut tpl_parents: HashMap<String, Vec<String>> =
-            HashMap::with_capacity(self.templates.len());
-        let mut tpl_size_hint: HashMap<String, usize> =
-            HashMap::with_capacity(self.templates.len());
-        // Track which template defined each component: component_name -> (tpl_name, priority)
+        let component_sources = self.find_component_sources()?;
+        let names: Vec<String> = self.templates.keys().cloned().collect();
+        self.finalize_only(names, component_sources)
+    }
+
+    /// Finds which template defines each component: when several templates define the same
+    /// component, the one with the highest fallback prefix priority wins.
+    fn find_component_sources(&self) -> TeraResult<HashMap<String, String>> {
+        // component_name -> (tpl_name, priority)
         let mut component_sources: HashMap<&str, (&str, usize)> = HashMap::new();
 
-        // 1st loop: find parents of each template and check for duplicate components
-        // Sort so error messages (circular include chains, etc.) are deterministic
+        // Sort so error messages are deterministic
         let mut ordered_names: Vec<&String> = self.templates.keys().collect();
         ordered_names.sort();
         for name in ordered_names {
             let tpl = &self.templates[name];
-            let parents = find_parents(self, tpl, tpl, vec![])?;
-            check_include_cycles(self, tpl)?;
             for component_name in tpl.components.keys() {
                 let current_priority = self.get_template_priority(&tpl.name);
 
@@ -617,6 +1175,37 @@ impl Tera {
                     }
                 }
             }
+        }
+
+        Ok(component_sources
+            .into_iter()
+            .map(|(component_name, (tpl_name, _))| {
+                (component_name.to_string(), tpl_name.to_string())
+            })
+            .collect())
+    }
+
+    /// Finalizes the given templates only: the other ones need to be unaffected by the
+    /// changes made since they were last finalized.
+    /// Nothing is changed if there is an error.
+    fn finalize_only(
+        &mut self,
+        mut names: Vec<String>,
+        component_sources: HashMap<String, String>,
+    ) -> TeraResult<()> {
+        let mut tpl_parents: HashMap<String, Vec<String>> = HashMap::with_capacity(names.len());
+        let mut tpl_size_hint: HashMap<String, usize> = HashMap::with_capacity(names.len());
+        let lookup = |name: &str| {
+            self.find_with_prefixes(name, |n| self.templates.get(n).map(AsRef::as_ref))
+        };
+
+        // 1st loop: find parents of each template
+        // Sort so error messages (circular include chains, etc.) are deterministic
+        names.sort();
+        for name in &names {
+            let tpl = &self.templates[name];
+            let parents = find_parents(&lookup, tpl, tpl, vec![])?;
+            check_include_cycles(&lookup, tpl)?;
 
             // This will include the Tera expr etc but it's ok, it's just a hint
             let mut size_hint = tpl.source.len();
@@ -631,143 +1220,303 @@ impl Tera {
         // Build components map from component_sources (needed for validation)
         let components: HashMap<String, (ComponentDefinition, Chunk)> = component_sources
             .iter()
-            .map(|(component_name, (tpl_name, _))| {
-                let tpl = &self.templates[*tpl_name];
-                let data = tpl.components[*component_name].clone();
-                (component_name.to_string(), data)
+            .map(|(component_name, tpl_name)| {
+                let tpl = &self.templates[tpl_name];
+                let data = tpl.components[component_name].clone();
+                (component_name.clone(), data)
             })
             .collect();
 
         // 2nd loop: we check whether all called components/filters/tests/functions are defined
         // as well as finding each block lineage
         let mut tpl_blocks: HashMap<String, HashMap<String, Vec<Chunk>>> =
-            HashMap::with_capacity(self.templates.len());
-        // Collect errors with their location for stable sorting
-        let mut errors: Vec<(&str, usize, String)> = Vec::new();
-
-        for (name, tpl) in &self.templates {
-            // Validate filter/test/function/component/include references
-            for (pos, report) in
-                self.validate_template_references(tpl, |c| components.contains_key(c))
-            {
-                errors.push((&tpl.name, pos, report));
-            }
-
-            // Check that blocks in child templates exist in at least one parent
-            let parents = &tpl_parents[name];
-            if !parents.is_empty() {
-                for (block_name, span) in &tpl.block_name_spans {
-                    let exists_in_parent = parents.iter().any(|parent_name| {
-                        self.templates
-                            .get(parent_name)
-                            .map(|p| p.blocks.contains_key(block_name))
-                            .unwrap_or(false)
-                    });
-                    if !exists_in_parent {
-                        let err = ReportError::new(
-                            format!("Block `{block_name}` is not defined in any parent template"),
-                            &tpl.name,
-                            &tpl.source,
-                            span,
-                        );
-                        errors.push((&tpl.name, span.range.start, err.generate_report()));
-                    }
-                }
-            }
+            HashMap::with_capacity(names.len());
+        let mut errors: Vec<ReportError> = Vec::new();
 
-            let mut blocks = HashMap::with_capacity(tpl.blocks.len());
-            for (block_name, chunk) in &tpl.blocks {
-                let mut all_blocks = vec![chunk.clone()];
-                if chunk.is_calling_function("super") {
-                    for parent_tpl_name in tpl_parents[name].iter().rev() {
-                        let parent_tpl = self.must_get_template(parent_tpl_name)?;
-                        if let Some(parent_chunk) = parent_tpl.blocks.get(block_name) {
-                            all_blocks.push(parent_chunk.clone());
-                            if !parent_chunk.is_calling_function("super") {
-                                break;
-                            }
-                        }
-                    }
-                }
-                blocks.insert(block_name.clone(), all_blocks);
-            }
-            tpl_blocks.insert(name.clone(), blocks);
-        }
+        for name in &names {
+            let tpl = &self.templates[name];
+            let parents: Vec<&Template> = tpl_parents[name]
+                .iter()
+                .map(|p| self.templates[p].as_ref())
+                .collect();
+            // Validate filter/test/function/component/include/block references
+            errors.extend(self.validate_template_references(
+                tpl,
+                &parents,
+                |c| components.get(c).map(|(def, _)| def),
+                |t| lookup(t).is_some(),
+            ));
 
-        // Add inherited blocks from parents that aren't overridden in child templates
-        for (name, parents) in &tpl_parents {
-            for parent_name in parents.iter().rev() {
-                if let Some(parent_blocks) = tpl_blocks.get(parent_name).cloned() {
-                    let child_blocks = tpl_blocks.get_mut(name).unwrap();
-                    for (block_name, lineage) in parent_blocks {
-                        child_blocks.entry(block_name).or_insert(lineage);
-                    }
-                }
-            }
+            tpl_blocks.insert(name.clone(), build_block_lineage(tpl, &parents));
         }
 
         if !errors.is_empty() {
-            // Sort by template name, then by position in source
-            errors.sort_by(|a, b| a.0.cmp(b.0).then(a.1.cmp(&b.1)));
-            let reports: Vec<String> = errors.into_iter().map(|(_, _, report)| report).collect();
-            return Err(Error::message(reports.join("\n\n")));
+            return Err(Error::invalid_templates(errors));
         }
 
         // 3rd loop: we actually set everything we've done on the templates objects
-        for (name, tpl) in self.templates.iter_mut() {
-            tpl.total_content_num_bytes = tpl_size_hint.remove(name.as_str()).unwrap();
-            tpl.parents = tpl_parents.remove(name.as_str()).unwrap();
-            tpl.block_lineage = tpl_blocks.remove(name.as_str()).unwrap();
+        for name in names {
+            let tpl = Arc::make_mut(self.templates.get_mut(&name).unwrap());
+            tpl.total_content_num_bytes = tpl_size_hint.remove(&name).unwrap();
+            tpl.parents = tpl_parents.remove(&name).unwrap();
+            tpl.block_lineage = tpl_blocks.remove(&name).unwrap();
         }
 
         self.components = components;
+        self.component_sources = component_sources;
         self.set_templates_auto_escape();
+        // Templates loaded on demand were checked against the previous set of templates
+        self.clear_lazy_templates();
         Ok(())
     }
 
-    /// Add a single template to the Tera instance.
-    ///
-    /// This will error if there are errors in the inheritance, such as adding a child
-    /// template without the parent one.
-    ///
-    /// # Bulk loading
-    ///
-    /// If you want to add several templates, use
-    /// [`add_raw_templates()`](Tera::add_raw_templates).
-    ///
-    /// # Examples
-    ///
-    /// Basic usage:
-    ///
-    /// ```
-    /// # use tera::Tera;
-    /// let mut tera = Tera::default();
-    /// tera.add_raw_template("new.html", "Blabla").unwrap();
-    /// ```
-    pub fn add_raw_template(&mut self, name: &str, content: &str) -> TeraResult<()> {
-        self.add_raw_templates(std::iter::once((name, content)))
+    /// Drops the templates loaded on demand, so they are loaded and checked again.
+    fn clear_lazy_templates(&mut self) {
+        #[cfg(feature = "loader")]
+        self.lazy_templates.clear();
     }
 
-    /// Add all the templates given to the Tera instance
-    ///
-    /// This will error if there are errors in the inheritance, such as adding a child
-    /// template without the parent one.
-    ///
-    /// ```
-    /// # use tera::Tera;
-    /// let mut tera = Tera::default();
-    /// tera.add_raw_templates(vec![
-    ///     ("new.html", "blabla"),
-    ///     ("new2.html", "hello"),
-    /// ]).unwrap();
-    /// ```
-    pub fn add_raw_templates<I, N, C>(&mut self, templates: I) -> TeraResult<()>
-    where
-        I: IntoIterator<Item = (N, C)>,
+    /// Asks the loader for a template, trying the fallback prefixes if it doesn't have the
+    /// exact name. Returns the name it was found under along with the template.
+    #[cfg(feature = "loader")]
+    fn load_from_loader(
+        &self,
+        loader: &dyn Loader,
+        name: &str,
+    ) -> TeraResult<Option<(String, LoadedTemplate)>> {
+        for candidate in self.candidate_names(name) {
+            if let Some(loaded) = loader.load(&candidate)? {
+                return Ok(Some((candidate.into_owned(), loaded)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Adds the parents and includes of the inserted templates that are not known yet, as long
+    /// as the loader has them, recursively.
+    #[cfg(feature = "loader")]
+    fn load_missing_dependencies(
+        &mut self,
+        inserted: &mut Vec<(String, Option<Arc<Template>>)>,
+    ) -> TeraResult<()> {
+        let Some(loader) = self.loader.clone() else {
+            return Ok(());
+        };
+        let mut queue: Vec<String> = Vec::new();
+        for (name, _) in inserted.iter() {
+            let tpl = &self.templates[name];
+            queue.extend(tpl.extends.iter().chain(tpl.included_names()).cloned());
+        }
+
+        while let Some(wanted) = queue.pop() {
+            if self
+                .find_with_prefixes(&wanted, |n| self.templates.get(n).map(AsRef::as_ref))
+                .is_some()
+            {
+                continue;
+            }
+            // If the loader doesn't have it either, finalizing will report it
+            let Some((tpl_name, loaded)) = self.load_from_loader(loader.as_ref(), &wanted)? else {
+                continue;
+            };
+            let template = Template::new(
+                &tpl_name,
+                &loaded.source,
+                loaded.path,
+                self.delimiters.clone(),
+            )?;
+            queue.extend(
+                template
+                    .extends
+                    .iter()
+                    .chain(template.included_names())
+                    .cloned(),
+            );
+            let previous = self.templates.insert(tpl_name.clone(), Arc::new(template));
+            inserted.push((tpl_name, previous));
+        }
+
+        Ok(())
+    }
+
+    /// Loads a template Tera doesn't know about from the loader, along with the parents and
+    /// includes it needs that are not known either.
+    /// The new templates are checked the same way `finalize_templates` does before being cached.
+    #[cfg(feature = "loader")]
+    fn load_template(&self, name: &str) -> TeraResult<Option<&Template>> {
+        let Some(loader) = &self.loader else {
+            return Ok(None);
+        };
+
+        let mut batch: HashMap<String, Template> = HashMap::new();
+        let mut requested = None;
+        let mut queue = vec![name.to_string()];
+        while let Some(wanted) = queue.pop() {
+            if self.get_template(&wanted).is_some()
+                || self.find_with_prefixes(&wanted, |n| batch.get(n)).is_some()
+            {
+                continue;
+            }
+            let Some((tpl_name, loaded)) = self.load_from_loader(loader.as_ref(), &wanted)? else {
+                continue;
+            };
+            let template = Template::new(
+                &tpl_name,
+                &loaded.source,
+                loaded.path,
+                self.delimiters.clone(),
+            )?;
+            if !template.components.is_empty() {
+                return Err(Error::message(format!(
+                    "Template `{tpl_name}` defines components: it needs to be added to Tera directly instead of being loaded on demand"
+                )));
+            }
+            queue.extend(
+                template
+                    .extends
+                    .iter()
+                    .chain(template.included_names())
+                    .cloned(),
+            );
+            requested.get_or_insert_with(|| tpl_name.clone());
+            batch.insert(tpl_name, template);
+        }
+
+        let Some(requested) = requested else {
+            return Ok(None);
+        };
+
+        let lookup = |name: &str| {
+            self.find_with_prefixes(name, |n| {
+                self.templates
+                    .get(n)
+                    .map(AsRef::as_ref)
+                    .or_else(|| self.lazy_templates.get(n))
+                    .or_else(|| batch.get(n))
+            })
+        };
+        let mut ordered_names: Vec<&String> = batch.keys().collect();
+        ordered_names.sort();
+        let mut finalized = Vec::with_capacity(batch.len());
+        let mut errors: Vec<ReportError> = Vec::new();
+        for name in ordered_names {
+            let tpl = &batch[name];
+            let parent_names = find_parents(&lookup, tpl, tpl, vec![])?;
+            check_include_cycles(&lookup, tpl)?;
+            let parents: Vec<&Template> = parent_names
+                .iter()
+                .map(|p| lookup(p).expect("parent to be found"))
+                .collect();
+
+            errors.extend(self.validate_template_references(
+                tpl,
+                &parents,
+                |c| self.components.get(c).map(|(def, _)| def),
+                |t| lookup(t).is_some(),
+            ));
+
+            let size_hint =
+                tpl.source.len() + parents.iter().map(|p| p.source.len()).sum::<usize>();
+            let block_lineage = build_block_lineage(tpl, &parents);
+            finalized.push((name.clone(), parent_names, block_lineage, size_hint));
+        }
+
+        if !errors.is_empty() {
+            return Err(Error::invalid_templates(errors));
+        }
+
+        for (name, parents, block_lineage, size_hint) in finalized {
+            let tpl = batch.get_mut(&name).unwrap();
+            tpl.total_content_num_bytes = size_hint;
+            tpl.parents = parents;
+            tpl.block_lineage = block_lineage;
+            tpl.autoescape_enabled = self
+                .autoescape_suffixes
+                .iter()
+                .any(|s| name.ends_with(s.as_ref()));
+        }
+        self.lazy_templates.extend(batch.into_values());
+
+        Ok(self.lazy_templates.get(&requested))
+    }
+
+    /// Get a template loaded on demand from the loader so far.
+    #[cfg(feature = "loader")]
+    fn lazy_template(&self, name: &str) -> Option<&Template> {
+        self.lazy_templates.get(name)
+    }
+
+    #[cfg(feature = "loader")]
+    fn lazy_template_names(&self) -> Vec<&str> {
+        self.lazy_templates.names()
+    }
+
+    #[cfg(not(feature = "loader"))]
+    fn load_missing_dependencies(
+        &mut self,
+        _inserted: &mut Vec<(String, Option<Arc<Template>>)>,
+    ) -> TeraResult<()> {
+        Ok(())
+    }
+
+    #[cfg(not(feature = "loader"))]
+    fn load_template(&self, _name: &str) -> TeraResult<Option<&Template>> {
+        Ok(None)
+    }
+
+    #[cfg(not(feature = "loader"))]
+    fn lazy_template(&self, _name: &str) -> Option<&Template> {
+        None
+    }
+
+    #[cfg(not(feature = "loader"))]
+    fn lazy_template_names(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Add a single template to the Tera instance.
+    ///
+    /// This will error if there are errors in the inheritance, such as adding a child
+    /// template without the parent one.
+    ///
+    /// # Bulk loading
+    ///
+    /// If you want to add several templates, use
+    /// [`add_raw_templates()`](Tera::add_raw_templates).
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("new.html", "Blabla").unwrap();
+    /// ```
+    pub fn add_raw_template(&mut self, name: &str, content: &str) -> TeraResult<()> {
+        self.add_raw_templates(std::iter::once((name, content)))
+    }
+
+    /// Add all the templates given to the Tera instance
+    ///
+    /// This will error if there are errors in the inheritance, such as adding a child
+    /// template without the parent one.
+    ///
+    /// ```
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("new.html", "blabla"),
+    ///     ("new2.html", "hello"),
+    /// ]).unwrap();
+    /// ```
+    pub fn add_raw_templates<I, N, C>(&mut self, templates: I) -> TeraResult<()>
+    where
+        I: IntoIterator<Item = (N, C)>,
         N: AsRef<str>,
         C: AsRef<str>,
     {
-        let mut inserted: Vec<(String, Option<Template>)> = Vec::new();
+        let mut inserted: Vec<(String, Option<Arc<Template>>)> = Vec::new();
         let result = (|| -> TeraResult<()> {
             for (name, content) in templates {
                 let template = Template::new(
@@ -777,9 +1526,10 @@ impl Tera {
                     self.delimiters.clone(),
                 )?;
                 let key = name.as_ref().to_string();
-                let previous = self.templates.insert(key.clone(), template);
+                let previous = self.templates.insert(key.clone(), Arc::new(template));
                 inserted.push((key, previous));
             }
+            self.load_missing_dependencies(&mut inserted)?;
             self.finalize_templates()
         })();
 
@@ -799,6 +1549,76 @@ impl Tera {
         result
     }
 
+    /// Serializes the templates of this instance, already parsed and compiled, so they can be
+    /// loaded later with [`add_compiled_templates`](Self::add_compiled_templates) without
+    /// being parsed again.
+    ///
+    /// The bytes can only be loaded by the same version of Tera.
+    /// Templates loaded on demand by a `Loader` are not included. Filters, tests, functions
+    /// and settings like autoescaping are not included either: they need to be set on the Tera
+    /// instance loading the templates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tera::{Tera, Context};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("hello.html", "Hello {{ name }}!").unwrap();
+    /// let bytes = tera.compiled_templates();
+    ///
+    /// // For example when starting the application
+    /// let mut tera = Tera::default();
+    /// tera.add_compiled_templates(&bytes).unwrap();
+    /// let out = tera.render("hello.html", &tera::context! { name => "Bob" }).unwrap();
+    /// assert_eq!(out, "Hello Bob!");
+    /// ```
+    pub fn compiled_templates(&self) -> Vec<u8> {
+        let mut templates: Vec<&Template> = self.templates.values().map(AsRef::as_ref).collect();
+        // Keep the output deterministic
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        bundle::encode_templates(templates.into_iter())
+    }
+
+    /// Adds templates serialized by [`compiled_templates`](Self::compiled_templates),
+    /// replacing any existing templates with the same names.
+    ///
+    /// The templates are not parsed again but they are still checked against this instance,
+    /// eg that the filters they use exist, like with [`add_raw_templates`](Self::add_raw_templates).
+    /// An error is returned if the bytes were created by another version of Tera.
+    ///
+    /// The bytes must come from a trusted call to [`compiled_templates`](Self::compiled_templates)
+    /// or [`compile_templates`](crate::compile_templates). Their checksum only catches accidental
+    /// corruption: the instructions themselves are not validated and crafted bytes can make
+    /// rendering panic.
+    pub fn add_compiled_templates(&mut self, bytes: &[u8]) -> TeraResult<()> {
+        let templates = bundle::decode_templates(bytes)?;
+        let mut inserted: Vec<(String, Option<Arc<Template>>)> =
+            Vec::with_capacity(templates.len());
+        for template in templates {
+            let key = template.name.clone();
+            let previous = self.templates.insert(key.clone(), Arc::new(template));
+            inserted.push((key, previous));
+        }
+
+        let result = self
+            .load_missing_dependencies(&mut inserted)
+            .and_then(|_| self.finalize_templates());
+
+        if result.is_err() {
+            for (key, previous) in inserted.into_iter().rev() {
+                match previous {
+                    Some(old) => {
+                        self.templates.insert(key, old);
+                    }
+                    None => {
+                        self.templates.remove(&key);
+                    }
+                }
+            }
+        }
+        result
+    }
+
     /// Add a template from a path: reads the file and parses it.
     /// This will return an error if the template is invalid and doesn't check the validity of
     /// the new set of templates.
@@ -806,7 +1626,7 @@ impl Tera {
         &mut self,
         path: P,
         name: Option<&str>,
-    ) -> TeraResult<(String, Option<Template>)> {
+    ) -> TeraResult<(String, Option<Arc<Template>>)> {
         let path = path.as_ref();
         let path_str = path.to_str().ok_or_else(|| {
             Error::message(format!("Template path is not valid UTF-8: {:?}", path))
@@ -828,7 +1648,7 @@ impl Tera {
         )?;
 
         let key = tpl_name.to_string();
-        let previous = self.templates.insert(key.clone(), template);
+        let previous = self.templates.insert(key.clone(), Arc::new(template));
         Ok((key, previous))
     }
 
@@ -877,12 +1697,13 @@ impl Tera {
         P: AsRef<Path>,
         N: AsRef<str>,
     {
-        let mut inserted: Vec<(String, Option<Template>)> = Vec::new();
+        let mut inserted: Vec<(String, Option<Arc<Template>>)> = Vec::new();
         let result = (|| -> TeraResult<()> {
             for (path, name) in files {
                 let (key, previous) = self.add_file(path, name.as_ref().map(AsRef::as_ref))?;
                 inserted.push((key, previous));
             }
+            self.load_missing_dependencies(&mut inserted)?;
             self.finalize_templates()
         })();
 
@@ -928,6 +1749,7 @@ impl Tera {
             ));
         }
         self.fallback_prefixes = prefixes.into_iter().map(Into::into).collect();
+        self.clear_lazy_templates();
         Ok(())
     }
 
@@ -942,36 +1764,106 @@ impl Tera {
         0
     }
 
-    /// Resolves a template name, trying exact match first, then fallback prefixes.
-    /// Returns the actual template name if found, or None.
-    pub(crate) fn resolve_template_name(&self, name: &str) -> Option<&str> {
-        if let Some((resolved, _)) = self.templates.get_key_value(name) {
-            return Some(resolved.as_str());
-        }
-        for prefix in &self.fallback_prefixes {
-            let prefixed = format!("{}{}", prefix, name);
-            if let Some((resolved, _)) = self.templates.get_key_value(&prefixed) {
-                return Some(resolved.as_str());
-            }
-        }
-        None
+    /// Set the loader Tera will ask for templates it doesn't know about.
+    ///
+    /// Templates requested by name (via render, extends, or include) that were not added are
+    /// loaded on demand from it, along with any of their parents and includes that are missing,
+    /// and are then checked like any other template. Templates added with
+    /// [`add_raw_templates`](Self::add_raw_templates) and friends can also extend or include
+    /// templates from the loader.
+    ///
+    /// Templates defining components cannot be loaded on demand since all components need to be
+    /// known upfront: add them to Tera directly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tera::{Tera, Context, MemoryLoader};
+    /// let mut tera = Tera::default();
+    /// tera.set_loader(MemoryLoader::from_iter([
+    ///     ("base.html", "<title>{% block title %}{% endblock %}</title>"),
+    ///     ("index.html", "{% extends 'base.html' %}{% block title %}Home{% endblock %}"),
+    /// ]));
+    /// let out = tera.render("index.html", &Context::new()).unwrap();
+    /// assert_eq!(out, "<title>Home</title>");
+    /// ```
+    #[cfg(feature = "loader")]
+    pub fn set_loader(&mut self, loader: impl Loader + 'static) {
+        self.loader = Some(Arc::new(loader));
+        self.clear_lazy_templates();
+    }
+
+    /// Set the resource limits used for every render of this instance.
+    ///
+    /// Use [`render_with_limits`](Self::render_with_limits) to use different limits for a
+    /// single render, eg for templates coming from untrusted users.
+    /// Going over a limit will return an error of kind
+    /// [`ErrorKind::ResourceLimitExceeded`](crate::ErrorKind::ResourceLimitExceeded).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tera::{Tera, Context, RenderLimits};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("loop", "{% for i in range(end=1000) %}{{ i }}{% endfor %}").unwrap();
+    /// tera.set_render_limits(RenderLimits {
+    ///     max_output_bytes: Some(100),
+    ///     ..RenderLimits::default()
+    /// });
+    /// assert!(tera.render("loop", &Context::new()).is_err());
+    /// ```
+    pub fn set_render_limits(&mut self, limits: RenderLimits) {
+        self.render_limits = limits;
+    }
+
+    /// Returns the resource limits used for every render of this instance.
+    pub fn render_limits(&self) -> &RenderLimits {
+        &self.render_limits
+    }
+
+    /// The names to try for a template: the exact name first, then with each fallback prefix.
+    fn candidate_names<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Cow<'a, str>> {
+        std::iter::once(Cow::Borrowed(name)).chain(
+            self.fallback_prefixes
+                .iter()
+                .map(move |prefix| Cow::Owned(format!("{prefix}{name}"))),
+        )
+    }
+
+    /// Finds a template with `find`, trying exact match first, then fallback prefixes.
+    fn find_with_prefixes<'a>(
+        &self,
+        name: &str,
+        find: impl Fn(&str) -> Option<&'a Template>,
+    ) -> Option<&'a Template> {
+        self.candidate_names(name).find_map(|n| find(&n))
     }
 
     /// Get a template by name, resolving fallback prefixes if needed.
+    /// This doesn't ask the loader for templates.
     pub(crate) fn get_template(&self, template_name: &str) -> Option<&Template> {
-        self.resolve_template_name(template_name)
-            .map(|resolved| &self.templates[resolved])
+        self.find_with_prefixes(template_name, |n| {
+            self.templates
+                .get(n)
+                .map(AsRef::as_ref)
+                .or_else(|| self.lazy_template(n))
+        })
     }
 
     /// Lookups a template by name, resolving fallback prefixes if needed, returning whether it's
-    /// found or not
+    /// found or not.
+    ///
+    /// If a loader is set, it will be asked for the template if it's not loaded yet: templates
+    /// failing to load are considered as not found.
     pub fn contains_template(&self, template_name: &str) -> bool {
-        self.resolve_template_name(template_name).is_some()
+        self.must_get_template(template_name).is_ok()
     }
 
     /// Returns an iterator over the names of all registered templates in an
     /// unspecified order.
     ///
+    /// This includes the templates loaded on demand from the loader so far.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -987,13 +1879,52 @@ impl Tera {
     /// assert!(names.contains(&"another-one.html"));
     /// ```
     pub fn get_template_names(&self) -> impl Iterator<Item = &str> {
-        self.templates.keys().map(|s| s.as_str())
+        self.templates
+            .keys()
+            .map(|s| s.as_str())
+            .chain(self.lazy_template_names())
+    }
+
+    /// Returns an iterator over the names of all registered filters, including the built-in
+    /// ones, in an unspecified order.
+    pub fn get_filter_names(&self) -> impl Iterator<Item = &str> {
+        self.filters.keys().map(|s| s.as_ref())
+    }
+
+    /// Returns an iterator over the names of all registered tests, including the built-in
+    /// ones, in an unspecified order.
+    pub fn get_test_names(&self) -> impl Iterator<Item = &str> {
+        self.tests.keys().map(|s| s.as_ref())
+    }
+
+    /// Returns an iterator over the names of all registered functions, including the built-in
+    /// ones, in an unspecified order.
+    pub fn get_function_names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(|s| s.as_ref())
+    }
+
+    /// Returns the first of the given templates that exists, asking the loader for the ones not
+    /// loaded yet.
+    pub(crate) fn find_first_template(&self, names: &[&str]) -> TeraResult<Option<&Template>> {
+        for name in names {
+            if let Some(tpl) = self.get_template(name) {
+                return Ok(Some(tpl));
+            }
+            if let Some(tpl) = self.load_template(name)? {
+                return Ok(Some(tpl));
+            }
+        }
+        Ok(None)
     }
 
-    /// Get a template by name, returning an error if not found. Used internally.
+    /// Get a template by name, asking the loader for it if needed and returning an error if
+    /// not found. Used internally.
     #[inline]
     pub(crate) fn must_get_template(&self, template_name: &str) -> TeraResult<&Template> {
-        self.get_template(template_name)
+        if let Some(tpl) = self.get_template(template_name) {
+            return Ok(tpl);
+        }
+        self.load_template(template_name)?
             .ok_or_else(|| Error::template_not_found(template_name))
     }
 
@@ -1031,11 +1962,69 @@ impl Tera {
     /// assert_eq!(output, "<h1>Hello</h1>");
     /// ```
     pub fn render(&self, template_name: &str, context: &Context) -> TeraResult<String> {
+        self.render_with_limits(template_name, context, &self.render_limits)
+    }
+
+    /// Renders a Tera template given a [`Context`], using the given [`RenderLimits`] instead
+    /// of the ones set with [`set_render_limits`](Self::set_render_limits).
+    ///
+    /// ```
+    /// # use tera::{Tera, Context, ErrorKind, RenderLimits, ResourceLimit};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("list", "{{ range(end=1000) | length }}").unwrap();
+    ///
+    /// let limits = RenderLimits { max_list_length: Some(100), ..RenderLimits::default() };
+    /// let err = tera.render_with_limits("list", &Context::new(), &limits).unwrap_err();
+    /// assert!(matches!(
+    ///     err.kind(),
+    ///     ErrorKind::ResourceLimitExceeded { limit: ResourceLimit::ListLength, .. }
+    /// ));
+    /// ```
+    pub fn render_with_limits(
+        &self,
+        template_name: &str,
+        context: &Context,
+        limits: &RenderLimits,
+    ) -> TeraResult<String> {
         let template = self.must_get_template(template_name)?;
-        let mut vm = VirtualMachine::new(self, template);
+        let budget = Budget::new(*limits);
+        let mut vm = VirtualMachine::new(self, template, &budget);
         vm.render(context, &self.global_context)
     }
 
+    /// Renders a Tera template given a [`Context`], also returning the components it rendered.
+    ///
+    /// The components, including the ones rendered by other components, are listed once in the
+    /// order they were first rendered. Their metadata can be used to only load the assets a page
+    /// needs for example.
+    ///
+    /// ```
+    /// # use tera::{Tera, Context};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("components.html", r#"{% component icon() {"css": "icon.css"} %}*{% endcomponent icon %}
+    /// {% component button() {"css": "button.css"} %}<button>{{ <icon /> }}</button>{% endcomponent button %}"#),
+    ///     ("page.html", "{{ <button /> }}{{ <button /> }}"),
+    /// ]).unwrap();
+    ///
+    /// let (output, components) = tera.render_with_components("page.html", &Context::new()).unwrap();
+    /// assert_eq!(output, "<button>*</button><button>*</button>");
+    /// let css: Vec<_> = components.iter().map(|c| c.metadata()["css"].as_str().unwrap()).collect();
+    /// assert_eq!(css, ["button.css", "icon.css"]);
+    /// ```
+    pub fn render_with_components(
+        &self,
+        template_name: &str,
+        context: &Context,
+    ) -> TeraResult<(String, Vec<ComponentInfo>)> {
+        let template = self.must_get_template(template_name)?;
+        let budget = Budget::new(self.render_limits);
+        let components = RefCell::new(Vec::new());
+        let mut vm = VirtualMachine::new(self, template, &budget).record_components(&components);
+        let output = vm.render(context, &self.global_context)?;
+        Ok((output, components.into_inner()))
+    }
+
     /// Renders a Tera template given a [`Context`] to something that implements [`Write`].
     ///
     /// The only difference from [`render()`](Self::render) is that this version doesn't convert
@@ -1065,9 +2054,24 @@ impl Tera {
         template_name: &str,
         context: &Context,
         write: impl Write,
+    ) -> TeraResult<()> {
+        self.render_to_with_limits(template_name, context, &self.render_limits, write)
+    }
+
+    /// Renders a Tera template given a [`Context`] to something that implements [`Write`],
+    /// using the given [`RenderLimits`].
+    ///
+    /// See [`render_with_limits`](Self::render_with_limits).
+    pub fn render_to_with_limits(
+        &self,
+        template_name: &str,
+        context: &Context,
+        limits: &RenderLimits,
+        write: impl Write,
     ) -> TeraResult<()> {
         let template = self.must_get_template(template_name)?;
-        let mut vm = VirtualMachine::new(self, template);
+        let budget = Budget::new(*limits);
+        let mut vm = VirtualMachine::new(self, template, &budget);
         vm.render_to(None, context, &self.global_context, write)
     }
 
@@ -1077,6 +2081,7 @@ impl Tera {
     /// which is useful for sharing common data.
     ///
     /// The global context is *not* passed if you call `render_component`.
+    /// Components only see it in the default values of their arguments.
     ///
     /// ```
     /// # use tera::{Tera, Context, context};
@@ -1141,7 +2146,7 @@ impl Tera {
         write: impl Write,
     ) -> TeraResult<()> {
         let mut template =
-            Template::new(ONE_OFF_TEMPLATE_NAME, input, None, self.delimiters.clone())?;
+            Template::new_one_off(ONE_OFF_TEMPLATE_NAME, input, self.delimiters.clone())?;
 
         if template.extends.is_some() {
             return Err(Error::message(
@@ -1154,16 +2159,31 @@ impl Tera {
 
         template.autoescape_enabled = autoescape;
 
+        // Make sure the included templates are loaded before checking they exist
+        for include_name in template.included_names() {
+            if self.get_template(include_name).is_none() {
+                self.load_template(include_name)?;
+            }
+        }
+
         // Validate template references
-        let errors = self.validate_template_references(&template, |c| {
-            self.components.contains_key(c) || template.components.contains_key(c)
-        });
+        let errors = self.validate_template_references(
+            &template,
+            &[],
+            |c| {
+                self.components
+                    .get(c)
+                    .or_else(|| template.components.get(c))
+                    .map(|(def, _)| def)
+            },
+            |t| self.get_template(t).is_some(),
+        );
         if !errors.is_empty() {
-            let reports: Vec<String> = errors.into_iter().map(|(_, report)| report).collect();
-            return Err(Error::message(reports.join("\n\n")));
+            return Err(Error::invalid_templates(errors));
         }
 
-        let mut vm = VirtualMachine::new(self, &template);
+        let budget = Budget::new(self.render_limits);
+        let mut vm = VirtualMachine::new(self, &template, &budget);
         vm.render_to(None, context, &self.global_context, write)
     }
 
@@ -1262,7 +2282,7 @@ impl Tera {
         context: &Context,
         body: Option<&str>,
         autoescape: bool,
-        mut write: impl Write,
+        write: impl Write,
     ) -> TeraResult<()> {
         let (component_def, chunk) = self
             .components
@@ -1285,10 +2305,14 @@ impl Tera {
             )
             .map_err(Error::message)?;
 
-        let vm = VirtualMachine::new_with_autoescape(self, template, autoescape);
+        let budget = Budget::new(self.render_limits);
+        let vm = VirtualMachine::new_with_autoescape(self, template, autoescape, &budget);
         let mut state = State::new_with_chunk(&component_context, chunk);
         state.filters = Some(&self.filters);
-        vm.interpret(&mut state, &mut write)?;
+        state.tests = Some(&self.tests);
+        state.vm = Some(vm);
+        state.component_global_context = Some(&self.global_context);
+        vm.interpret(&mut state, &mut CountingWriter::new(write, &budget))?;
 
         Ok(())
     }
@@ -1319,7 +2343,8 @@ impl Tera {
                 "Block `{block_name}` not found in template `{template_name}`",
             )));
         }
-        let mut vm = VirtualMachine::new(self, template);
+        let budget = Budget::new(self.render_limits);
+        let mut vm = VirtualMachine::new(self, template, &budget);
         vm.render_block(block_name, context, &self.global_context)
     }
 
@@ -1349,7 +2374,8 @@ impl Tera {
                 "Block `{block_name}` not found in template `{template_name}`",
             )));
         }
-        let mut vm = VirtualMachine::new(self, template);
+        let budget = Budget::new(self.render_limits);
+        let mut vm = VirtualMachine::new(self, template, &budget);
         vm.render_to(Some(block_name), context, &self.global_context, write)
     }
 }
@@ -1370,8 +2396,14 @@ impl Default for Tera {
             tests: HashMap::new(),
             functions: HashMap::new(),
             components: HashMap::new(),
+            component_sources: HashMap::new(),
             delimiters: Delimiters::default(),
             fallback_prefixes: Vec::new(),
+            #[cfg(feature = "loader")]
+            loader: None,
+            #[cfg(feature = "loader")]
+            lazy_templates: LazyTemplates::default(),
+            render_limits: RenderLimits::default(),
         };
         tera.register_builtin_filters();
         tera.register_builtin_tests();
@@ -1382,8 +2414,8 @@ impl Default for Tera {
 
 impl fmt::Debug for Tera {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Tera")
-            .field("glob", &self.glob)
+        let mut s = f.debug_struct("Tera");
+        s.field("glob", &self.glob)
             .field("templates", &self.templates.len())
             .field("autoescape_suffixes", &self.autoescape_suffixes)
             .field("filters", &self.filters.len())
@@ -1391,13 +2423,16 @@ impl fmt::Debug for Tera {
             .field("functions", &self.functions.len())
             .field("components", &self.components.len())
             .field("delimiters", &self.delimiters)
-            .finish_non_exhaustive()
+            .field("render_limits", &self.render_limits);
+        #[cfg(feature = "loader")]
+        s.field("loader", &self.loader.is_some());
+        s.finish_non_exhaustive()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Kwargs, context};
+    use crate::{ErrorKind, Kwargs, ResourceLimit, context};
 
     use super::*;
 
@@ -1419,6 +2454,17 @@ mod tests {
             )
             .unwrap();
         assert_eq!(content2, "UserID: 7489, Username: John Doe");
+
+        tera.add_raw_template("greeting.html", "{{ greeting }}, {{ name }}!")
+            .unwrap();
+        let content3 = tera
+            .render_str(
+                r#"{% include "greeting.html" with {"greeting": "Hi"} only %}"#,
+                &context! { greeting => "Hello" },
+                false,
+            )
+            .unwrap();
+        assert_eq!(content3, "Hi, John Doe!");
     }
 
     #[cfg(feature = "glob_fs")]
@@ -1432,32 +2478,139 @@ mod tests {
         assert!(tera.get_template("base.html").is_some());
     }
 
-    #[cfg(feature = "glob_fs")]
     #[test]
-    fn error_on_malformed_template_in_glob() {
+    fn can_reload_paths() {
         let dir = tempfile::tempdir().unwrap();
-        std::fs::write(dir.path().join("bad.html"), "{% if foo %}oops").unwrap();
-        let glob = dir.path().join("**/*").to_string_lossy().to_string();
+        let base_path = dir.path().join("base.html");
+        let page_path = dir.path().join("page.html");
+        std::fs::write(&base_path, "[{% block content %}{% endblock %}]").unwrap();
+        std::fs::write(
+            &page_path,
+            "{% extends 'base.html' %}{% block content %}Hi{% endblock %}",
+        )
+        .unwrap();
+
         let mut tera = Tera::default();
-        let result = tera.load_from_glob(&glob);
-        assert!(result.is_err());
+        tera.add_template_files(vec![
+            (&base_path, Some("base.html")),
+            (&page_path, Some("page.html")),
+        ])
+        .unwrap();
+        tera.add_raw_template("raw.html", "{% include 'base.html' %}")
+            .unwrap();
+        assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "[Hi]");
+
+        std::fs::write(&base_path, "({% block content %}{% endblock %})").unwrap();
+        tera.reload_paths(&[&base_path]).unwrap();
+        assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "(Hi)");
+        assert_eq!(tera.render("raw.html", &Context::new()).unwrap(), "()");
+
+        // An invalid change doesn't change anything
+        std::fs::write(&base_path, "{% block other %}{% endblock %}").unwrap();
+        assert!(tera.reload_paths(&[&base_path]).is_err());
+        assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "(Hi)");
+
+        // Unknown paths are ignored
+        tera.reload_paths(&[dir.path().join("unknown.html")])
+            .unwrap();
     }
 
-    #[cfg(feature = "glob_fs")]
     #[test]
-    fn failed_full_reload_preserves_existing_templates() {
+    fn reloading_a_clone_only_copies_the_changed_templates() {
         let dir = tempfile::tempdir().unwrap();
-        let good_path = dir.path().join("good.html");
-        std::fs::write(&good_path, "Hello {{ name }}").unwrap();
-        let glob = dir.path().join("**/*").to_string_lossy().to_string();
-
+        let path = dir.path().join("hello.html");
+        std::fs::write(&path, "Hello").unwrap();
         let mut tera = Tera::default();
-        tera.load_from_glob(&glob).unwrap();
-        let mut ctx = Context::new();
-        ctx.insert("name", &"world");
-        assert_eq!(tera.render("good.html", &ctx).unwrap(), "Hello world");
+        tera.add_template_files(vec![(&path, Some("hello.html"))])
+            .unwrap();
+        tera.add_raw_template("other.html", "Other").unwrap();
 
-        std::fs::write(&good_path, "{% if x %}oops").unwrap();
+        let mut reloaded = tera.clone();
+        std::fs::write(&path, "Bonjour").unwrap();
+        reloaded.reload_paths(&[&path]).unwrap();
+        assert_eq!(tera.render("hello.html", &Context::new()).unwrap(), "Hello");
+        assert_eq!(
+            reloaded.render("hello.html", &Context::new()).unwrap(),
+            "Bonjour"
+        );
+        assert!(Arc::ptr_eq(
+            &tera.templates["other.html"],
+            &reloaded.templates["other.html"]
+        ));
+    }
+
+    #[cfg(feature = "glob_fs")]
+    #[test]
+    fn can_reload_paths_from_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("base.html");
+        let nav_path = dir.path().join("nav.html");
+        let new_path = dir.path().join("new.html");
+        std::fs::write(&base_path, "{% include 'nav.html' %}").unwrap();
+        std::fs::write(&nav_path, "nav").unwrap();
+        let glob = dir.path().join("**/*.html").to_string_lossy().to_string();
+
+        let mut tera = Tera::default();
+        tera.load_from_glob(&glob).unwrap();
+
+        // Removing an included template errors and keeps it
+        std::fs::remove_file(&nav_path).unwrap();
+        assert!(tera.reload_paths(&[&nav_path]).is_err());
+        assert_eq!(tera.render("base.html", &Context::new()).unwrap(), "nav");
+
+        std::fs::write(&base_path, "{% include 'new.html' %}").unwrap();
+        std::fs::write(&new_path, "new").unwrap();
+        tera.reload_paths(&[
+            &base_path,
+            &nav_path,
+            &new_path,
+            &dir.path().join("other.txt"),
+        ])
+        .unwrap();
+        assert_eq!(tera.render("base.html", &Context::new()).unwrap(), "new");
+        assert!(tera.get_template("nav.html").is_none());
+        assert!(tera.templates["new.html"].from_glob);
+
+        // It still works with a full reload
+        tera.full_reload().unwrap();
+        assert_eq!(tera.render("base.html", &Context::new()).unwrap(), "new");
+    }
+
+    #[cfg(feature = "glob_fs")]
+    #[test]
+    fn error_on_malformed_template_in_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("bad.html"), "{% if foo %}oops").unwrap();
+        std::fs::write(dir.path().join("worse.html"), "{{ foo").unwrap();
+        std::fs::write(dir.path().join("good.html"), "{{ foo }}").unwrap();
+        let glob = dir.path().join("**/*").to_string_lossy().to_string();
+        let mut tera = Tera::default();
+        let err = tera.load_from_glob(&glob).unwrap_err();
+        match err.kind() {
+            ErrorKind::InvalidTemplates(reports) => {
+                let names: Vec<_> = reports.iter().map(|r| r.filename.as_str()).collect();
+                assert_eq!(names, ["bad.html", "worse.html"]);
+            }
+            kind => panic!("unexpected error: {kind:?}"),
+        }
+        assert!(tera.get_template("good.html").is_none());
+    }
+
+    #[cfg(feature = "glob_fs")]
+    #[test]
+    fn failed_full_reload_preserves_existing_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let good_path = dir.path().join("good.html");
+        std::fs::write(&good_path, "Hello {{ name }}").unwrap();
+        let glob = dir.path().join("**/*").to_string_lossy().to_string();
+
+        let mut tera = Tera::default();
+        tera.load_from_glob(&glob).unwrap();
+        let mut ctx = Context::new();
+        ctx.insert("name", &"world");
+        assert_eq!(tera.render("good.html", &ctx).unwrap(), "Hello world");
+
+        std::fs::write(&good_path, "{% if x %}oops").unwrap();
         let result = tera.full_reload();
         assert!(result.is_err());
         assert_eq!(tera.render("good.html", &ctx).unwrap(), "Hello world");
@@ -1515,6 +2668,111 @@ mod tests {
         assert_eq!(out, "\u{FFFD}\u{FFFD}\u{FFFD}");
     }
 
+    #[test]
+    fn component_default_expressions_see_globals() {
+        let mut tera = Tera::default();
+        tera.global_context()
+            .insert("theme", &HashMap::from([("size", "big")]));
+        tera.add_raw_templates(vec![
+            (
+                "components.html",
+                r#"{% component Button(label, size = theme.size, id = size ~ "-" ~ label) %}<button id="{{ id }}" class="{{ size }}">{{ label }}</button>{% endcomponent Button %}"#,
+            ),
+            (
+                "page.html",
+                r#"{{ <Button label="ok" /> }}{{ <Button label="ok" size="small" /> }}"#,
+            ),
+        ])
+        .unwrap();
+
+        insta::assert_snapshot!(
+            tera.render("page.html", &Context::new()).unwrap(),
+            @r#"<button id="big-ok" class="big">ok</button><button id="small-ok" class="small">ok</button>"#
+        );
+        insta::assert_snapshot!(
+            tera.render_component("Button", &context! { label => "go" }, None, true).unwrap(),
+            @r#"<button id="big-go" class="big">go</button>"#
+        );
+    }
+
+    #[test]
+    fn component_body_does_not_see_globals() {
+        let mut tera = Tera::default();
+        tera.global_context().insert("theme", "dark");
+        tera.add_raw_templates(vec![
+            (
+                "components.html",
+                r#"{% component Theme(name = theme) %}{{ name }}:{{ theme is defined }}{% endcomponent Theme %}"#,
+            ),
+            ("page.html", r#"{{ theme }} {{ <Theme /> }}"#),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            tera.render("page.html", &Context::new()).unwrap(),
+            "dark dark:false"
+        );
+        assert_eq!(
+            tera.render_component("Theme", &Context::new(), None, false)
+                .unwrap(),
+            "dark:false"
+        );
+    }
+
+    #[test]
+    fn render_with_components_includes_blocks_and_includes() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "components.html",
+                r#"{% component nav() {"js": "nav.js"} %}nav{% endcomponent nav %}
+{% component footer() %}footer{% endcomponent footer %}
+{% component unused() %}{% endcomponent unused %}"#,
+            ),
+            ("footer.html", "{{ <footer /> }}"),
+            (
+                "base.html",
+                r#"{% block nav %}{% endblock %}{% include "footer.html" %}"#,
+            ),
+            (
+                "page.html",
+                r#"{% extends "base.html" %}{% block nav %}{{ <nav /> }}{% endblock %}"#,
+            ),
+        ])
+        .unwrap();
+
+        let (output, components) = tera
+            .render_with_components("page.html", &Context::new())
+            .unwrap();
+        assert_eq!(output, "navfooter");
+        let names: Vec<_> = components.iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["nav", "footer"]);
+        assert_eq!(components[0].metadata()["js"], Value::from("nav.js"));
+    }
+
+    #[test]
+    fn render_with_components_skips_calls_with_invalid_arguments() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "components.html",
+                "{% component card(title: string) %}{{ title }}{% endcomponent card %}",
+            ),
+            ("page.html", "{{ <card title={n} /> }}"),
+        ])
+        .unwrap();
+
+        let template = tera.get_template("page.html").unwrap();
+        let budget = Budget::new(tera.render_limits);
+        let components = RefCell::new(Vec::new());
+        let mut vm = VirtualMachine::new(&tera, template, &budget).record_components(&components);
+        let err = vm
+            .render(&Context::new(), &tera.global_context)
+            .unwrap_err();
+        assert!(err.to_string().contains("title"), "{err}");
+        assert!(components.borrow().is_empty());
+    }
+
     #[test]
     fn test_render_component() {
         let mut tera = Tera::default();
@@ -1733,6 +2991,99 @@ mod tests {
         assert_eq!(output.trim(), "user");
     }
 
+    #[test]
+    fn component_calls_are_checked_when_loading() {
+        let components = (
+            "components.html",
+            "{% component card(title: string, level: integer=1, ...rest) %}{{ title }}{% endcomponent %}",
+        );
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            components,
+            (
+                "ok.html",
+                "{{<card title={name} />}}{{<card title='a' class='big' />}}{% set props = {} %}{{<card level={2} {...props} />}}{{<card title={1} level={'2'} {...props} />}}",
+            ),
+        ])
+        .unwrap();
+
+        let err = tera
+            .add_raw_templates(vec![
+                components,
+                ("bad.html", "{{<card />}}{{<card title='a' level={'2'} />}}"),
+            ])
+            .unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| d.code() == "invalid-component-call")
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .any(|d| d.message().starts_with("Component argument `level`"))
+        );
+    }
+
+    #[test]
+    fn template_dependency_graph() {
+        let mut tera = Tera::default();
+        tera.set_fallback_prefixes(vec!["partials/".to_string()])
+            .unwrap();
+        tera.add_raw_templates(vec![
+            (
+                "base.html",
+                "{% include 'nav.html' %}{% block content %}{% endblock %}",
+            ),
+            ("partials/nav.html", "{% include 'partials/links.html' %}"),
+            ("partials/links.html", "{{<ui.link />}}"),
+            (
+                "components.html",
+                "{% component ui.link() %}{{<ui.icon />}}{% endcomponent %}{% component ui.icon() %}{% endcomponent %}",
+            ),
+            ("page.html", "{% extends 'base.html' %}"),
+            ("about.html", "{{<ui.icon />}}"),
+        ])
+        .unwrap();
+
+        fn sorted(names: HashSet<&str>) -> Vec<&str> {
+            let mut names: Vec<_> = names.into_iter().collect();
+            names.sort();
+            names
+        }
+        assert_eq!(
+            sorted(tera.get_template_dependencies("page.html").unwrap()),
+            vec![
+                "base.html",
+                "components.html",
+                "partials/links.html",
+                "partials/nav.html"
+            ]
+        );
+        assert!(
+            tera.get_template_dependencies("components.html")
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            sorted(tera.get_template_dependents("nav.html").unwrap()),
+            vec!["base.html", "page.html"]
+        );
+        assert_eq!(
+            sorted(tera.get_template_dependents("components.html").unwrap()),
+            vec![
+                "about.html",
+                "base.html",
+                "page.html",
+                "partials/links.html",
+                "partials/nav.html"
+            ]
+        );
+        assert!(tera.get_template_dependents("missing.html").is_err());
+    }
+
     #[test]
     fn test_fallback_template_resolution() {
         let mut tera = Tera::default();
@@ -1842,4 +3193,427 @@ mod tests {
             .unwrap();
         assert_eq!(outer, "<o>inner</o>");
     }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn loader_templates_are_loaded_on_demand() {
+        let mut tera = Tera::default();
+        tera.set_loader(crate::MemoryLoader::from_iter([
+            ("base.html", "[{% block content %}base{% endblock %}]"),
+            ("partial.html", "partial"),
+            (
+                "child.html",
+                "{% extends 'base.html' %}{% block content %}{{ super() }}-{% include 'partial.html' %}{% endblock %}",
+            ),
+            ("unused.html", "unused"),
+        ]));
+        assert_eq!(tera.get_template_names().count(), 0);
+
+        let result = tera.render("child.html", &Context::new()).unwrap();
+        assert_eq!(result, "[base-partial]");
+        let mut names: Vec<_> = tera.get_template_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["base.html", "child.html", "partial.html"]);
+
+        assert!(tera.contains_template("unused.html"));
+        assert!(!tera.contains_template("missing.html"));
+        let err = tera.render("missing.html", &Context::new()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::TemplateNotFound(_)));
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn dynamic_includes_use_loader_templates() {
+        let mut tera = Tera::default();
+        tera.set_loader(crate::MemoryLoader::from_iter([
+            ("partials/default.html", "default"),
+            ("partials/Bob.html", "Bob"),
+        ]));
+        tera.add_raw_template(
+            "tpl.html",
+            "{% include ['partials/' ~ name ~ '.html', 'partials/default.html'] %}{% include 'missing.html' ignore missing %}",
+        )
+        .unwrap();
+
+        let mut context = Context::new();
+        context.insert("name", "Bob");
+        assert_eq!(tera.render("tpl.html", &context).unwrap(), "Bob");
+        context.insert("name", "Alice");
+        assert_eq!(tera.render("tpl.html", &context).unwrap(), "default");
+
+        tera.add_raw_template("missing.html", "{% include 'partials/' ~ name %}")
+            .unwrap();
+        let err = tera.render("missing.html", &context).unwrap_err();
+        assert_eq!(err.diagnostics()[0].code(), "template-not-found");
+        assert_eq!(err.diagnostics()[0].span().unwrap().range, 11..29);
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn added_templates_can_use_loader_templates() {
+        let mut tera = Tera::default();
+        tera.set_loader(crate::MemoryLoader::from_iter([(
+            "base.html",
+            "<{% block content %}{% endblock %}>",
+        )]));
+        tera.add_raw_template(
+            "child.html",
+            "{% extends 'base.html' %}{% block content %}child{% endblock %}",
+        )
+        .unwrap();
+
+        assert!(tera.templates.contains_key("base.html"));
+        let result = tera.render("child.html", &Context::new()).unwrap();
+        assert_eq!(result, "<child>");
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn loader_templates_are_validated() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "components.html",
+            "{% component Button(label) %}{{ label }}{% endcomponent Button %}",
+        )
+        .unwrap();
+        tera.set_loader(crate::MemoryLoader::from_iter([
+            ("ok.html", "{{ <Button label=\"hi\" /> }}"),
+            ("unknown_filter.html", "{{ name | nope }}"),
+            ("syntax.html", "{% if %}"),
+            ("missing_parent.html", "{% extends 'nope.html' %}"),
+            (
+                "with_component.html",
+                "{% component Other() %}{% endcomponent Other %}",
+            ),
+        ]));
+
+        assert_eq!(tera.render("ok.html", &Context::new()).unwrap(), "hi");
+
+        let err = tera
+            .render("unknown_filter.html", &Context::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown filter `nope`"));
+        let err = tera.render("syntax.html", &Context::new()).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::SyntaxError(_)));
+        let err = tera
+            .render("missing_parent.html", &Context::new())
+            .unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingParent { .. }));
+        let err = tera
+            .render("with_component.html", &Context::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("defines components"));
+
+        // Failed templates are not kept around
+        let names: Vec<_> = tera.get_template_names().collect();
+        assert_eq!(names.len(), 2);
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn loader_uses_fallback_prefixes() {
+        let mut tera = Tera::default();
+        tera.set_fallback_prefixes(["themes/cool/"]).unwrap();
+        tera.set_loader(crate::MemoryLoader::from_iter([
+            ("themes/cool/base.html", "base:{% include 'partial.html' %}"),
+            ("themes/cool/partial.html", "partial"),
+        ]));
+
+        let result = tera.render("base.html", &Context::new()).unwrap();
+        assert_eq!(result, "base:partial");
+        assert!(tera.get_template("themes/cool/partial.html").is_some());
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn adding_templates_clears_loaded_templates() {
+        let mut tera = Tera::default();
+        tera.set_loader(crate::MemoryLoader::from_iter([("a.html", "a")]));
+        tera.render("a.html", &Context::new()).unwrap();
+        assert!(tera.get_template("a.html").is_some());
+
+        tera.add_raw_template("a.html", "overridden").unwrap();
+        let result = tera.render("a.html", &Context::new()).unwrap();
+        assert_eq!(result, "overridden");
+        assert_eq!(tera.get_template_names().count(), 1);
+    }
+
+    fn render_limit_error(
+        tera: &Tera,
+        name: &str,
+        limits: RenderLimits,
+    ) -> (ResourceLimit, String) {
+        let err = tera
+            .render_with_limits(name, &Context::new(), &limits)
+            .unwrap_err();
+        match &err.kind {
+            ErrorKind::ResourceLimitExceeded { limit, .. } => (*limit, err.to_string()),
+            _ => panic!("unexpected error: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn render_limits_instructions() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("tpl", "{% for i in range(end=10000) %}{{ i }}{% endfor %}")
+            .unwrap();
+        let limits = RenderLimits {
+            max_instructions: Some(1000),
+            ..RenderLimits::default()
+        };
+        let (limit, msg) = render_limit_error(&tera, "tpl", limits);
+        assert_eq!(limit, ResourceLimit::Instructions);
+        assert!(msg.contains("limit of 1000 executed instructions"));
+        // Without limits it renders fine
+        assert!(tera.render("tpl", &Context::new()).is_ok());
+    }
+
+    #[test]
+    fn render_limits_output_bytes() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("inc", "{{ 'a' ~ 'bcdefghij' }}"),
+            (
+                "tpl",
+                "{% for i in range(end=10) %}{% include 'inc' %}{% endfor %}",
+            ),
+        ])
+        .unwrap();
+        let limits = RenderLimits {
+            max_output_bytes: Some(25),
+            ..RenderLimits::default()
+        };
+        let (limit, msg) = render_limit_error(&tera, "tpl", limits);
+        assert_eq!(limit, ResourceLimit::OutputBytes);
+        assert!(msg.contains("limit of 25 bytes"));
+        assert!(msg.contains("called from"));
+
+        let mut out = Vec::new();
+        assert!(
+            tera.render_to_with_limits("tpl", &Context::new(), &limits, &mut out)
+                .is_err()
+        );
+        assert_eq!(out, b"abcdefghijabcdefghijabcde");
+    }
+
+    #[test]
+    fn render_limits_output_bytes_built_strings() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "concat",
+                "{% set_global s = 'ab' %}{% for i in range(end=100) %}{% set_global s = s ~ s %}{% endfor %}",
+            ),
+            (
+                "capture",
+                "{% set s %}{% for i in range(end=100) %}abcdefghij{% endfor %}{% endset %}",
+            ),
+            ("filter", "{% set s = range(end=100) | join(sep=',') %}"),
+        ])
+        .unwrap();
+        let limits = RenderLimits {
+            max_output_bytes: Some(100),
+            ..RenderLimits::default()
+        };
+        for name in ["concat", "capture", "filter"] {
+            let (limit, msg) = render_limit_error(&tera, name, limits);
+            assert_eq!(limit, ResourceLimit::OutputBytes);
+            assert!(
+                msg.contains("limit of 100 bytes of output"),
+                "{name}: {msg}"
+            );
+        }
+    }
+
+    #[test]
+    fn render_limits_nesting_depth() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("c", "c"),
+            ("b", "{% include 'c' %}"),
+            ("a", "{% block content %}{% include 'b' %}{% endblock %}"),
+        ])
+        .unwrap();
+        // Blocks don't count
+        let mut limits = RenderLimits {
+            max_nesting_depth: Some(2),
+            ..RenderLimits::default()
+        };
+        assert_eq!(
+            tera.render_with_limits("a", &Context::new(), &limits)
+                .unwrap(),
+            "c"
+        );
+        limits.max_nesting_depth = Some(1);
+        let (limit, msg) = render_limit_error(&tera, "a", limits);
+        assert_eq!(limit, ResourceLimit::NestingDepth);
+        assert!(msg.contains("maximum nesting depth of 1"));
+    }
+
+    #[test]
+    fn includes_are_not_limited_by_default() {
+        let mut tera = Tera::default();
+        let mut templates = vec![("t30".to_string(), "end".to_string())];
+        for i in 0..30 {
+            templates.push((format!("t{i}"), format!("{{% include 't{}' %}}", i + 1)));
+        }
+        tera.add_raw_templates(templates).unwrap();
+        assert_eq!(tera.render("t0", &Context::new()).unwrap(), "end");
+    }
+
+    #[test]
+    fn errors_on_dynamic_include_cycles() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("a", "{% set next = 'b' %}{% include next %}"),
+            ("b", "{% include ['missing', 'a'] %}"),
+        ])
+        .unwrap();
+        let err = tera.render("a", &Context::new()).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                r#"Circular include detected for template 'a'. Include chain: `["a", "b", "a"]`"#
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn render_limits_list_length() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("literal", "{% set x = 1 %}{{ [x, x, x, x] | length }}"),
+            ("range", "{{ range(end=100) | length }}"),
+            ("filter", "{{ 'a,b,c,d' | split(pat=',') | length }}"),
+        ])
+        .unwrap();
+        let limits = RenderLimits {
+            max_list_length: Some(3),
+            ..RenderLimits::default()
+        };
+        for name in ["literal", "range", "filter"] {
+            let (limit, _) = render_limit_error(&tera, name, limits);
+            assert_eq!(limit, ResourceLimit::ListLength);
+        }
+    }
+
+    #[test]
+    fn render_limits_recursion_depth() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            (
+                "tree",
+                "{% set t = [[]] %}{% for n in [[t]] recursive %}{{ loop.depth }}{{ loop(n) }}{% endfor %}",
+            ),
+            (
+                "cycle",
+                "{% set tree = [1] %}{% for n in tree recursive %}{{ loop(tree) }}{% endfor %}",
+            ),
+        ])
+        .unwrap();
+        let mut limits = RenderLimits {
+            max_recursion_depth: Some(3),
+            ..RenderLimits::default()
+        };
+        assert_eq!(
+            tera.render_with_limits("tree", &Context::new(), &limits)
+                .unwrap(),
+            "123"
+        );
+        limits.max_recursion_depth = Some(2);
+        let (limit, msg) = render_limit_error(&tera, "tree", limits);
+        assert_eq!(limit, ResourceLimit::RecursionDepth);
+        assert!(msg.contains("maximum recursion depth of 2"));
+
+        limits.max_recursion_depth = Some(10);
+        let (limit, _) = render_limit_error(&tera, "cycle", limits);
+        assert_eq!(limit, ResourceLimit::RecursionDepth);
+
+        // There is a limit by default
+        let err = tera.render("cycle", &Context::new()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::ResourceLimitExceeded {
+                limit: ResourceLimit::RecursionDepth,
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("maximum recursion depth of 20"));
+
+        // But it can be turned off
+        limits.max_recursion_depth = None;
+        assert_eq!(
+            tera.render_with_limits("tree", &Context::new(), &limits)
+                .unwrap(),
+            "123"
+        );
+    }
+
+    #[test]
+    fn render_limits_set_on_tera() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("tpl", "{{ range(end=100) | length }}")
+            .unwrap();
+        tera.set_render_limits(RenderLimits {
+            max_list_length: Some(3),
+            ..RenderLimits::default()
+        });
+        assert!(tera.render("tpl", &Context::new()).is_err());
+        assert!(
+            tera.render_str("{% set x = 1 %}{{ [x, x, x, x] }}", &Context::new(), false)
+                .is_err()
+        );
+        // Explicit limits take precedence over the ones set on Tera
+        let out = tera
+            .render_with_limits("tpl", &Context::new(), &RenderLimits::default())
+            .unwrap();
+        assert_eq!(out, "100");
+    }
+
+    #[test]
+    fn can_load_compiled_templates() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("components.html", "{% component Hi(name) %}Hi {{ name }}{% endcomponent Hi %}"),
+            ("base.html", "<{% block content %}base{% endblock %}>"),
+            (
+                "index.html",
+                "{% extends 'base.html' %}{% block content %}{{ super() }}{{ '&' }}{{ <Hi name='Bob'/> }}{% endblock %}",
+            ),
+        ])
+        .unwrap();
+        let expected = tera.render("index.html", &Context::new()).unwrap();
+        let bytes = tera.compiled_templates();
+
+        let mut loaded = Tera::default();
+        loaded.add_compiled_templates(&bytes).unwrap();
+        assert_eq!(
+            loaded.render("index.html", &Context::new()).unwrap(),
+            expected
+        );
+        assert_eq!(expected, "<base&amp;Hi Bob>");
+        assert_eq!(loaded.compiled_templates(), bytes);
+    }
+
+    #[test]
+    fn compiled_templates_are_validated() {
+        let mut tera = Tera::default();
+        tera.register_filter("double", |x: i64, _: Kwargs, _: &State| x * 2);
+        tera.add_raw_template("tpl", "{{ 2 | double }}").unwrap();
+        let bytes = tera.compiled_templates();
+
+        let mut loaded = Tera::default();
+        loaded.add_raw_template("existing", "hello").unwrap();
+        let err = loaded.add_compiled_templates(&bytes).unwrap_err();
+        assert!(err.to_string().contains("double"));
+        // The instance is left as it was
+        assert_eq!(
+            loaded.get_template_names().collect::<Vec<_>>(),
+            vec!["existing"]
+        );
+
+        loaded.register_filter("double", |x: i64, _: Kwargs, _: &State| x * 2);
+        loaded.add_compiled_templates(&bytes).unwrap();
+        assert_eq!(loaded.render("tpl", &Context::new()).unwrap(), "4");
+    }
 }
@@ -29,6 +29,13 @@ impl TestResult for bool {
 pub trait Test<Arg, Res: TestResult>: Sync + Send + 'static {
     /// The test function type definition
     fn call(&self, value: Arg, kwargs: Kwargs, state: &State) -> Res;
+
+    /// The names of the arguments that can be given by position, in order: `["divisor"]` allows
+    /// `divisible_by(3)` for `divisible_by(divisor=3)`. Defaults to none, all arguments need to be
+    /// given by name.
+    fn positional_args(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 impl<Func, Arg, Res> Test<Arg, Res> for Func
@@ -45,7 +52,18 @@ where
 type TestFunc = dyn Fn(&Value, Kwargs, &State) -> TeraResult<bool> + Sync + Send + 'static;
 
 #[derive(Clone)]
-pub(crate) struct StoredTest(Arc<TestFunc>);
+pub(crate) struct StoredTest {
+    func: Arc<TestFunc>,
+    positional_args: &'static [&'static str],
+}
+
+impl std::fmt::Debug for StoredTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoredTest")
+            .field("positional_args", &self.positional_args)
+            .finish_non_exhaustive()
+    }
+}
 
 impl StoredTest {
     pub fn new<Func, Arg, Res>(f: Func) -> Self
@@ -54,15 +72,27 @@ impl StoredTest {
         Arg: for<'a> ArgFromValue<'a>,
         Res: TestResult,
     {
+        let positional_args = Test::<Arg, Res>::positional_args(&f);
         let closure = move |arg: &Value, kwargs, state: &State| -> TeraResult<bool> {
             f.call(Arg::from_value(arg)?, kwargs, state).into_result()
         };
 
-        StoredTest(Arc::new(closure))
+        StoredTest {
+            func: Arc::new(closure),
+            positional_args,
+        }
     }
 
     pub fn call(&self, arg: &Value, kwargs: Kwargs, state: &State) -> TeraResult<bool> {
-        (self.0)(arg, kwargs, state)
+        (self.func)(arg, kwargs, state)
+    }
+
+    pub fn positional_args(&self) -> &'static [&'static str] {
+        self.positional_args
+    }
+
+    pub(crate) fn set_positional_args(&mut self, params: &'static [&'static str]) {
+        self.positional_args = params;
     }
 }
 
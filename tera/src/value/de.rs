@@ -31,6 +31,7 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
             ValueInner::F64(v) => visitor.visit_f64(v),
             ValueInner::String(v) => visitor.visit_str(v.as_str()),
             ValueInner::Bytes(v) => visitor.visit_bytes(&v),
+            ValueInner::Lambda(_) => visitor.visit_str("<lambda>"),
             ValueInner::Undefined | ValueInner::None => visitor.visit_unit(),
             ValueInner::Array(v) => visitor.visit_seq(de::value::SeqDeserializer::new(
                 v.iter().map(|v| ValueDeserializer::from_value(v.clone())),
@@ -19,6 +19,7 @@ mod utils;
 
 use crate::HashMap;
 use crate::errors::{Error, TeraResult};
+use crate::parsing::Lambda;
 use crate::value::number::Number;
 pub use key::Key;
 
@@ -95,6 +96,9 @@ pub enum ValueKind {
     /// There is no way to create a `Value::Bytes` from inside a template: it's mostly there
     /// for interaction with filters etc
     Bytes,
+    /// A `x => ...` lambda from a template, that filters can call with `State::call_lambda`.
+    /// Rendering one directly is an error, it is shown as `<lambda>` inside other values.
+    Lambda,
 }
 
 /// Smart string with embedded StringKind for memory efficiency.
@@ -197,6 +201,7 @@ pub(crate) enum ValueInner {
     Array(Arc<Vec<Value>>),
     Map(Arc<Map>),
     Bytes(Arc<Vec<u8>>),
+    Lambda(Arc<Lambda>),
 }
 
 /// The Value type that Tera uses internally and that will handle ser/de
@@ -279,6 +284,7 @@ impl PartialEq for Value {
             (ValueInner::Bool(v), ValueInner::Bool(v2)) => v == v2,
             (ValueInner::Array(v), ValueInner::Array(v2)) => v == v2,
             (ValueInner::Bytes(v), ValueInner::Bytes(v2)) => v == v2,
+            (ValueInner::Lambda(v), ValueInner::Lambda(v2)) => Arc::ptr_eq(v, v2),
             // TODO: should string kind be used for partialeq? They might be equal now but
             // different later if one needs to be escape
             (ValueInner::String(v), ValueInner::String(v2)) => v.as_str() == v2.as_str(),
@@ -364,8 +370,9 @@ impl Ord for Value {
                 ValueInner::Array(_) => 3,
                 ValueInner::Map(_) => 4,
                 ValueInner::Bytes(_) => 5,
-                ValueInner::None => 6,
-                ValueInner::Undefined => 7,
+                ValueInner::Lambda(_) => 6,
+                ValueInner::None => 7,
+                ValueInner::Undefined => 8,
             }
         }
         type_order(&self.inner).cmp(&type_order(&other.inner))
@@ -420,6 +427,7 @@ impl Value {
             ValueInner::Array(_) => ValueKind::Array,
             ValueInner::Map(_) => ValueKind::Map,
             ValueInner::Bytes(_) => ValueKind::Bytes,
+            ValueInner::Lambda(_) => ValueKind::Lambda,
         }
     }
 
@@ -472,12 +480,30 @@ impl Value {
     pub fn is_bytes(&self) -> bool {
         matches!(self.kind(), ValueKind::Bytes)
     }
+    #[allow(missing_docs)]
+    pub fn is_lambda(&self) -> bool {
+        matches!(self.kind(), ValueKind::Lambda)
+    }
+
+    pub(crate) fn lambda(lambda: Lambda) -> Self {
+        Value {
+            inner: ValueInner::Lambda(Arc::new(lambda)),
+        }
+    }
+
+    pub(crate) fn as_lambda(&self) -> Option<&Lambda> {
+        match &self.inner {
+            ValueInner::Lambda(l) => Some(l),
+            _ => None,
+        }
+    }
 
     pub(crate) fn format(&self, f: &mut impl std::io::Write) -> std::io::Result<()> {
         match &self.inner {
             ValueInner::None | ValueInner::Undefined => Ok(()),
             ValueInner::Bool(v) => f.write_all(if *v { b"true" } else { b"false" }),
             ValueInner::Bytes(v) => f.write_all(String::from_utf8_lossy(v).as_bytes()),
+            ValueInner::Lambda(_) => f.write_all(b"<lambda>"),
             ValueInner::String(v) => f.write_all(v.as_str().as_bytes()),
             ValueInner::Array(v) => {
                 f.write_all(b"[")?;
@@ -796,6 +822,7 @@ impl Value {
             ValueInner::I128(v) => **v != 0,
             ValueInner::Array(v) => !v.is_empty(),
             ValueInner::Bytes(v) => !v.is_empty(),
+            ValueInner::Lambda(_) => true,
             ValueInner::String(v) => !v.as_str().is_empty(),
             ValueInner::Map(v) => !v.is_empty(),
         }
@@ -1053,6 +1080,7 @@ impl Value {
             ValueInner::I128(_) => "i128",
             ValueInner::Array(_) => "array",
             ValueInner::Bytes(_) => "bytes",
+            ValueInner::Lambda(_) => "lambda",
             ValueInner::String(_) => "string",
             ValueInner::Map(_) => "map/struct",
         }
@@ -1073,6 +1101,7 @@ impl Serialize for Value {
             ValueInner::U128(u) => serializer.serialize_u128(**u),
             ValueInner::I128(i) => serializer.serialize_i128(**i),
             ValueInner::Bytes(b) => serializer.serialize_bytes(b),
+            ValueInner::Lambda(_) => serializer.serialize_str("<lambda>"),
             ValueInner::String(s) => serializer.serialize_str(s.as_str()),
             ValueInner::Array(arr) => {
                 let mut seq = serializer.serialize_seq(Some(arr.len()))?;
@@ -117,6 +117,34 @@ impl Iterator for ForLoopIterator {
 }
 
 impl ForLoopIterator {
+    /// Returns the next value without advancing
+    fn peek(&self) -> Option<Value> {
+        match self {
+            ForLoopIterator::Array { arr, index } => arr.get(*index).cloned(),
+            ForLoopIterator::Map { pairs } => pairs.as_slice().first().map(|(_, v)| v.clone()),
+            #[cfg(not(feature = "unicode"))]
+            ForLoopIterator::String {
+                content,
+                current_pos,
+                ..
+            } => content[*current_pos..]
+                .chars()
+                .next()
+                .map(|c| Value::from(c.to_string())),
+            ForLoopIterator::Bytes { bytes, index } => {
+                bytes.get(*index).map(|b| Value::from(*b as u64))
+            }
+            #[cfg(feature = "unicode")]
+            ForLoopIterator::Graphemes {
+                content,
+                ranges,
+                index,
+            } => ranges
+                .get(*index)
+                .map(|(start, end)| Value::from(&content[*start..*end])),
+        }
+    }
+
     fn indexed_size_hint(len: usize, index: usize) -> (usize, Option<usize>) {
         let remaining = len - index;
         (remaining, Some(remaining))
@@ -209,7 +237,13 @@ pub(crate) struct ForLoop {
     value_name: String,
     key_name: Option<String>,
     current_values: (Option<Value>, Value),
+    /// The value of the previous iteration, for `loop.previtem`
+    previous_value: Option<Value>,
+    /// The last value given to `loop.changed`
+    last_changed: Option<Value>,
     iterated: bool,
+    /// How many `loop(...)` calls of a recursive loop we are in, for `loop.depth0`
+    depth0: usize,
     /// List comprehension are desugared to for loops but we don't expose inner loop.* vars
     is_comprehension: bool,
 }
@@ -235,11 +269,20 @@ impl ForLoop {
             value_name: String::new(), // Will be set by store_local
             key_name: None,
             current_values: (None, Value::undefined()), // Will be set by first advance()
+            previous_value: None,
+            last_changed: None,
             iterated: false,
+            depth0: 0,
             is_comprehension: false,
         }
     }
 
+    pub fn new_recursive(container: Value, depth0: usize) -> Self {
+        let mut for_loop = Self::new(container);
+        for_loop.depth0 = depth0;
+        for_loop
+    }
+
     pub fn new_comprehension(container: Value) -> Self {
         let mut for_loop = Self::new(container);
         for_loop.is_comprehension = true;
@@ -261,7 +304,10 @@ impl ForLoop {
     #[inline(always)]
     pub(crate) fn advance(&mut self) {
         if let Some((key, value)) = self.iterator.next() {
-            self.current_values = (key, value);
+            let (_, previous) = std::mem::replace(&mut self.current_values, (key, value));
+            if self.iterated {
+                self.previous_value = Some(previous);
+            }
             self.iterated = true;
             if self.end_ip != 0 {
                 self.loop_data.advance();
@@ -281,6 +327,36 @@ impl ForLoop {
         self.iterated
     }
 
+    pub(crate) fn depth0(&self) -> usize {
+        self.depth0
+    }
+
+    /// Whether it's a list comprehension, which doesn't expose `loop`
+    pub(crate) fn is_comprehension(&self) -> bool {
+        self.is_comprehension
+    }
+
+    /// Whether the value is different from the one given the last time it was called, or
+    /// if it's the first time it is called. Used by `loop.changed`
+    pub(crate) fn changed(&mut self, value: Value) -> bool {
+        if self.last_changed.as_ref() == Some(&value) {
+            false
+        } else {
+            self.last_changed = Some(value);
+            true
+        }
+    }
+
+    /// Picks the value for the current iteration, going back to the first one once all have
+    /// been used. Used by `loop.cycle`
+    pub(crate) fn cycle<'v>(&self, values: &'v [Value]) -> Option<&'v Value> {
+        if values.is_empty() {
+            None
+        } else {
+            values.get(self.loop_data.index0 % values.len())
+        }
+    }
+
     pub(crate) fn store(&mut self, name: &str, value: Value) {
         self.context.insert(name.to_string(), value);
     }
@@ -302,6 +378,22 @@ impl ForLoop {
             "__tera_loop_length" if !self.is_comprehension => {
                 Some(Value::from(self.loop_data.length as u64))
             }
+            "__tera_loop_revindex" if !self.is_comprehension => Some(Value::from(
+                (self.loop_data.length - self.loop_data.index0) as u64,
+            )),
+            "__tera_loop_revindex0" if !self.is_comprehension => Some(Value::from(
+                (self.loop_data.length - self.loop_data.index()) as u64,
+            )),
+            "__tera_loop_previtem" if !self.is_comprehension => {
+                Some(self.previous_value.clone().unwrap_or(Value::undefined()))
+            }
+            "__tera_loop_nextitem" if !self.is_comprehension => {
+                Some(self.iterator.peek().unwrap_or(Value::undefined()))
+            }
+            "__tera_loop_depth" if !self.is_comprehension => {
+                Some(Value::from((self.depth0 + 1) as u64))
+            }
+            "__tera_loop_depth0" if !self.is_comprehension => Some(Value::from(self.depth0 as u64)),
             _ => {
                 if !self.context.is_empty()
                     && let Some(v) = self.context.get(name)
@@ -1,35 +1,40 @@
+use std::cell::RefCell;
 use std::io::{self, Write};
 use std::sync::Arc;
 
 use crate::errors::{Error, ErrorKind, ReportError, TeraResult};
-use crate::parsing::{Chunk, Instruction};
+use crate::limits::{Budget, CountingWriter, ResourceLimit};
+use crate::parsing::{Chunk, IncludeContext, Instruction};
 use crate::template::Template;
 use crate::utils::Span;
-use crate::value::{Key, Value, ValueInner};
+use crate::value::{Key, Map, Value, ValueInner};
 use crate::vm::for_loop::ForLoop;
 use crate::vm::stack::{SpanRange, combine_spans};
 
 use crate::args::Kwargs;
 use crate::vm::state::{MAGICAL_DUMP_VAR, State};
-use crate::{Context, Tera};
-
-const MAX_COMPONENT_RECURSION_DEPTH: usize = 20;
+use crate::{ComponentInfo, Context, Tera};
 
+#[derive(Debug, Clone, Copy)]
 pub(crate) struct VirtualMachine<'tera> {
     tera: &'tera Tera,
     template: &'tera Template,
     /// Only used when rendering a single component, to decide whether to auto-escape it or not
     autoescape_override: Option<bool>,
-    component_recursion_depth: usize,
+    /// The resource limits of the whole render, shared with the VMs of includes/components
+    budget: &'tera Budget,
+    /// Where to record the components rendered, if the caller wants to know them
+    rendered_components: Option<&'tera RefCell<Vec<ComponentInfo>>>,
 }
 
 impl<'tera> VirtualMachine<'tera> {
-    pub fn new(tera: &'tera Tera, template: &'tera Template) -> Self {
+    pub fn new(tera: &'tera Tera, template: &'tera Template, budget: &'tera Budget) -> Self {
         Self {
             tera,
             template,
             autoescape_override: None,
-            component_recursion_depth: 0,
+            budget,
+            rendered_components: None,
         }
     }
 
@@ -37,15 +42,24 @@ impl<'tera> VirtualMachine<'tera> {
         tera: &'tera Tera,
         template: &'tera Template,
         autoescape: bool,
+        budget: &'tera Budget,
     ) -> Self {
         Self {
             tera,
             template,
             autoescape_override: Some(autoescape),
-            component_recursion_depth: 0,
+            budget,
+            rendered_components: None,
         }
     }
 
+    /// Records the components rendered, including nested ones, in the order they are first
+    /// rendered
+    pub fn record_components(mut self, components: &'tera RefCell<Vec<ComponentInfo>>) -> Self {
+        self.rendered_components = Some(components);
+        self
+    }
+
     fn autoescape_enabled(&self) -> bool {
         self.autoescape_override
             .unwrap_or(self.template.autoescape_enabled)
@@ -56,17 +70,22 @@ impl<'tera> VirtualMachine<'tera> {
         state: &mut State<'tera>,
         output: &mut impl Write,
     ) -> TeraResult<()> {
-        let mut ip = 0;
+        self.interpret_range(state, output, 0, usize::MAX)
+    }
 
+    /// Runs the instructions of the current chunk from `ip` until `end_ip`, excluded.
+    /// Only recursive loops run a part of a chunk, everything else runs it whole.
+    fn interpret_range(
+        &self,
+        state: &mut State<'tera>,
+        output: &mut impl Write,
+        mut ip: usize,
+        end_ip: usize,
+    ) -> TeraResult<()> {
         macro_rules! rendering_error {
             ($msg:expr,$span_range:expr) => {{
                 let chunk = state.chunk.expect("to have a chunk");
-                let span = chunk
-                    .expand_span(&$span_range)
-                    .expect("to have a span for error");
-                let (name, source) = self.report_target(chunk);
-                let err = ReportError::new($msg, name, source, &span);
-                return Err(Error::new(ErrorKind::RenderingError(Box::new(err))));
+                return Err(self.rendering_error_in_range($msg, chunk, &$span_range));
             }};
             // Variant for fused instructions that takes a direct span
             ($msg:expr, span: $span:expr) => {{
@@ -78,6 +97,46 @@ impl<'tera> VirtualMachine<'tera> {
             }};
         }
 
+        macro_rules! limit_error {
+            ($limit:expr, $msg:expr, $ip:expr) => {{
+                let chunk = state.chunk.expect("to have a chunk");
+                return Err(self.limit_error($limit, $msg, chunk, $ip));
+            }};
+        }
+
+        macro_rules! check_list_length {
+            ($len:expr, $ip:expr) => {{
+                let len = $len;
+                if self.budget.is_list_too_long(len) {
+                    limit_error!(
+                        ResourceLimit::ListLength,
+                        format!(
+                            "List of {len} elements exceeds the limit of {} elements",
+                            self.budget.limits.max_list_length.unwrap_or_default()
+                        ),
+                        $ip
+                    );
+                }
+            }};
+        }
+
+        // Strings built while rendering can't be longer than the allowed output
+        macro_rules! check_string_length {
+            ($len:expr, $ip:expr) => {{
+                let len = $len;
+                if self.budget.is_string_too_long(len) {
+                    limit_error!(
+                        ResourceLimit::OutputBytes,
+                        format!(
+                            "String of {len} bytes built while rendering exceeds the limit of {} bytes of output",
+                            self.budget.limits.max_output_bytes.unwrap_or_default()
+                        ),
+                        $ip
+                    );
+                }
+            }};
+        }
+
         macro_rules! op_binop {
             ($op:tt) => {{
                 let (b, b_span) = state.stack.pop();
@@ -154,10 +213,12 @@ impl<'tera> VirtualMachine<'tera> {
                     .unwrap_or_else(|| &self.template.components[$name]);
                 let current_span: SpanRange = $span_idx..=$span_idx;
 
-                let body = if $has_body {
-                    Some(state.stack.pop().0.mark_safe())
+                let (body, slots) = if $has_body {
+                    let body = state.stack.pop().0.mark_safe();
+                    let (slots, _) = state.stack.pop();
+                    (Some(body), slots.into_map().expect("to have slots"))
                 } else {
-                    None
+                    (None, Map::new())
                 };
 
                 let context = match component_def.build_context(
@@ -168,11 +229,48 @@ impl<'tera> VirtualMachine<'tera> {
                     Ok(ctx) => ctx,
                     Err(msg) => rendering_error!(msg, current_span),
                 };
+                // Only calls with valid arguments are rendered
+                if let Some(rendered) = self.rendered_components {
+                    let mut rendered = rendered.borrow_mut();
+                    if !rendered.iter().any(|c| c.name() == component_def.name) {
+                        rendered.push(ComponentInfo::from(component_def));
+                    }
+                }
 
-                let val = match self.render_component(&component_chunk, context) {
+                if !self.budget.enter() {
+                    self.budget.exit();
+                    limit_error!(
+                        ResourceLimit::NestingDepth,
+                        self.nesting_depth_message(),
+                        $span_idx
+                    );
+                }
+                if !self.budget.enter_component() {
+                    self.budget.exit_component();
+                    self.budget.exit();
+                    limit_error!(
+                        ResourceLimit::RecursionDepth,
+                        format!(
+                            "Render exceeded the maximum recursion depth of {} in component calls",
+                            self.budget.limits.max_recursion_depth.unwrap_or_default()
+                        ),
+                        $span_idx
+                    );
+                }
+                let res = self.render_component(
+                    &component_chunk,
+                    context,
+                    slots,
+                    state.tera_global_context(),
+                );
+                self.budget.exit_component();
+                self.budget.exit();
+                let val = match res {
                     Ok(v) => v,
                     Err(mut e) => {
-                        if let ErrorKind::RenderingError(ref mut report) = e.kind {
+                        if let ErrorKind::RenderingError(ref mut report)
+                        | ErrorKind::ResourceLimitExceeded { ref mut report, .. } = e.kind
+                        {
                             let chunk = state.chunk.expect("to have a chunk");
                             if let Some(span) = chunk.expand_span(&current_span) {
                                 let (name, source) = self.report_target(chunk);
@@ -186,10 +284,44 @@ impl<'tera> VirtualMachine<'tera> {
             }};
         }
 
-        while let Some((instr, _)) = state.chunk.expect("To have a chunk").get(ip) {
+        macro_rules! check_iterable {
+            ($container:expr, $span:expr, $is_key_value:expr) => {{
+                if !$container.can_be_iterated_on() {
+                    rendering_error!(
+                        format!("Iteration not possible on type `{}`", $container.name()),
+                        $span
+                    );
+                }
+
+                if $is_key_value && !$container.is_map() {
+                    rendering_error!(
+                        format!(
+                            "Key/value iteration is not possible on type `{}`, only on maps.",
+                            $container.name()
+                        ),
+                        $span
+                    );
+                }
+            }};
+        }
+
+        while ip < end_ip
+            && let Some((instr, _)) = state.chunk.expect("To have a chunk").get(ip)
+        {
             // Current instruction index as span reference
             let current_ip = ip as u32;
 
+            if !self.budget.count_instruction() {
+                limit_error!(
+                    ResourceLimit::Instructions,
+                    format!(
+                        "Render exceeded the limit of {} executed instructions",
+                        self.budget.limits.max_instructions.unwrap_or_default()
+                    ),
+                    current_ip
+                );
+            }
+
             match instr {
                 Instruction::LoadConst(v) => {
                     state.stack.push(v.clone(), current_ip..=current_ip);
@@ -331,6 +463,12 @@ impl<'tera> VirtualMachine<'tera> {
                             top_span
                         );
                     }
+                    if top.is_lambda() {
+                        rendering_error!(
+                            "Tried to render a lambda: lambdas can only be passed to filters, tests and functions".to_string(),
+                            top_span
+                        );
+                    }
 
                     if !self.autoescape_enabled() || top.is_safe() {
                         if let Some(captured) = state.capture_buffers.last_mut() {
@@ -361,18 +499,81 @@ impl<'tera> VirtualMachine<'tera> {
                     let (val, _) = state.stack.pop();
                     state.store_global(name, val);
                 }
-                Instruction::Include(name) => {
+                Instruction::Include(ignore_missing, context) => {
+                    let with = if *context == IncludeContext::Inherit {
+                        None
+                    } else {
+                        let (with, with_span) = state.stack.pop();
+                        if with.as_map().is_none() {
+                            rendering_error!(
+                                format!(
+                                    "The variables given to an include need to be a map, found `{}`",
+                                    with.name()
+                                ),
+                                with_span
+                            );
+                        }
+                        Some(with)
+                    };
+                    let (val, val_span) = state.stack.pop();
+                    let names = match include_names(&val) {
+                        Ok(names) => names,
+                        Err(msg) => rendering_error!(msg, val_span),
+                    };
+                    let Some(tpl) = self.tera.find_first_template(&names)? else {
+                        if !ignore_missing {
+                            let chunk = state.chunk.expect("to have a chunk");
+                            let span = chunk
+                                .get_span(current_ip)
+                                .expect("to have a span for error");
+                            let (name, source) = self.report_target(chunk);
+                            let err = ReportError::new(
+                                template_not_found_message(&names),
+                                name,
+                                source,
+                                span,
+                            )
+                            .with_code("template-not-found");
+                            return Err(Error::new(ErrorKind::RenderingError(Box::new(err))));
+                        }
+                        ip += 1;
+                        continue;
+                    };
+                    let chunk = state.chunk.expect("to have a chunk");
+                    if let Err(chain) = self.budget.enter_include(&chunk.name, &tpl.name) {
+                        rendering_error!(
+                            format!(
+                                "Circular include detected for template '{}'. Include chain: `{chain:?}`",
+                                tpl.name
+                            ),
+                            val_span
+                        );
+                    }
+                    if !self.budget.enter() {
+                        self.budget.exit();
+                        self.budget.exit_include();
+                        limit_error!(
+                            ResourceLimit::NestingDepth,
+                            self.nesting_depth_message(),
+                            current_ip
+                        );
+                    }
                     let res = if state.capture_buffers.is_empty() {
-                        self.render_include(name, state, output)
+                        self.render_include(tpl, state, *context, with.as_ref(), output)
                     } else {
                         let last = state.capture_buffers.len() - 1;
                         let mut buf = std::mem::take(&mut state.capture_buffers[last]);
-                        let result = self.render_include(name, state, &mut buf);
+                        let result =
+                            self.render_include(tpl, state, *context, with.as_ref(), &mut buf);
                         state.capture_buffers[last] = buf;
                         result
                     };
+                    self.budget.exit();
+                    self.budget.exit_include();
                     if let Err(mut e) = res {
-                        if let ErrorKind::RenderingError(ref mut report) = e.kind {
+                        if let ErrorKind::RenderingError(ref mut report)
+                        | ErrorKind::ResourceLimitExceeded { ref mut report, .. } = e.kind
+                        {
                             let chunk = state.chunk.expect("to have a chunk");
                             if let Some(span) = chunk.get_span(current_ip) {
                                 let (name, source) = self.report_target(chunk);
@@ -436,6 +637,7 @@ impl<'tera> VirtualMachine<'tera> {
                         elems.push(state.stack.pop().0);
                     }
                     elems.reverse();
+                    check_list_length!(elems.len(), current_ip);
                     state
                         .stack
                         .push(Value::from(elems), current_ip..=current_ip);
@@ -462,6 +664,7 @@ impl<'tera> VirtualMachine<'tera> {
                         }
                     }
                     result.reverse();
+                    check_list_length!(result.len(), current_ip);
 
                     state
                         .stack
@@ -506,12 +709,19 @@ impl<'tera> VirtualMachine<'tera> {
                             .push(Value::safe_string(&val), current_ip..=current_ip);
                     } else {
                         let f = &self.tera.functions[name.as_str()];
-                        let val = match f.call(Kwargs::new(kwargs.into_map_arc().unwrap()), state) {
+                        let val = match Kwargs::new(kwargs.into_map_arc().unwrap())
+                            .name_positional_args("function", name, f.positional_args())
+                            .and_then(|kwargs| f.call(kwargs, state))
+                        {
                             Ok(v) => v,
                             Err(err) => {
                                 rendering_error!(format!("{err}"), current_ip..=current_ip)
                             }
                         };
+                        if let Some(arr) = val.as_array() {
+                            check_list_length!(arr.len(), current_ip);
+                        }
+                        check_string_length!(val.as_str().map_or(0, str::len), current_ip);
                         let val = if f.is_safe() { val.mark_safe() } else { val };
                         state.stack.push(val, current_ip..=current_ip);
                     }
@@ -520,16 +730,25 @@ impl<'tera> VirtualMachine<'tera> {
                     let f = &self.tera.filters[name.as_str()];
                     let (kwargs, _) = state.stack.pop();
                     let (value, value_span) = state.stack.pop();
-                    let val =
-                        match f.call(&value, Kwargs::new(kwargs.into_map_arc().unwrap()), state) {
-                            Ok(v) => v,
-                            Err(err) => match err.kind {
-                                ErrorKind::InvalidArgument { .. } => {
-                                    rendering_error!(format!("{err}"), value_span)
-                                }
-                                _ => rendering_error!(format!("{err}"), current_ip..=current_ip),
-                            },
-                        };
+                    let val = match Kwargs::new(kwargs.into_map_arc().unwrap())
+                        .name_positional_args("filter", name, f.positional_args())
+                        .and_then(|kwargs| f.call(&value, kwargs, state))
+                    {
+                        Ok(v) => v,
+                        Err(err) => match err.kind {
+                            // Errors from a lambda already point to where they happened
+                            ErrorKind::RenderingError(_)
+                            | ErrorKind::ResourceLimitExceeded { .. } => return Err(err),
+                            ErrorKind::InvalidArgument { .. } => {
+                                rendering_error!(format!("{err}"), value_span)
+                            }
+                            _ => rendering_error!(format!("{err}"), current_ip..=current_ip),
+                        },
+                    };
+                    if let Some(arr) = val.as_array() {
+                        check_list_length!(arr.len(), current_ip);
+                    }
+                    check_string_length!(val.as_str().map_or(0, str::len), current_ip);
                     let val = if f.is_safe() { val.mark_safe() } else { val };
                     state.stack.push(val, current_ip..=current_ip);
                 }
@@ -537,25 +756,80 @@ impl<'tera> VirtualMachine<'tera> {
                     let f = &self.tera.tests[name.as_str()];
                     let (kwargs, _) = state.stack.pop();
                     let (value, value_span) = state.stack.pop();
-                    let val =
-                        match f.call(&value, Kwargs::new(kwargs.into_map_arc().unwrap()), state) {
-                            Ok(v) => v,
-                            Err(err) => match err.kind {
-                                ErrorKind::InvalidArgument { .. } => {
-                                    rendering_error!(format!("{err}"), value_span)
-                                }
-                                _ => rendering_error!(format!("{err}"), current_ip..=current_ip),
-                            },
-                        };
+                    let val = match Kwargs::new(kwargs.into_map_arc().unwrap())
+                        .name_positional_args("test", name, f.positional_args())
+                        .and_then(|kwargs| f.call(&value, kwargs, state))
+                    {
+                        Ok(v) => v,
+                        Err(err) => match err.kind {
+                            // Errors from a lambda already point to where they happened
+                            ErrorKind::RenderingError(_)
+                            | ErrorKind::ResourceLimitExceeded { .. } => return Err(err),
+                            ErrorKind::InvalidArgument { .. } => {
+                                rendering_error!(format!("{err}"), value_span)
+                            }
+                            _ => rendering_error!(format!("{err}"), current_ip..=current_ip),
+                        },
+                    };
 
                     state.stack.push(val.into(), current_ip..=current_ip);
                 }
+                Instruction::LoopChanged => {
+                    let (value, span) = state.stack.pop();
+                    let changed = match state.current_loop_mut() {
+                        Some(for_loop) => for_loop.changed(value),
+                        None => unreachable!("loop.changed is only allowed in loops"),
+                    };
+                    state.stack.push(Value::from(changed), span);
+                }
+                Instruction::LoopCycle => {
+                    let (values, span) = state.stack.pop();
+                    let values = values.as_array().expect("loop.cycle values to be a list");
+                    let value = match state.current_loop_mut() {
+                        Some(for_loop) => for_loop.cycle(values).cloned(),
+                        None => unreachable!("loop.cycle is only allowed in loops"),
+                    };
+                    state.stack.push(value.unwrap_or(Value::none()), span);
+                }
                 Instruction::RenderBodyComponent(name) => {
                     component!(name, current_ip, true);
                 }
                 Instruction::RenderInlineComponent(name) => {
                     component!(name, current_ip, false);
                 }
+                Instruction::RenderSlot(name, end_ip) => {
+                    if let Some(content) = state.slots.get(&Key::Str(name)) {
+                        let content = content.as_str().expect("slots to be strings").as_bytes();
+                        if let Some(captured) = state.capture_buffers.last_mut() {
+                            captured.write_all(content)?;
+                        } else {
+                            output.write_all(content)?;
+                        }
+                        ip = *end_ip;
+                        continue;
+                    }
+                }
+                Instruction::JumpIfArgGiven(name, end_ip) => {
+                    if state.context.data.contains_key(&**name) {
+                        ip = *end_ip;
+                        continue;
+                    }
+                    // The default value can use the global context, unlike the component body
+                    state.global_context = state.component_global_context;
+                }
+                Instruction::SetArgDefault(name, typ) => {
+                    state.global_context = None;
+                    let (val, _) = state.stack.pop();
+                    if let Some(typ) = typ
+                        && !typ.matches_value(&val)
+                    {
+                        rendering_error!(
+                            typ.arg_mismatch_message(name, &val),
+                            current_ip..=current_ip
+                        );
+                    }
+                    state.store_local(name, val);
+                }
                 Instruction::RenderBlock(block_name) => {
                     let Some(block_lineage) = self
                         .template
@@ -575,7 +849,8 @@ impl<'tera> VirtualMachine<'tera> {
                     let old_block_name = state.current_block_name.replace(block_name);
                     let res = if state.capture_block == Some(block_name.as_str()) {
                         let mut buf = Vec::with_capacity(256);
-                        let r = self.interpret(state, &mut buf);
+                        let r =
+                            self.interpret(state, &mut CountingWriter::new(&mut buf, self.budget));
                         state.block_buffer = buf;
                         r
                     } else {
@@ -620,34 +895,59 @@ impl<'tera> VirtualMachine<'tera> {
                 }
                 Instruction::EndCapture => {
                     let captured = state.capture_buffers.pop().unwrap();
+                    check_string_length!(captured.len(), current_ip);
                     let val = Value::safe_string(&String::from_utf8(captured)?);
                     state.stack.push(val, current_ip..=current_ip);
                 }
                 Instruction::StartIterate(is_key_value)
-                | Instruction::StartIterateComprehension(is_key_value) => {
+                | Instruction::StartIterateComprehension(is_key_value)
+                | Instruction::RecursiveLoop(is_key_value, _) => {
                     let (container, container_span) = state.stack.pop();
-                    if !container.can_be_iterated_on() {
-                        rendering_error!(
-                            format!("Iteration not possible on type `{}`", container.name()),
-                            container_span
-                        );
+                    check_iterable!(container, container_span, *is_key_value);
+
+                    if matches!(instr, Instruction::StartIterateComprehension(_)) {
+                        state.for_loops.push(ForLoop::new_comprehension(container));
+                    } else {
+                        state.for_loops.push(ForLoop::new(container));
                     }
+                }
+                Instruction::RecurseLoop(loop_ip) => {
+                    let (container, container_span) = state.stack.pop();
+                    let Some((Instruction::RecursiveLoop(is_key_value, loop_end_ip), _)) =
+                        state.chunk.expect("to have a chunk").get(*loop_ip)
+                    else {
+                        unreachable!("loop() to point to a recursive loop");
+                    };
+                    check_iterable!(container, container_span, *is_key_value);
 
-                    if *is_key_value && !container.is_map() {
-                        rendering_error!(
+                    let depth0 = state
+                        .for_loops
+                        .iter()
+                        .rev()
+                        .find(|l| !l.is_comprehension())
+                        .map_or(0, |l| l.depth0() + 1);
+                    if self.budget.is_recursion_too_deep(depth0) {
+                        limit_error!(
+                            ResourceLimit::RecursionDepth,
                             format!(
-                                "Key/value iteration is not possible on type `{}`, only on maps.",
-                                container.name()
+                                "Render exceeded the maximum recursion depth of {} in a recursive loop",
+                                self.budget.limits.max_recursion_depth.unwrap_or_default()
                             ),
-                            container_span
+                            current_ip
                         );
                     }
 
-                    if matches!(instr, Instruction::StartIterateComprehension(_)) {
-                        state.for_loops.push(ForLoop::new_comprehension(container));
-                    } else {
-                        state.for_loops.push(ForLoop::new(container));
-                    }
+                    // The loop body is rendered again, right after its `RecursiveLoop`
+                    state
+                        .for_loops
+                        .push(ForLoop::new_recursive(container, depth0));
+                    state.capture_buffers.push(Vec::with_capacity(128));
+                    let res = self.interpret_range(state, output, loop_ip + 1, *loop_end_ip);
+                    let captured = state.capture_buffers.pop().unwrap();
+                    res?;
+                    check_string_length!(captured.len(), current_ip);
+                    let val = Value::safe_string(&String::from_utf8(captured)?);
+                    state.stack.push(val, current_ip..=current_ip);
                 }
                 Instruction::StoreLocal(name) => {
                     if let Some(for_loop) = state.for_loops.last_mut() {
@@ -683,11 +983,22 @@ impl<'tera> VirtualMachine<'tera> {
                 Instruction::AppendToList => {
                     let (val, _) = state.stack.pop();
                     let (list, _) = state.stack.peek_mut();
-                    if let ValueInner::Array(arr) = &mut list.inner {
-                        Arc::make_mut(arr).push(val);
+                    let len = if let ValueInner::Array(arr) = &mut list.inner {
+                        let arr = Arc::make_mut(arr);
+                        arr.push(val);
+                        arr.len()
                     } else {
                         unreachable!("AppendToList only works on arrays")
-                    }
+                    };
+                    check_list_length!(len, current_ip);
+                }
+                Instruction::CaptureLambda => {
+                    let (val, span) = state.stack.pop();
+                    let lambda = val
+                        .as_lambda()
+                        .expect("CaptureLambda to follow a lambda")
+                        .capture(|name| state.get_value(name));
+                    state.stack.push(Value::lambda(lambda), span);
                 }
                 Instruction::Mul => math_binop!(mul),
                 Instruction::Div => math_binop!(div),
@@ -736,6 +1047,7 @@ impl<'tera> VirtualMachine<'tera> {
                         }
                         _ => Value::from(format!("{a}{b}")),
                     };
+                    check_string_length!(result.as_str().map_or(0, str::len), current_ip);
                     state.stack.push(result, c_span);
                 }
                 Instruction::In => {
@@ -845,6 +1157,16 @@ impl<'tera> VirtualMachine<'tera> {
                     } else {
                         &root
                     };
+                    if val.is_lambda() {
+                        let span = chunk
+                            .get_span_at(current_ip, num_attrs)
+                            .expect("to have a span for error");
+                        return Err(self.rendering_error(
+                            "Tried to render a lambda: lambdas can only be passed to filters, tests and functions".to_string(),
+                            chunk,
+                            span,
+                        ));
+                    }
 
                     if !self.autoescape_enabled() || val.is_safe() {
                         if let Some(captured) = state.capture_buffers.last_mut() {
@@ -868,12 +1190,71 @@ impl<'tera> VirtualMachine<'tera> {
                 }
             }
 
+            if let Some(captured) = state.capture_buffers.last() {
+                check_string_length!(captured.len(), current_ip);
+            }
+
+            if self.budget.output_exceeded() {
+                limit_error!(
+                    ResourceLimit::OutputBytes,
+                    format!(
+                        "Render exceeded the limit of {} bytes of output",
+                        self.budget.limits.max_output_bytes.unwrap_or_default()
+                    ),
+                    current_ip
+                );
+            }
+
             ip += 1;
         }
 
         Ok(())
     }
 
+    fn nesting_depth_message(&self) -> String {
+        format!(
+            "Render exceeded the maximum nesting depth of {} includes/components",
+            self.budget.limits.max_nesting_depth.unwrap_or_default()
+        )
+    }
+
+    /// Runs the body of a lambda called by a filter, test or function.
+    /// Lambdas calling lambdas count against `max_recursion_depth`, so a lambda calling itself
+    /// errors instead of overflowing the stack.
+    pub(crate) fn interpret_lambda(&self, state: &mut State<'tera>) -> TeraResult<()> {
+        if !self.budget.enter_lambda() {
+            self.budget.exit_lambda();
+            let chunk = state.chunk.expect("to have a chunk");
+            return Err(self.limit_error(
+                ResourceLimit::RecursionDepth,
+                format!(
+                    "Render exceeded the maximum recursion depth of {} in lambda calls",
+                    self.budget.limits.max_recursion_depth.unwrap_or_default()
+                ),
+                chunk,
+                0,
+            ));
+        }
+        let res = self.interpret(state, &mut io::sink());
+        self.budget.exit_lambda();
+        res
+    }
+
+    fn limit_error(&self, limit: ResourceLimit, msg: String, chunk: &Chunk, ip: u32) -> Error {
+        // Some instructions like jumps don't have a span so we take the closest one
+        let span = chunk.closest_span(ip).cloned().unwrap_or_else(|| Span {
+            start_line: 1,
+            end_line: 1,
+            ..Span::default()
+        });
+        let (name, source) = self.report_target(chunk);
+        let err = ReportError::new(msg, name, source, &span);
+        Error::new(ErrorKind::ResourceLimitExceeded {
+            limit,
+            report: Box::new(err),
+        })
+    }
+
     fn undefined_var_error(
         &self,
         state: &State<'tera>,
@@ -881,6 +1262,20 @@ impl<'tera> VirtualMachine<'tera> {
         name: &str,
         span: &Span,
     ) -> Error {
+        // Only `loop.previtem` and `loop.nextitem` can be undefined
+        if let Some(attr) = name.strip_prefix("__tera_loop_") {
+            let reason = if attr.ends_with("previtem") {
+                "there is no previous item in the first iteration"
+            } else {
+                "there is no next item in the last iteration"
+            };
+            let attr = attr.replace("parent_", "parent.");
+            return self.rendering_error(
+                format!("`loop.{attr}` is not defined: {reason}."),
+                chunk,
+                span,
+            );
+        }
         let available_vars = state.available_variables();
         let available_msg = if available_vars.is_empty() {
             String::new()
@@ -916,35 +1311,52 @@ impl<'tera> VirtualMachine<'tera> {
 
     fn report_target(&self, chunk: &Chunk) -> (&'tera str, &'tera str) {
         if self.template.name != chunk.name {
-            let tpl = &self.tera.templates[&chunk.name];
+            let tpl = self
+                .tera
+                .get_template(&chunk.name)
+                .expect("chunk template to exist");
             (&tpl.name, &tpl.source)
         } else {
             (&self.template.name, &self.template.source)
         }
     }
 
+    /// Out of `interpret_range` to keep its stack frame small, since includes and components
+    /// recurse through it
+    #[cold]
+    #[inline(never)]
+    fn rendering_error_in_range(&self, msg: String, chunk: &Chunk, range: &SpanRange) -> Error {
+        let span = chunk.expand_span(range).expect("to have a span for error");
+        self.rendering_error(msg, chunk, &span)
+    }
+
     fn rendering_error(&self, msg: String, chunk: &Chunk, span: &Span) -> Error {
         let (name, source) = self.report_target(chunk);
         let err = ReportError::new(msg, name, source, span);
         Error::new(ErrorKind::RenderingError(Box::new(err)))
     }
 
-    fn render_component(&self, chunk: &Chunk, context: Context) -> TeraResult<String> {
-        let depth = self.component_recursion_depth + 1;
-        if depth > MAX_COMPONENT_RECURSION_DEPTH {
-            return Err(Error::message(
-                "Maximum render recursion depth for components exceeded.",
-            ));
-        }
+    fn render_component(
+        &self,
+        chunk: &Chunk,
+        context: Context,
+        slots: Map,
+        global_context: Option<&Context>,
+    ) -> TeraResult<String> {
         let vm = Self {
             tera: self.tera,
             template: self.template,
             autoescape_override: self.autoescape_override,
-            component_recursion_depth: depth,
+            budget: self.budget,
+            rendered_components: self.rendered_components,
         };
 
         let mut state = State::new_with_chunk(&context, chunk);
         state.filters = Some(&self.tera.filters);
+        state.tests = Some(&self.tera.tests);
+        state.vm = Some(vm);
+        state.slots = slots;
+        state.component_global_context = global_context;
         let mut output = Vec::with_capacity(1024);
         vm.interpret(&mut state, &mut output)?;
 
@@ -953,22 +1365,43 @@ impl<'tera> VirtualMachine<'tera> {
 
     fn render_include(
         &self,
-        name: &str,
+        tpl: &'tera Template,
         state: &State<'tera>,
+        context: IncludeContext,
+        with: Option<&Value>,
         output: &mut impl Write,
     ) -> TeraResult<()> {
-        let tpl = self.tera.must_get_template(name)?;
         let vm = Self {
             tera: self.tera,
             template: tpl,
             autoescape_override: self.autoescape_override,
-            component_recursion_depth: self.component_recursion_depth,
+            budget: self.budget,
+            rendered_components: self.rendered_components,
         };
+        let with = with.and_then(|w| w.as_map());
 
         // We create a dummy state for variables to be written to, but we don't keep it around
-        let mut include_state = State::new_with_chunk(state.context, &tpl.chunk);
-        include_state.include_parent = Some(state);
+        let only_context;
+        let mut include_state = if context == IncludeContext::Only {
+            let mut ctx = Context::new();
+            for (key, value) in with.into_iter().flatten() {
+                ctx.insert_value(key.to_string(), value.clone());
+            }
+            only_context = ctx;
+            let mut include_state = State::new_with_chunk(&only_context, &tpl.chunk);
+            include_state.global_context = state.root_global_context();
+            include_state
+        } else {
+            let mut include_state = State::new_with_chunk(state.context, &tpl.chunk);
+            include_state.include_parent = Some(state);
+            for (key, value) in with.into_iter().flatten() {
+                include_state.store_global(&key.to_string(), value.clone());
+            }
+            include_state
+        };
         include_state.filters = Some(&self.tera.filters);
+        include_state.tests = Some(&self.tera.tests);
+        include_state.vm = Some(vm);
         vm.interpret(&mut include_state, output)?;
         Ok(())
     }
@@ -999,7 +1432,7 @@ impl<'tera> VirtualMachine<'tera> {
         block_name: Option<&str>,
         context: &Context,
         global_context: &Context,
-        mut output: impl Write,
+        output: impl Write,
     ) -> TeraResult<()> {
         // TODO(perf): can we optimize this at the bytecode level to avoid hashmap lookups?
         let chunk = if let Some(base_tpl_name) = self.template.parents.first() {
@@ -1011,15 +1444,50 @@ impl<'tera> VirtualMachine<'tera> {
         let mut state = State::new_with_chunk(context, chunk);
         state.global_context = Some(global_context);
         state.filters = Some(&self.tera.filters);
+        state.tests = Some(&self.tera.tests);
+        state.vm = Some(*self);
 
         if let Some(block) = block_name {
             state.capture_block = Some(block);
             // we don't care about keeping the full rendered template
             self.interpret(&mut state, &mut io::sink())?;
+            let mut output = output;
             output.write_all(&state.block_buffer)?;
         } else {
-            self.interpret(&mut state, &mut output)?;
+            self.interpret(&mut state, &mut CountingWriter::new(output, self.budget))?;
         }
         Ok(())
     }
 }
+
+/// The names of the templates to try for an include, in order.
+fn include_names(val: &Value) -> Result<Vec<&str>, String> {
+    let invalid = || {
+        format!(
+            "Included template names need to be a string or an array of strings, found `{}`",
+            val.name()
+        )
+    };
+    if let Some(name) = val.as_str() {
+        return Ok(vec![name]);
+    }
+    let names = val.as_array().ok_or_else(invalid)?;
+    names
+        .iter()
+        .map(|n| n.as_str().ok_or_else(invalid))
+        .collect()
+}
+
+fn template_not_found_message(names: &[&str]) -> String {
+    match names {
+        [name] => format!("Template '{name}' not found"),
+        _ => format!(
+            "None of the templates [{}] were found",
+            names
+                .iter()
+                .map(|n| format!("'{n}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
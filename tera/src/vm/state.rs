@@ -1,10 +1,12 @@
 use crate::args::{ArgFromValue, Kwargs};
-use crate::errors::TeraResult;
+use crate::errors::{Error, TeraResult};
 use crate::filters::StoredFilter;
 use crate::parsing::Chunk;
+use crate::tests::StoredTest;
 use crate::vm::for_loop::ForLoop;
+use crate::vm::interpreter::VirtualMachine;
 use crate::vm::stack::Stack;
-use crate::{Context, HashMap, Value};
+use crate::{Context, HashMap, Map, Value};
 
 use std::borrow::Cow;
 use std::collections::BTreeMap;
@@ -27,6 +29,9 @@ pub struct State<'tera> {
     pub(crate) context: &'tera Context,
     /// The global context from Tera, checked after user context
     pub(crate) global_context: Option<&'tera Context>,
+    /// The global context from Tera in components, where only the default values of the
+    /// arguments can use it
+    pub(crate) component_global_context: Option<&'tera Context>,
     /// To handle the capture instructions
     pub(crate) capture_buffers: Vec<Vec<u8>>,
     /// Scratch buffer for escaping output to avoid per-write allocations
@@ -41,8 +46,14 @@ pub struct State<'tera> {
     /// (block name, all_chunks, level).
     pub(crate) blocks: Vec<(&'tera str, &'tera Vec<Chunk>, usize)>,
     pub(crate) current_block_name: Option<&'tera str>,
+    /// The content of the slots filled by the component call being rendered
+    pub(crate) slots: Map,
     /// Reference to registered filters for calling filters from within filters (e.g., map filter)
     pub(crate) filters: Option<&'tera HashMap<Cow<'static, str>, StoredFilter>>,
+    /// Reference to registered tests for calling tests from within filters (e.g., select filter)
+    pub(crate) tests: Option<&'tera HashMap<Cow<'static, str>, StoredTest>>,
+    /// The VM rendering the template, to run the lambdas given to filters (e.g., sort filter)
+    pub(crate) vm: Option<VirtualMachine<'tera>>,
 }
 
 impl<'t> State<'t> {
@@ -61,6 +72,7 @@ impl<'t> State<'t> {
             set_variables: BTreeMap::new(),
             context,
             global_context: None,
+            component_global_context: None,
             chunk: None,
             capture_buffers: Vec::with_capacity(4),
             escape_buffer: Vec::with_capacity(128),
@@ -69,10 +81,26 @@ impl<'t> State<'t> {
             block_buffer: Vec::new(),
             blocks: Vec::new(),
             current_block_name: None,
+            slots: Map::new(),
             filters: None,
+            tests: None,
+            vm: None,
         }
     }
 
+    /// Tera's global context, which is only set on the state of the template being rendered
+    pub(crate) fn root_global_context(&self) -> Option<&'t Context> {
+        self.global_context
+            .or_else(|| self.include_parent.and_then(|p| p.root_global_context()))
+    }
+
+    /// Tera's global context, including in components where it is hidden from their body
+    pub(crate) fn tera_global_context(&self) -> Option<&'t Context> {
+        self.global_context
+            .or(self.component_global_context)
+            .or_else(|| self.include_parent.and_then(|p| p.tera_global_context()))
+    }
+
     pub(crate) fn store_local(&mut self, name: &str, value: Value) {
         if let Some(forloop) = self.for_loops.last_mut() {
             forloop.store(name, value);
@@ -93,6 +121,14 @@ impl<'t> State<'t> {
     /// 4. self.context (user context)
     /// 5. self.global_context (Tera's global context) or return Value::Undefined
     pub(crate) fn get_value(&self, name: &str) -> Value {
+        if let Some(attr) = name.strip_prefix("__tera_loop_parent_") {
+            // Lambdas get the values captured where they were written
+            if let Some(val) = self.set_variables.get(name) {
+                return val.clone();
+            }
+            return self.get_parent_loop_value(attr);
+        }
+
         for forloop in self.for_loops.iter().rev() {
             if let Some(v) = forloop.get(name) {
                 return v;
@@ -123,6 +159,32 @@ impl<'t> State<'t> {
         Value::undefined()
     }
 
+    /// The innermost loop exposing `loop`, eg not a list comprehension
+    pub(crate) fn current_loop_mut(&mut self) -> Option<&mut ForLoop> {
+        self.for_loops
+            .iter_mut()
+            .rev()
+            .find(|l| !l.is_comprehension())
+    }
+
+    /// Handles `loop.parent.parent.index` and the like, `attr` being what is after
+    /// the first `parent` in the variable name, eg `parent_index`
+    fn get_parent_loop_value(&self, mut attr: &str) -> Value {
+        let mut depth = 1;
+        while let Some(rest) = attr.strip_prefix("parent_") {
+            depth += 1;
+            attr = rest;
+        }
+
+        self.for_loops
+            .iter()
+            .rev()
+            .filter(|l| !l.is_comprehension())
+            .nth(depth)
+            .and_then(|l| l.get(&format!("__tera_loop_{attr}")))
+            .unwrap_or(Value::undefined())
+    }
+
     /// Get a variable from the context by name and convert it to the specified type.
     ///
     /// Returns `Ok(None)` if the variable is not defined (undefined).
@@ -186,6 +248,47 @@ impl<'t> State<'t> {
         }
     }
 
+    /// Call a test by name. Used by filters like `select` that need to apply tests.
+    pub fn call_test(&self, name: &str, value: &Value, kwargs: Kwargs) -> TeraResult<bool> {
+        match self.tests.and_then(|t| t.get(name)) {
+            Some(test) => test.call(value, kwargs, self),
+            None => Err(crate::errors::Error::message(format!(
+                "Test `{name}` is not registered"
+            ))),
+        }
+    }
+
+    /// Call a lambda, like `p => p.price * p.qty`, with the value of its parameter.
+    /// Used by filters like `sort` that accept a lambda to compute a key.
+    ///
+    /// The lambda uses the variables of the template where it is written, with the values
+    /// they had when the lambda was created.
+    pub fn call_lambda(&self, lambda: &Value, value: &Value) -> TeraResult<Value> {
+        let Some(compiled) = lambda.as_lambda() else {
+            return Err(Error::message(format!(
+                "Expected a lambda like `x => x.name`, got a value of type `{}`",
+                lambda.name()
+            )));
+        };
+        let Some(vm) = self.vm else {
+            return Err(Error::message(
+                "Lambdas can only be called while rendering a template",
+            ));
+        };
+
+        let context = Context::new();
+        let mut state = State::new_with_chunk(&context, &compiled.chunk);
+        state.filters = self.filters;
+        state.tests = self.tests;
+        state.vm = self.vm;
+        for (name, val) in &compiled.captures {
+            state.store_global(name, val.clone());
+        }
+        state.store_global(&compiled.param, value.clone());
+        vm.interpret_lambda(&mut state)?;
+        Ok(state.stack.pop().0)
+    }
+
     /// Returns a sorted list of all available variable names in the current scope.
     /// Used for error messages only.
     pub(crate) fn available_variables(&self) -> Vec<String> {
@@ -0,0 +1,196 @@
+//! Reloading the templates when their files change.
+use std::sync::mpsc::{Receiver, channel};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::errors::{Error, TeraResult};
+use crate::globbing::Glob;
+use crate::tera::Tera;
+
+/// How long to wait for more changes before reloading: editors can touch several files, or the
+/// same file several times, when saving.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// A [`Tera`] instance reloading its templates when their files change.
+///
+/// It watches the directory of the glob given to [`Tera::load_from_glob`]. The changed files
+/// are reloaded with [`Tera::reload_paths`] on a background thread and the reloaded instance then
+/// replaces the current one. If reloading fails, the error is given to the callback and the
+/// current instance is kept: the files that failed are reloaded again with the next changes.
+///
+/// Watching stops when it is dropped.
+///
+/// ```no_run
+/// # use tera::{Context, Tera, TeraWatcher};
+/// let mut tera = Tera::default();
+/// tera.load_from_glob("templates/**/*.html").unwrap();
+/// let watcher = TeraWatcher::new(tera, |e| eprintln!("Failed to reload the templates: {e}")).unwrap();
+///
+/// // eg in a request handler
+/// let html = watcher.tera().render("index.html", &Context::new()).unwrap();
+/// ```
+pub struct TeraWatcher {
+    current: Arc<RwLock<Arc<Tera>>>,
+    // Only kept to not stop watching
+    _watcher: RecommendedWatcher,
+}
+
+impl TeraWatcher {
+    /// Starts watching the templates of that instance, which needs to have loaded them with
+    /// [`Tera::load_from_glob`].
+    pub fn new(tera: Tera, on_error: impl FnMut(Error) + Send + 'static) -> TeraResult<Self> {
+        let Some(glob) = &tera.glob else {
+            return Err(Error::message(
+                "Watching is only available if you are using a glob",
+            ));
+        };
+        let dir = Glob::new(glob)?.parent_dir().to_path_buf();
+
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .map_err(|e| Error::chain("Failed to create the file watcher", e))?;
+        watcher
+            .watch(&dir, RecursiveMode::Recursive)
+            .map_err(|e| Error::chain(format!("Failed to watch '{}'", dir.display()), e))?;
+
+        let current = Arc::new(RwLock::new(Arc::new(tera)));
+        let shared = Arc::clone(&current);
+        std::thread::spawn(move || reload_on_changes(receiver, shared, on_error));
+
+        Ok(Self {
+            current,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the current instance.
+    ///
+    /// It is not updated by later reloads: call this method again to get the latest templates.
+    pub fn tera(&self) -> Arc<Tera> {
+        Arc::clone(&self.current.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+fn reload_on_changes(
+    receiver: Receiver<notify::Result<Event>>,
+    current: Arc<RwLock<Arc<Tera>>>,
+    mut on_error: impl FnMut(Error),
+) {
+    // Changes that failed to reload, eg a parent breaking a child, are retried with the next ones
+    // since fixing the child alone would otherwise not bring the new parent in
+    let mut failed = Vec::new();
+    // It stops when the watcher, which owns the sender, is dropped
+    while let Ok(first) = receiver.recv() {
+        let mut paths = std::mem::take(&mut failed);
+        let mut next = Some(first);
+        while let Some(event) = next {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => (),
+                Ok(event) => paths.extend(event.paths),
+                Err(e) => on_error(Error::chain("Failed to watch the templates", e)),
+            }
+            next = receiver.recv_timeout(DEBOUNCE).ok();
+        }
+        if paths.is_empty() {
+            continue;
+        }
+        paths.sort();
+        paths.dedup();
+
+        // Cloning is cheap: the templates are shared and only the reloaded ones are copied
+        let mut tera = Tera::clone(&current.read().unwrap_or_else(PoisonError::into_inner));
+        match tera.reload_paths(&paths) {
+            Ok(()) => *current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(tera),
+            Err(e) => {
+                on_error(e);
+                failed = paths;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+
+    use super::TeraWatcher;
+    use crate::{Context, Tera};
+
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn reloads_changed_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.html");
+        std::fs::write(&path, "Hello").unwrap();
+        let glob = dir.path().join("**/*.html").to_string_lossy().to_string();
+        let mut tera = Tera::default();
+        tera.load_from_glob(&glob).unwrap();
+
+        let (sender, receiver) = channel();
+        let watcher = TeraWatcher::new(tera, move |e| sender.send(e.to_string()).unwrap()).unwrap();
+        let render = || {
+            watcher
+                .tera()
+                .render("hello.html", &Context::new())
+                .unwrap()
+        };
+
+        std::fs::write(&path, "Bonjour").unwrap();
+        assert!(wait_for(|| render() == "Bonjour"));
+
+        std::fs::write(&path, "{% if %}").unwrap();
+        let err = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(err.contains("hello.html"));
+        assert_eq!(render(), "Bonjour");
+    }
+
+    #[test]
+    fn retries_failed_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let parent = dir.path().join("parent.html");
+        let child = dir.path().join("child.html");
+        std::fs::write(&parent, "parent").unwrap();
+        std::fs::write(&child, "{% extends 'parent.html' %}").unwrap();
+        let glob = dir.path().join("**/*.html").to_string_lossy().to_string();
+        let mut tera = Tera::default();
+        tera.load_from_glob(&glob).unwrap();
+
+        let (sender, receiver) = channel();
+        let watcher = TeraWatcher::new(tera, move |e| sender.send(e.to_string()).unwrap()).unwrap();
+        let render = || {
+            watcher
+                .tera()
+                .render("parent.html", &Context::new())
+                .unwrap()
+        };
+
+        // The new parent is valid on its own but makes the child extend itself
+        std::fs::write(&parent, "{% extends 'child.html' %}").unwrap();
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(render(), "parent");
+
+        // Only the child changes but the parent is reloaded too
+        std::fs::write(&child, "child").unwrap();
+        assert!(wait_for(|| render() == "child"));
+    }
+
+    #[test]
+    fn errors_without_glob() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello.html", "Hello").unwrap();
+        assert!(TeraWatcher::new(tera, |_| {}).is_err());
+    }
+}
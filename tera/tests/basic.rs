@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use serde::Serialize;
 
-use tera::{Context, Filter, Function, Kwargs, State, Tera};
+use tera::{Context, Filter, Function, Kwargs, State, Tera, TeraResult};
 
 #[derive(Serialize)]
 struct Test {
@@ -224,6 +224,40 @@ fn function_is_safe_skips_autoescape() {
     assert_eq!(out, "<em>hello</em>");
 }
 
+struct WrapFilter;
+
+impl Filter<&str, TeraResult<String>> for WrapFilter {
+    fn call(&self, value: &str, kwargs: Kwargs, _state: &State) -> TeraResult<String> {
+        let start = kwargs.must_get::<&str>("start")?;
+        let end = kwargs.get::<&str>("end")?.unwrap_or(start);
+        Ok(format!("{start}{value}{end}"))
+    }
+
+    fn positional_args(&self) -> &'static [&'static str] {
+        &["start", "end"]
+    }
+}
+
+#[test]
+fn filter_positional_args_are_named() {
+    let mut tera = Tera::default();
+    tera.register_filter("wrap", WrapFilter);
+    tera.add_raw_template(
+        "tpl",
+        r#"{{ "a" | wrap("*") }} {{ "b" | wrap("(", ")") }} {{ "c" | wrap("<", end=">") }}"#,
+    )
+    .unwrap();
+    tera.add_raw_template("too_many", r#"{{ "a" | wrap("(", ")", "!") }}"#)
+        .unwrap();
+
+    let out = tera.render("tpl", &Context::new()).unwrap();
+    assert_eq!(out, "*a* (b) <c>");
+    let err = tera.render("too_many", &Context::new()).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("accepts at most 2 positional argument(s): `wrap(start, end)`")
+    );
+}
+
 #[test]
 fn filter_not_safe_gets_autoescaped() {
     let mut tera = Tera::default();
@@ -1,19 +1,19 @@
 use std::collections::{HashMap, HashSet};
 
-use tera::{ComponentArgType, Tera, Value};
+use tera::{ComponentArgDefault, ComponentArgType, Tera, Value};
 
 #[test]
 fn test_get_component_definition() {
     let mut tera = Tera::default();
     tera.add_raw_template(
         "components.html",
-        r#"{% component Button(label: String, size: Integer = 1, variant = "primary", message, ...restant) {"doc": "An alert box", "deprecated": true} %} %}<button>{{ label }}</button>{% endcomponent Button %}"#,
+        r#"{% component Button(label: String, size: Integer = 1, variant = "primary", id = "btn-" ~ label, message, ...restant) {"doc": "An alert box", "deprecated": true} %} %}<button>{{ label }}</button>{% endcomponent Button %}"#,
     )
         .unwrap();
 
     let info = tera.get_component_definition("Button").unwrap();
     assert_eq!(info.name(), "Button");
-    assert_eq!(info.args().len(), 4);
+    assert_eq!(info.args().len(), 5);
     assert_eq!(info.rest_param(), Some("restant"));
 
     let args: HashMap<_, _> = info.args().iter().map(|x| (x.name(), x)).collect();
@@ -27,13 +27,27 @@ fn test_get_component_definition() {
     let size = args.get("size").unwrap();
     assert!(!size.is_required());
     assert_eq!(size.arg_type().unwrap(), ComponentArgType::Integer);
-    assert_eq!(size.default().unwrap(), &Value::from(1));
+    assert_eq!(
+        size.default().unwrap(),
+        &ComponentArgDefault::Value(Value::from(1))
+    );
 
     let variant = args.get("variant").unwrap();
     assert!(!variant.is_required());
     // Type is inferred from the default value
     assert_eq!(variant.arg_type().unwrap(), ComponentArgType::String);
-    assert_eq!(variant.default().unwrap(), &Value::from("primary"));
+    assert_eq!(
+        variant.default().unwrap(),
+        &ComponentArgDefault::Value(Value::from("primary"))
+    );
+
+    let id = args.get("id").unwrap();
+    assert!(!id.is_required());
+    assert!(id.arg_type().is_none());
+    assert_eq!(
+        id.default().unwrap(),
+        &ComponentArgDefault::Expression(r#""btn-" ~ label"#.to_string())
+    );
 
     let meta = info.metadata();
     assert_eq!(meta.get("doc").unwrap(), &Value::from("An alert box"));
@@ -42,6 +56,54 @@ fn test_get_component_definition() {
     assert!(tera.get_component_definition("DoesNotExist").is_none());
 }
 
+#[test]
+fn test_get_component_definition_rich_types() {
+    let mut tera = Tera::default();
+    tera.add_raw_template(
+        "components.html",
+        r#"{% component Nav(variant: "main" | "footer", links: array<{url: string, title: string?}>, icon: string?) %}{% endcomponent Nav %}"#,
+    )
+    .unwrap();
+
+    let info = tera.get_component_definition("Nav").unwrap();
+    let args: HashMap<_, _> = info.args().iter().map(|x| (x.name(), x)).collect();
+
+    let variant = args.get("variant").unwrap();
+    assert!(variant.is_required());
+    assert_eq!(
+        variant.arg_type().unwrap(),
+        ComponentArgType::OneOf(vec![Value::from("main"), Value::from("footer")])
+    );
+    assert_eq!(variant.arg_type().unwrap().as_str(), "string");
+    assert_eq!(
+        variant.arg_type().unwrap().to_string(),
+        r#""main" | "footer""#
+    );
+
+    let links = args.get("links").unwrap();
+    assert_eq!(
+        links.arg_type().unwrap().to_string(),
+        "array<{url: string, title: string?}>"
+    );
+    assert_eq!(links.arg_type().unwrap().as_str(), "array");
+
+    // Optional types don't need to be passed
+    let icon = args.get("icon").unwrap();
+    assert!(!icon.is_required());
+    assert_eq!(
+        icon.arg_type().unwrap(),
+        ComponentArgType::Optional(Box::new(ComponentArgType::String))
+    );
+    assert_eq!(icon.arg_type().unwrap().as_str(), "string");
+
+    // Literals that are all numbers report the kind of number they hold
+    let one_of = |values: Vec<Value>| ComponentArgType::OneOf(values).as_str();
+    assert_eq!(one_of(vec![Value::from(1), Value::from(2)]), "integer");
+    assert_eq!(one_of(vec![Value::from(1.5), Value::from(2.5)]), "float");
+    assert_eq!(one_of(vec![Value::from(1), Value::from(2.5)]), "number");
+    assert_eq!(one_of(vec![Value::from(1), Value::from("a")]), "literal");
+}
+
 #[test]
 fn test_get_template_variables() {
     let mut tera = Tera::new();
@@ -101,3 +163,55 @@ fn test_get_template_variables() {
         );
     }
 }
+
+#[test]
+fn test_get_components_and_templates() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "base.html",
+            "{% block content %}{% endblock %}{% block footer %}{% endblock %}",
+        ),
+        (
+            "layout.html",
+            r#"{% extends "base.html" %}{% block content %}{% include "nav.txt" %}{% endblock %}"#,
+        ),
+        (
+            "page.html",
+            r#"{% extends "layout.html" %}{% block footer %}{{<Icon name="x" />}}{% endblock %}"#,
+        ),
+        ("nav.txt", "{% component Icon(name: string) %}{{ name }}{% endcomponent Icon %}\n{% component Link(url) %}{{ url }}{% endcomponent Link %}"),
+    ])
+    .unwrap();
+
+    let mut components: Vec<_> = tera
+        .get_components()
+        .map(|(tpl, symbol, info)| (tpl, symbol.span().start_line, info.name().to_string()))
+        .collect();
+    components.sort();
+    assert_eq!(
+        components,
+        vec![
+            ("nav.txt", 1, "Icon".to_string()),
+            ("nav.txt", 2, "Link".to_string())
+        ]
+    );
+
+    let page = tera.get_template_info("page.html").unwrap();
+    assert_eq!(page.name(), "page.html");
+    assert!(page.path().is_none());
+    assert_eq!(page.parents(), ["base.html", "layout.html"]);
+    assert!(page.autoescape());
+    let blocks: Vec<_> = page.symbols().blocks().iter().map(|b| b.name()).collect();
+    assert_eq!(blocks, ["footer"]);
+
+    let nav = tera.get_template_info("nav.txt").unwrap();
+    assert!(!nav.autoescape());
+    assert!(nav.parents().is_empty());
+    assert_eq!(nav.symbols().components().len(), 2);
+
+    let mut names: Vec<_> = tera.get_templates().map(|t| t.name().to_string()).collect();
+    names.sort();
+    assert_eq!(names, ["base.html", "layout.html", "nav.txt", "page.html"]);
+    assert!(tera.get_template_info("missing.html").is_err());
+}